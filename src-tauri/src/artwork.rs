@@ -0,0 +1,160 @@
+//! 埋め込むアートワーク画像の正規化（形式の判定・JPEG への変換・縮小・再圧縮）
+
+use std::path::Path;
+use std::process::Stdio;
+
+use tokio::process::Command;
+
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// 未指定のときの最大の幅・高さ（px）
+const DEFAULT_MAX_DIMENSION: u32 = 1500;
+/// 未指定のときの最大サイズ（1 MiB）。埋め込み画像は全トラックに複製されるため小さめにする
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024;
+/// 再圧縮する JPEG の画質（FFmpeg の -q:v。小さいほど高画質）。上限に収まる最も高い画質を二分探索するため、
+/// 7 段階なら多くても 3 回のエンコードで決まる
+const JPEG_QUALITIES: [u32; 7] = [2, 4, 7, 11, 16, 23, 31];
+
+/// 埋め込み画像の上限（0 は無制限）
+#[derive(Debug, Clone, Copy)]
+pub struct ArtworkLimits {
+    pub max_dimension: u32,
+    pub max_bytes: u64,
+}
+
+impl Default for ArtworkLimits {
+    fn default() -> Self {
+        Self {
+            max_dimension: DEFAULT_MAX_DIMENSION,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl ArtworkLimits {
+    pub fn from_settings(max_dimension: Option<u32>, max_bytes: Option<u64>) -> Self {
+        Self {
+            max_dimension: max_dimension.unwrap_or(DEFAULT_MAX_DIMENSION),
+            max_bytes: max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+        }
+    }
+}
+
+/// 出力形式にそのまま埋め込める画像か（ID3v2 / FLAC / Vorbis は任意の MIME を書けるが、
+/// 多くのプレイヤーが表示できるのは JPEG と PNG だけで、MP4 の covr は WebP を表せない）
+fn is_embeddable(mime: &str) -> bool {
+    matches!(mime, "image/jpeg" | "image/png")
+}
+
+/// 変換した画像
+#[derive(Debug, Clone)]
+pub struct NormalizedArtwork {
+    pub data: Vec<u8>,
+    pub mime: &'static str,
+}
+
+/// 画像を埋め込める形式・上限に収める。data は path の内容で、元の画像をそのまま使える場合は None を返す。
+/// PNG は縮小だけで上限に収まれば PNG のままにし、それ以外は FFmpeg で縮小して JPEG にする。
+/// JPEG の画質は上限のサイズに収まる最も高いものを二分探索で探す（最低画質でも収まらなければ最も小さいもの。
+/// それでも元の画像の方が小さく、元の画像が埋め込める形式・寸法なら元の画像を使う）
+pub async fn normalize(data: &[u8], path: &Path, limits: ArtworkLimits) -> Result<Option<NormalizedArtwork>, String> {
+    let info = crate::utils::sniff_image(data);
+    let too_wide = limits.max_dimension > 0
+        && (info.width.unwrap_or(0) > limits.max_dimension || info.height.unwrap_or(0) > limits.max_dimension);
+    let fits = |len: usize| limits.max_bytes == 0 || len as u64 <= limits.max_bytes;
+    if is_embeddable(info.mime) && !too_wide && fits(data.len()) {
+        return Ok(None);
+    }
+
+    // 透過や線画を保つため、PNG は縮小だけで済めば PNG のままにする（縮小しない再エンコードではほぼ小さくならない）
+    if info.mime == "image/png" && too_wide {
+        let png = encode_image(path, limits.max_dimension, &["-c:v", "png"]).await?;
+        if fits(png.len()) {
+            return Ok(Some(NormalizedArtwork { data: png, mime: "image/png" }));
+        }
+    }
+
+    // 画質が下がるほど小さくなるため、収まる最も高い画質を二分探索する
+    let mut fitting: Option<Vec<u8>> = None;
+    let mut smallest: Option<Vec<u8>> = None;
+    let (mut low, mut high) = (0, JPEG_QUALITIES.len());
+    while low < high {
+        let mid = (low + high) / 2;
+        let encoded = encode_jpeg(path, limits.max_dimension, JPEG_QUALITIES[mid]).await?;
+        if fits(encoded.len()) {
+            fitting = Some(encoded);
+            // 上限が無ければ最高画質で決まり
+            if limits.max_bytes == 0 {
+                break;
+            }
+            high = mid;
+        } else {
+            if smallest.as_ref().map(Vec::len).unwrap_or(usize::MAX) > encoded.len() {
+                smallest = Some(encoded);
+            }
+            low = mid + 1;
+        }
+    }
+
+    let Some(jpeg) = fitting.or(smallest) else { return Ok(None) };
+    if is_embeddable(info.mime) && !too_wide && jpeg.len() >= data.len() {
+        return Ok(None);
+    }
+    Ok(Some(NormalizedArtwork { data: jpeg, mime: "image/jpeg" }))
+}
+
+/// FFmpeg で 1 枚の JPEG にする（max_dimension に収まるよう縦横比を保って縮小する。拡大はしない）
+pub(crate) async fn encode_jpeg(path: &Path, max_dimension: u32, quality: u32) -> Result<Vec<u8>, String> {
+    let quality = quality.to_string();
+    encode_image(path, max_dimension, &["-pix_fmt", "yuvj420p", "-c:v", "mjpeg", "-q:v", &quality]).await
+}
+
+/// FFmpeg で 1 枚の画像にする（codec_args で形式を指定する。縮小は encode_jpeg と同じ）
+async fn encode_image(path: &Path, max_dimension: u32, codec_args: &[&str]) -> Result<Vec<u8>, String> {
+    let ffmpeg_path = crate::system_check::get_ffmpeg_path()
+        .await
+        .unwrap_or_else(|| std::path::PathBuf::from("ffmpeg"));
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.kill_on_drop(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let mut args: Vec<String> = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        crate::path_utils::prepare_cmd_arg(&path.to_string_lossy()),
+        "-frames:v".to_string(),
+        "1".to_string(),
+    ];
+    if max_dimension > 0 {
+        args.extend([
+            "-vf".to_string(),
+            format!(
+                "scale=w='min(iw,{0})':h='min(ih,{0})':force_original_aspect_ratio=decrease",
+                max_dimension
+            ),
+        ]);
+    }
+    args.extend(codec_args.iter().map(|arg| arg.to_string()));
+    args.extend(["-f", "image2pipe", "-"].map(String::from));
+
+    let output = cmd
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| format!("ffmpegの実行に失敗しました: {}", e))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(format!(
+            "画像の変換に失敗しました: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
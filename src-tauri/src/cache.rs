@@ -1,49 +1,473 @@
 use base64::prelude::*;
-use std::{fs, path::Path};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use walkdir::WalkDir;
 
-use crate::utils::sanitize_filename;
+use crate::models::{AlbumArt, CacheEntry, CacheKindUsage, CacheUsage};
+use crate::path_utils::to_extended_length_path_if_needed;
+use crate::utils::sniff_image;
 
-#[tauri::command]
-pub async fn save_album_art_to_cache(
-    base64_data: String,
-    album_title: String,
-    album_artist: String,
-) -> Result<String, String> {
-    // キャッシュディレクトリのパスを取得
-    let home_dir = std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .map_err(|_| "ホームディレクトリの取得に失敗しました")?;
+/// 索引に登録して追い出しの対象にするディレクトリ。album_art は以前の形式（アルバム名_アーティスト名）の
+/// ファイルで、新しくは書かないが残っているものを索引に取り込んで追い出せるようにする
+const INDEXED_KINDS: [&str; 3] = ["artwork", "thumbnails", "album_art"];
+/// clear_cache で消せるディレクトリ（staging は ZIP の展開先で、使用中のことがあるため索引には載せない）
+const CLEARABLE_KINDS: [&str; 4] = ["artwork", "thumbnails", "album_art", "staging"];
+const INDEX_FILE: &str = "index.json";
+/// 未指定のときの索引のファイルの合計の上限（MiB）
+const DEFAULT_MAX_MB: u64 = 512;
+/// 最終アクセス時刻はこれより細かく更新しない（読み込みのたびに索引を書き直さないように）
+const TOUCH_INTERVAL_SECS: u64 = 60;
+
+static APP_CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+static INDEX: OnceLock<Mutex<CacheIndex>> = OnceLock::new();
+
+/// キャッシュの索引（<ルート>/index.json）。サイズと最終アクセス時刻を持ち、LRU の追い出しに使う
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    /// キーはルートからの相対パス（"artwork/<hash>.jpg"）
+    #[serde(default)]
+    entries: HashMap<String, IndexEntry>,
+    /// 読み込んだルート（ルートが変わったら読み直す）
+    #[serde(skip)]
+    root: Option<PathBuf>,
+    /// この起動中に保存・参照したファイル。パスをフロントエンドに返したり変換の入力にしたりしているため追い出さない
+    #[serde(skip)]
+    session: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    kind: String,
+    size: u64,
+    #[serde(default)]
+    label: Option<String>,
+    created_at: u64,
+    last_accessed_at: u64,
+}
+
+impl CacheIndex {
+    /// 索引を読み込み、ディスク上のファイルと突き合わせる（消えたファイルを除き、索引に無いファイルを取り込む）
+    fn load(root: &Path) -> Self {
+        let mut index: CacheIndex = fs::read(to_extended_length_path_if_needed(root.join(INDEX_FILE)))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        index.root = Some(root.to_path_buf());
+
+        let mut files = HashMap::new();
+        for kind in INDEXED_KINDS {
+            let Ok(dir) = fs::read_dir(to_extended_length_path_if_needed(root.join(kind))) else {
+                continue;
+            };
+            for entry in dir.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // 書き込み途中の一時ファイル（.<name>.<pid>.tmp）は除く
+                if name.starts_with('.') {
+                    continue;
+                }
+                let Ok(meta) = entry.metadata() else { continue };
+                if !meta.is_file() {
+                    continue;
+                }
+                let modified = meta.modified().map(unix_secs).unwrap_or_else(|_| now());
+                files.insert(format!("{}/{}", kind, name), (kind, meta.len(), modified));
+            }
+        }
+
+        index.entries.retain(|key, _| files.contains_key(key));
+        for (key, (kind, size, modified)) in files {
+            index
+                .entries
+                .entry(key)
+                .and_modify(|entry| entry.size = size)
+                .or_insert_with(|| IndexEntry {
+                    kind: kind.to_string(),
+                    size,
+                    label: None,
+                    created_at: modified,
+                    last_accessed_at: modified,
+                });
+        }
+        index
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(root) = &self.root else { return Ok(()) };
+        let data = serde_json::to_vec(self).map_err(|e| format!("キャッシュの索引の作成に失敗しました: {}", e))?;
+        crate::path_utils::create_dir_all_extended(root)
+            .and_then(|_| write_atomic(&root.join(INDEX_FILE), &data))
+            .map_err(|e| format!("キャッシュの索引の書き込みに失敗しました: {}", e))
+    }
+
+    /// ファイルを登録する（既にあれば最終アクセス時刻を更新する）。索引が変わったら true
+    fn touch(&mut self, key: &str, kind: &str, size: u64, label: Option<&str>) -> bool {
+        self.session.insert(key.to_string());
+        let now = now();
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                let mut changed = false;
+                if label.is_some() && entry.label.as_deref() != label {
+                    entry.label = label.map(str::to_string);
+                    changed = true;
+                }
+                if now.saturating_sub(entry.last_accessed_at) >= TOUCH_INTERVAL_SECS {
+                    entry.last_accessed_at = now;
+                    changed = true;
+                }
+                changed
+            }
+            None => {
+                self.entries.insert(
+                    key.to_string(),
+                    IndexEntry {
+                        kind: kind.to_string(),
+                        size,
+                        label: label.map(str::to_string),
+                        created_at: now,
+                        last_accessed_at: now,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// 合計が上限を超えていれば、最近使われていないものから消す。この起動中に使ったものは消さないため、
+    /// それだけで上限を超える場合は次回の起動まで上限を超えたままになる。消したものがあれば true
+    fn evict(&mut self) -> bool {
+        let max_bytes = max_cache_bytes();
+        let mut total: u64 = self.entries.values().map(|entry| entry.size).sum();
+        if max_bytes == 0 || total <= max_bytes {
+            return false;
+        }
+        let Some(root) = self.root.clone() else { return false };
+
+        let mut candidates: Vec<(String, u64)> = self
+            .entries
+            .iter()
+            .filter(|(key, _)| !self.session.contains(key.as_str()))
+            .map(|(key, entry)| (key.clone(), entry.last_accessed_at))
+            .collect();
+        candidates.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+        let mut evicted = false;
+        for (key, _) in candidates {
+            if total <= max_bytes {
+                break;
+            }
+            match fs::remove_file(to_extended_length_path_if_needed(entry_path(&root, &key))) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                // 使用中などで消せなければ残す（次の追い出しで再び試す）
+                Err(_) => continue,
+            }
+            if let Some(entry) = self.entries.remove(&key) {
+                total = total.saturating_sub(entry.size);
+                evicted = true;
+            }
+        }
+        evicted
+    }
+}
 
-    let cache_dir = Path::new(&home_dir)
-        .join(".cache")
-        .join("VoiceTagEditor")
-        .join("album_art");
+/// Tauri のアプリ用キャッシュディレクトリをルートにする（setup で呼ぶ）
+pub(crate) fn set_app_cache_dir(dir: PathBuf) {
+    let _ = APP_CACHE_DIR.set(dir);
+}
+
+/// キャッシュのルートディレクトリ。Tauri のアプリ用キャッシュディレクトリを使い、
+/// 設定前なら $XDG_CACHE_HOME/VoiceTagEditor、それも無ければ ~/.cache/VoiceTagEditor
+pub(crate) fn cache_root() -> Result<PathBuf, String> {
+    if let Some(dir) = APP_CACHE_DIR.get() {
+        return Ok(dir.clone());
+    }
+    // XDG Base Directory の仕様どおり、相対パスは無視する
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok(dir.join("VoiceTagEditor"));
+    }
+
+    legacy_cache_root().ok_or_else(|| "ホームディレクトリの取得に失敗しました".to_string())
+}
+
+/// 以前のバージョンのキャッシュのルート（~/.cache/VoiceTagEditor）。
+/// Tauri のアプリ用キャッシュディレクトリに移った後は使わないため、clear_cache で一緒に消す
+fn legacy_cache_root() -> Option<PathBuf> {
+    let home_dir = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home_dir).join(".cache").join("VoiceTagEditor"))
+}
+
+/// 画像を内容の SHA-256 をファイル名にしてキャッシュに保存する。既に同じ画像があれば書き込まない
+pub(crate) fn store_artwork(image_data: &[u8]) -> Result<AlbumArt, String> {
+    store_image(image_data, None)
+}
+
+fn store_image(image_data: &[u8], label: Option<&str>) -> Result<AlbumArt, String> {
+    if image_data.is_empty() {
+        return Err("画像データが空です".to_string());
+    }
+
+    let hash: String = Sha256::digest(image_data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let info = sniff_image(image_data);
+
+    let file_path = store_file("artwork", &format!("{}.{}", hash, info.extension), image_data, label)?;
+
+    Ok(AlbumArt {
+        hash,
+        path: file_path.to_string_lossy().to_string(),
+        mime: info.mime.to_string(),
+        width: info.width,
+        height: info.height,
+        size: image_data.len() as u64,
+    })
+}
+
+/// サムネイル（thumbnails/<key>.jpg）がキャッシュにあればパスを返す
+pub(crate) fn cached_thumbnail(key: &str) -> Option<String> {
+    let root = cache_root().ok()?;
+    let file_name = format!("{}.jpg", key);
+    let file_path = root.join("thumbnails").join(&file_name);
+
+    // 追い出しと入れ違いにならないよう、存在の確認も索引のロック内で行う
+    let mut index = lock_index(&root);
+    let meta = fs::metadata(to_extended_length_path_if_needed(&file_path)).ok()?;
+    if index.touch(&format!("thumbnails/{}", file_name), "thumbnails", meta.len(), None) {
+        let _ = index.save();
+    }
+    Some(file_path.to_string_lossy().to_string())
+}
+
+/// サムネイルをキャッシュに保存する。key は元の画像から決めたもの（thumbnail::thumbnail_key）
+pub(crate) fn store_thumbnail(key: &str, jpeg: &[u8]) -> Result<String, String> {
+    store_file("thumbnails", &format!("{}.jpg", key), jpeg, None).map(|path| path.to_string_lossy().to_string())
+}
 
-    // キャッシュディレクトリを作成
+/// キャッシュの kind ディレクトリにファイルを保存して索引に登録する。既に同じ名前のファイルがあれば書き込まない
+fn store_file(kind: &str, file_name: &str, data: &[u8], label: Option<&str>) -> Result<PathBuf, String> {
+    let root = cache_root()?;
+    let cache_dir = root.join(kind);
     crate::path_utils::create_dir_all_extended(&cache_dir)
         .map_err(|e| format!("キャッシュディレクトリの作成に失敗しました: {}", e))?;
 
-    // ファイル名を生成（アルバム名とアーティスト名から）
-    let file_name = format!(
-        "{}_{}.jpg",
-        sanitize_filename(&album_title),
-        sanitize_filename(&album_artist)
-    );
-
     let file_path = cache_dir.join(file_name);
+    let key = format!("{}/{}", kind, file_name);
+
+    // 書き込みと索引の更新・追い出しを索引のロック内で行い、追い出し中のファイルを返さないようにする
+    let mut index = lock_index(&root);
+    if !to_extended_length_path_if_needed(&file_path).exists() {
+        write_atomic(&file_path, data).map_err(|e| format!("キャッシュの書き込みに失敗しました: {}", e))?;
+    }
+    let changed = index.touch(&key, kind, data.len() as u64, label);
+    if index.evict() || changed {
+        // 索引が書けなくてもファイルは使えるため無視する（次に読み込むときにディスクから作り直す）
+        let _ = index.save();
+    }
+    drop(index);
+
+    Ok(file_path)
+}
 
+/// フロントエンドで選んだ画像をアルバムのアートワークとしてキャッシュに保存する。
+/// ファイル名は内容のハッシュで、アルバム名とアーティスト名は索引のラベルとして残す。
+/// WebP などの埋め込めない形式や大きすぎる画像は縮小する（PNG は縮小だけで収まれば PNG、それ以外は JPEG）
+#[tauri::command]
+pub async fn save_album_art_to_cache(
+    base64_data: String,
+    album_title: String,
+    album_artist: String,
+) -> Result<String, String> {
     // Base64データをデコード
     let image_data = BASE64_STANDARD
         .decode(&base64_data)
         .map_err(|e| format!("Base64デコードに失敗しました: {}", e))?;
 
-    // ファイルに書き込み
-    {
-        let ep = crate::path_utils::to_extended_length_path_if_needed(&file_path);
-        std::fs::write(&ep, image_data)
-    }
-        .map_err(|e| format!("ファイルの書き込みに失敗しました: {}", e))?;
+    let label = [album_title.trim(), album_artist.trim()]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ");
+    let label = (!label.is_empty()).then_some(label.as_str());
+
+    // FFmpeg は元のファイルから変換するため、先に保存してから正規化する。
+    // 元の画像は他で使われている可能性があるので消さず、追い出しに任せる
+    let original = store_image(&image_data, label)?;
+    let normalized = crate::artwork::normalize(
+        &image_data,
+        Path::new(&original.path),
+        crate::artwork::ArtworkLimits::default(),
+    )
+    .await?;
+    let stored = match normalized {
+        Some(artwork) => store_image(&artwork.data, label)?,
+        None => original,
+    };
 
     // パスを文字列として返す
-    Ok(file_path.to_string_lossy().to_string())
+    Ok(stored.path)
+}
+
+/// 索引に登録されたキャッシュのファイルを、最近使われたものから順に返す。kind を指定すればその種類だけ
+#[tauri::command]
+pub async fn list_cache(kind: Option<String>) -> Result<Vec<CacheEntry>, String> {
+    let root = cache_root()?;
+    let index = lock_index(&root);
+
+    let mut entries: Vec<CacheEntry> = index
+        .entries
+        .iter()
+        .filter(|(_, entry)| kind.as_deref().map(|kind| entry.kind == kind).unwrap_or(true))
+        .map(|(key, entry)| CacheEntry {
+            path: entry_path(&root, key).to_string_lossy().to_string(),
+            kind: entry.kind.clone(),
+            size: entry.size,
+            label: entry.label.clone(),
+            created_at: entry.created_at,
+            last_accessed_at: entry.last_accessed_at,
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.last_accessed_at
+            .cmp(&a.last_accessed_at)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(entries)
+}
+
+/// キャッシュの使用量を種類ごとに返す
+#[tauri::command]
+pub async fn cache_usage() -> Result<CacheUsage, String> {
+    Ok(usage(&cache_root()?))
+}
+
+/// キャッシュを消して、消した後の使用量を返す。
+/// kind を指定すればその種類（"artwork" / "thumbnails" / "album_art" / "staging"）だけ。
+/// 以前のバージョンのキャッシュ（~/.cache/VoiceTagEditor）が残っていれば、そちらも同じ種類を消す
+#[tauri::command]
+pub async fn clear_cache(kind: Option<String>) -> Result<CacheUsage, String> {
+    let kinds: Vec<&str> = match kind.as_deref() {
+        None => CLEARABLE_KINDS.to_vec(),
+        Some(kind) => vec![*CLEARABLE_KINDS
+            .iter()
+            .find(|k| **k == kind)
+            .ok_or_else(|| format!("不明なキャッシュの種類です: {}", kind))?],
+    };
+
+    let root = cache_root()?;
+    let legacy_root = legacy_cache_root().filter(|legacy| *legacy != root);
+    let mut index = lock_index(&root);
+    let mut error = None;
+    for dir in std::iter::once(&root).chain(legacy_root.as_ref()) {
+        for kind in &kinds {
+            match fs::remove_dir_all(to_extended_length_path_if_needed(dir.join(kind))) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    error.get_or_insert(format!("キャッシュの削除に失敗しました: {}", e));
+                }
+            }
+        }
+    }
+    if let Some(legacy_root) = &legacy_root {
+        // 以前のキャッシュの索引を消し、空になったディレクトリごと消す（他のファイルが残っていれば残す）
+        if kind.is_none() {
+            let _ = fs::remove_file(to_extended_length_path_if_needed(legacy_root.join(INDEX_FILE)));
+        }
+        let _ = fs::remove_dir(to_extended_length_path_if_needed(legacy_root));
+    }
+    // 一部が消せなかった場合も索引をディスク上の状態に合わせる
+    *index = CacheIndex::load(&root);
+    index.save()?;
+    drop(index);
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(usage(&root)),
+    }
+}
+
+fn usage(root: &Path) -> CacheUsage {
+    let kinds: Vec<CacheKindUsage> = CLEARABLE_KINDS
+        .iter()
+        .map(|kind| {
+            let (files, bytes) = WalkDir::new(to_extended_length_path_if_needed(root.join(kind)))
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_file())
+                .filter_map(|entry| entry.metadata().ok())
+                .fold((0, 0), |(files, bytes), meta| (files + 1, bytes + meta.len()));
+            CacheKindUsage {
+                kind: kind.to_string(),
+                files,
+                bytes,
+            }
+        })
+        .collect();
+
+    CacheUsage {
+        root: root.to_string_lossy().to_string(),
+        total_bytes: kinds.iter().map(|kind| kind.bytes).sum(),
+        max_bytes: max_cache_bytes(),
+        kinds,
+    }
+}
+
+/// 索引をロックする。まだ読み込んでいないかルートが変わっていれば読み込む
+fn lock_index(root: &Path) -> MutexGuard<'static, CacheIndex> {
+    let mut index = INDEX
+        .get_or_init(|| Mutex::new(CacheIndex::default()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if index.root.as_deref() != Some(root) {
+        *index = CacheIndex::load(root);
+    }
+    index
+}
+
+/// 索引のキー（"/" 区切りの相対パス）をパスにする（拡張パスでは "/" が区切りにならないため 1 つずつ join する）
+fn entry_path(root: &Path, key: &str) -> PathBuf {
+    key.split('/').fold(root.to_path_buf(), |path, part| path.join(part))
+}
+
+/// 索引のファイルの合計の上限（VTE_CACHE_MAX_MB で変更できる。0 は無制限）
+fn max_cache_bytes() -> u64 {
+    std::env::var("VTE_CACHE_MAX_MB")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_MAX_MB)
+        .saturating_mul(1024 * 1024)
+}
+
+/// 一時ファイルに書いてから rename する（並行して読まれても書き込み途中の内容が見えないように）
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+    let temp_ep = to_extended_length_path_if_needed(&temp_path);
+    fs::write(&temp_ep, data)
+        .and_then(|_| fs::rename(&temp_ep, to_extended_length_path_if_needed(path)))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp_ep);
+        })
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn now() -> u64 {
+    unix_secs(SystemTime::now())
 }
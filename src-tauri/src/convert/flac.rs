@@ -1,4 +1,5 @@
 use crate::models::{ConvertAlbumData, ConvertOutputSettings, ConvertTrack};
+use crate::tags::{StandardField, TagFormat};
 
 // FFmpegでのFLAC出力は可逆圧縮のため、典型的には -compression_level でコントロール
 // 0(速い/大きい)〜12(遅い/小さい)。一般には 5〜8 が現実的。
@@ -13,6 +14,7 @@ pub fn append_format_specific_args(
     album_data: &ConvertAlbumData,
     output_settings: &ConvertOutputSettings,
     artwork_input_path: Option<&str>,
+    audio_copy: bool,
 ) {
     // FLACはVorbisComment。画像の埋め込みは -map で追加可能だが、
     // attached_pic はMP3/M4A向けの概念。FLACではMETADATA_BLOCK_PICTUREを使う。
@@ -60,17 +62,23 @@ pub fn append_format_specific_args(
         format!("TRACKNUMBER={}", track.track_number),
         "-metadata".to_string(),
         format!("DISCNUMBER={}", track.disk_number),
+        // 総数が無い場合も空文字で渡し、元ファイルの総数を引き継がない
         "-metadata".to_string(),
-        format!("DATE={}", album_data.release_date),
+        format!("TRACKTOTAL={}", track.track_total.map(|t| t.to_string()).unwrap_or_default()),
+        "-metadata".to_string(),
+        format!("DISCTOTAL={}", track.disk_total.map(|t| t.to_string()).unwrap_or_default()),
         "-metadata".to_string(),
-        format!("GENRE={}", album_data.tags.join(", ")),
+        format!("DATE={}", album_data.release_date),
     ]);
 
-    if !track.artists.is_empty() {
-        ffmpeg_args.extend(vec![
-            "-metadata".to_string(),
-            format!("ARTIST={}", track.artists.join(";")),
-        ]);
+    // 複数のアーティスト・ジャンルは 1 つの文字列になってしまうため、変換後に複数の値として書き込む
+    if let [artist] = track.artists.as_slice() {
+        ffmpeg_args.extend(vec!["-metadata".to_string(), format!("ARTIST={}", artist)]);
+    }
+    // ジャンルが空なら空文字で渡し、元ファイルのジャンルを引き継がない
+    if album_data.tags.len() <= 1 {
+        let genre = album_data.tags.first().map(String::as_str).unwrap_or_default();
+        ffmpeg_args.extend(vec!["-metadata".to_string(), format!("GENRE={}", genre)]);
     }
 
     if !album_data.tags.is_empty() {
@@ -81,6 +89,12 @@ pub fn append_format_specific_args(
         ]);
     }
 
+    // 元がFLACならストリームコピー（圧縮レベルは元ファイルのまま）
+    if audio_copy {
+        ffmpeg_args.extend(vec!["-c:a".to_string(), "copy".to_string()]);
+        return;
+    }
+
     // エンコードコーデック
     ffmpeg_args.extend(vec![
         "-c:a".to_string(),
@@ -95,4 +109,7 @@ pub fn append_format_specific_args(
     ffmpeg_args.extend(vec!["-compression_level".to_string(), level.to_string()]);
 }
 
-
+/// 拡張タグを渡す `-metadata` のキー。Vorbis コメントはキーがそのまま書かれる
+pub fn metadata_key(field: StandardField) -> Option<&'static str> {
+    field.frame_ids(TagFormat::VorbisComment).first().copied()
+}
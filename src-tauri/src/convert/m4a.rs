@@ -1,4 +1,6 @@
 use crate::models::{ConvertAlbumData, ConvertOutputSettings, ConvertTrack};
+use crate::tags::StandardField;
+use crate::utils::format_number_pair;
 
 pub fn append_format_specific_args(
     ffmpeg_args: &mut Vec<String>,
@@ -6,6 +8,7 @@ pub fn append_format_specific_args(
     track: &ConvertTrack,
     album_data: &ConvertAlbumData,
     output_settings: &ConvertOutputSettings,
+    audio_copy: bool,
 ) {
     // Map audio and optional cover art
     if artwork_input_added {
@@ -32,21 +35,27 @@ pub fn append_format_specific_args(
         "-metadata".to_string(),
         format!("album_artist={}", album_data.album_artist),
         "-metadata".to_string(),
-        format!("track={}", track.track_number),
+        format!("track={}", format_number_pair(&track.track_number, track.track_total)),
         "-metadata".to_string(),
-        format!("disc={}", track.disk_number),
+        format!("disc={}", format_number_pair(&track.disk_number, track.disk_total)),
         "-metadata".to_string(),
         format!("date={}", album_data.release_date),
-        "-metadata".to_string(),
-        // Join multi-value tags as semicolon
-        format!("genre={}", album_data.tags.join(";")),
     ]);
 
-    if !track.artists.is_empty() {
-        ffmpeg_args.extend(vec![
-            "-metadata".to_string(),
-            format!("artist={}", track.artists.join(";")),
-        ]);
+    // 複数のアーティスト・ジャンルは 1 つの文字列になってしまうため、変換後に複数の値として書き込む
+    if let [artist] = track.artists.as_slice() {
+        ffmpeg_args.extend(vec!["-metadata".to_string(), format!("artist={}", artist)]);
+    }
+    // ジャンルが空なら空文字で渡し、元ファイルのジャンルを引き継がない
+    if album_data.tags.len() <= 1 {
+        let genre = album_data.tags.first().map(String::as_str).unwrap_or_default();
+        ffmpeg_args.extend(vec!["-metadata".to_string(), format!("genre={}", genre)]);
+    }
+
+    // Passthrough when the source is already AAC/ALAC
+    if audio_copy {
+        ffmpeg_args.extend(vec!["-c:a".to_string(), "copy".to_string()]);
+        return;
     }
 
     // Encoder
@@ -65,3 +74,15 @@ pub fn append_format_specific_args(
     }
 }
 
+/// 拡張タグを渡す `-metadata` のキー。FFmpeg の MP4 マクサーが ilst に書けるのは決まったキーだけなので、
+/// フリーフォーム（----）とソート用アトムは None（変換後に直接書き込む）
+pub fn metadata_key(field: StandardField) -> Option<&'static str> {
+    use StandardField::*;
+    match field {
+        Composer => Some("composer"),
+        Copyright => Some("copyright"),
+        Compilation => Some("compilation"),
+        Bpm => Some("tmpo"),
+        _ => None,
+    }
+}
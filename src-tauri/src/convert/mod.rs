@@ -1,9 +1,12 @@
-use std::{fs, path::Path};
+use std::{fs, path::{Path, PathBuf}};
+
+use std::process::Stdio;
 
 use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tokio::process::Command;
 use futures::{stream, StreamExt};
-use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use std::sync::Arc;
 use serde::Deserialize;
 
 #[cfg(windows)]
@@ -11,12 +14,21 @@ const CREATE_NO_WINDOW: u32 = 0x08000000;
 
 mod mp3;
 mod m4a;
+mod flac;
+mod opus;
+mod progress;
+mod template;
 
 use crate::models::{
-    ConvertAlbumData, ConvertError, ConvertOutputSettings, ConvertProgress, ConvertRequest,
-    ConvertResult, ConvertTrack,
+    ConvertAlbumData, ConvertError, ConvertFileReport, ConvertOutputSettings, ConvertRequest,
+    ConvertResult, ConvertTrack, ExtendedTags, JobStartedEvent, OutputPathPreview,
 };
-use crate::utils::sanitize_filename;
+use crate::artwork::ArtworkLimits;
+use crate::jobs::PartialOutput;
+use crate::tags::{FrameValue, Picture, StandardField};
+use crate::utils::{fit_file_name, FilenameTarget};
+use progress::{FfmpegProgressParser, ProgressTracker};
+use template::{OutputTemplate, TemplateContext};
 
 /// ffprobeの出力形式（必要な部分のみ）
 #[derive(Debug, Deserialize)]
@@ -43,10 +55,77 @@ struct FFProbeFormat {
 fn resolve_output_extension(format: &str) -> &'static str {
     match format.to_ascii_uppercase().as_str() {
         "M4A" => "m4a",
+        "FLAC" => "flac",
+        "OPUS" => "opus",
         _ => "mp3",
     }
 }
 
+/// 出力形式ごとに、再エンコードせずそのままコピーできる入力コーデック
+fn is_passthrough_compatible(format: &str, source_codec: &str) -> bool {
+    match format.to_ascii_uppercase().as_str() {
+        "M4A" => matches!(source_codec, "aac" | "alac"),
+        "FLAC" => source_codec == "flac",
+        "OPUS" => source_codec == "opus",
+        "MP3" => source_codec == "mp3",
+        _ => false,
+    }
+}
+
+/// 入力ファイルの情報（パススルー判定と進捗計算に使う）
+#[derive(Default)]
+struct SourceProbe {
+    codec: Option<String>,
+    duration_seconds: Option<f64>,
+}
+
+/// 入力ファイルの最初のオーディオストリームのコーデック名と再生時間を取得する
+async fn probe_source(source_path: &str) -> Option<SourceProbe> {
+    let ffprobe_path = crate::system_check::get_ffprobe_path()
+        .await
+        .unwrap_or_else(|| std::path::PathBuf::from("ffprobe"));
+
+    let mut cmd = Command::new(ffprobe_path);
+    cmd.kill_on_drop(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let output = cmd
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-show_streams",
+            "-show_format",
+            "-select_streams", "a:0",
+            &crate::path_utils::prepare_cmd_arg(source_path),
+        ])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let probe_result: FFProbeOutput = serde_json::from_slice(&output.stdout).ok()?;
+    let codec = probe_result
+        .streams
+        .unwrap_or_default()
+        .into_iter()
+        .find(|s| s.codec_type.as_deref() == Some("audio"))
+        .and_then(|s| s.codec_name);
+    let duration_seconds = probe_result
+        .format
+        .and_then(|f| f.duration)
+        .and_then(|d| d.parse::<f64>().ok())
+        .filter(|d| *d > 0.0);
+
+    Some(SourceProbe { codec, duration_seconds })
+}
+
 fn resolve_artwork_input_path(album_data: &ConvertAlbumData) -> Option<String> {
     if let Some(artwork_path) = &album_data.album_artwork_path {
         let trimmed = artwork_path.trim();
@@ -65,8 +144,52 @@ fn resolve_artwork_input_path(album_data: &ConvertAlbumData) -> Option<String> {
     None
 }
 
+/// album_data.pictures の画像を読み込み、埋め込める形式・大きさにする（種別・説明付き）。
+/// 変換を始める前に 1 回だけ読む
+async fn load_pictures(album_data: &ConvertAlbumData, limits: ArtworkLimits) -> Result<Vec<Picture>, String> {
+    let mut pictures = Vec::with_capacity(album_data.pictures.len());
+    for source in &album_data.pictures {
+        let path = Path::new(source.path.trim());
+        let data = fs::read(crate::path_utils::to_extended_length_path_if_needed(path))
+            .map_err(|e| format!("画像の読み込みに失敗しました（{}）: {}", source.path, e))?;
+        let (data, mime) = match crate::artwork::normalize(&data, path, limits).await? {
+            Some(artwork) => (artwork.data, artwork.mime.to_string()),
+            None => {
+                let mime = source
+                    .mime
+                    .clone()
+                    .filter(|mime| !mime.trim().is_empty())
+                    .unwrap_or_else(|| crate::utils::sniff_image(&data).mime.to_string());
+                (data, mime)
+            }
+        };
+        pictures.push(Picture {
+            picture_type: source.picture_type,
+            mime,
+            description: source.description.clone(),
+            data,
+        });
+    }
+    Ok(pictures)
+}
+
+/// アルバムのアートワークを埋め込める形式・大きさにし、変換した場合はキャッシュに置いたもののパスに差し替える。
+/// 各トラックの変換で同じ画像を何度も変換しないよう、変換を始める前に 1 回だけ行う
+async fn normalize_album_artwork(album_data: &mut ConvertAlbumData, limits: ArtworkLimits) -> Result<(), String> {
+    let Some(artwork_path) = resolve_artwork_input_path(album_data) else { return Ok(()) };
+    let path = Path::new(&artwork_path);
+    let data = fs::read(crate::path_utils::to_extended_length_path_if_needed(path))
+        .map_err(|e| format!("アートワーク画像の読み込みに失敗しました: {}", e))?;
+    if let Some(artwork) = crate::artwork::normalize(&data, path, limits).await? {
+        let normalized = crate::cache::store_artwork(&artwork.data)?;
+        album_data.album_artwork_path = Some(normalized.path);
+        album_data.album_artwork_cache_path = None;
+    }
+    Ok(())
+}
+
 /// ffmpegで生成された出力ファイルが正常かを検証する
-async fn verify_output_file(output_path: &Path) -> Result<(), String> {
+pub(crate) async fn verify_output_file(output_path: &Path) -> Result<(), String> {
     // 1. ファイルの存在チェック
     if !output_path.exists() {
         return Err("出力ファイルが存在しません".to_string());
@@ -92,6 +215,7 @@ async fn verify_output_file(output_path: &Path) -> Result<(), String> {
         .unwrap_or_else(|| std::path::PathBuf::from("ffprobe"));
 
     let mut cmd = Command::new(ffprobe_path);
+    cmd.kill_on_drop(true);
     #[cfg(windows)]
     {
         use std::os::windows::process::CommandExt;
@@ -149,44 +273,86 @@ async fn verify_output_file(output_path: &Path) -> Result<(), String> {
     Ok(())
 }
 
+/// ffmpeg を -progress 付きで実行し、トラック内の進捗を通知しながら終了を待つ
+async fn run_ffmpeg_with_progress(
+    tracker: &ProgressTracker,
+    index: usize,
+    current_file: &str,
+    duration_seconds: Option<f64>,
+    ffmpeg_args: &[String],
+) -> Result<(std::process::ExitStatus, Vec<u8>), String> {
+    let ffmpeg_path = crate::system_check::get_ffmpeg_path()
+        .await
+        .unwrap_or_else(|| std::path::PathBuf::from("ffmpeg"));
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.kill_on_drop(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    let mut child = cmd
+        .args(ffmpeg_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("ffmpegの実行に失敗しました: {}", e))?;
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let progress_reader = async {
+        let Some(stdout) = stdout else { return };
+        let mut lines = BufReader::new(stdout).lines();
+        let mut parser = FfmpegProgressParser::default();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some((out_time, speed)) = parser.push_line(&line) {
+                let fraction = match (out_time, duration_seconds) {
+                    (Some(out_time), Some(duration)) => Some(out_time / duration),
+                    _ => None,
+                };
+                tracker.update_track(index, current_file, fraction, speed);
+            }
+        }
+    };
+
+    // stderr を読み切らないとパイプが詰まって ffmpeg が停止するため、並行して読み込む
+    let stderr_reader = async {
+        let mut buf = Vec::new();
+        if let Some(mut stderr) = stderr {
+            let _ = stderr.read_to_end(&mut buf).await;
+        }
+        buf
+    };
+
+    let (_, stderr_bytes, status) = futures::join!(progress_reader, stderr_reader, child.wait());
+    let status = status.map_err(|e| format!("ffmpegの実行に失敗しました: {}", e))?;
+    Ok((status, stderr_bytes))
+}
+
 async fn convert_single_file(
-    app_handle: &AppHandle,
+    tracker: &ProgressTracker,
+    index: usize,
     track: &ConvertTrack,
+    relative_output_path: &Path,
     album_data: &ConvertAlbumData,
+    pictures: &[Picture],
     output_settings: &ConvertOutputSettings,
-    current: usize,
-    total: usize,
-    finished_counter: &Arc<AtomicUsize>,
-) -> Result<String, String> {
+) -> Result<(String, bool), String> {
     let source_path = &track.source_path;
 
-    let file_extension = resolve_output_extension(&output_settings.format);
-
-    let output_filename = format!(
-        "{:02}-{:02} {}.{}",
-        track
-            .disk_number
-            .parse::<u32>()
-            .unwrap_or(1),
-        track
-            .track_number
-            .parse::<u32>()
-            .unwrap_or(1),
-        sanitize_filename(&track.title),
-        file_extension
-    );
-
-    let album_dir = Path::new(&output_settings.output_path)
-        .join(sanitize_filename(&album_data.album_artist))
-        .join(sanitize_filename(&album_data.album_title));
+    let mut output_path = Path::new(&output_settings.output_path).join(relative_output_path);
+    let album_dir = output_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| Path::new(&output_settings.output_path).to_path_buf());
 
     if !crate::path_utils::path_exists(&album_dir) {
         crate::path_utils::create_dir_all_extended(&album_dir)
             .map_err(|e| format!("出力ディレクトリの作成に失敗しました: {}", e))?;
     }
 
-    let mut output_path = album_dir.join(&output_filename);
-
     if output_path.exists() && output_settings.overwrite_mode == "rename" {
         let mut counter = 1;
         let stem = output_path
@@ -200,8 +366,9 @@ async fn convert_single_file(
             .to_string_lossy()
             .into_owned();
 
+        let target = FilenameTarget::from_setting(output_settings.filename_target.as_deref());
         loop {
-            let new_filename = format!("{}_{}.{}", stem, counter, extension);
+            let new_filename = fit_file_name(&stem, &format!("_{}", counter), &extension, target);
             output_path = album_dir.join(&new_filename);
             if !output_path.exists() {
                 break;
@@ -210,23 +377,29 @@ async fn convert_single_file(
         }
     }
 
-    let progress = ConvertProgress {
-        current,
-        total,
-        current_file: track.title.clone(),
-        status: "processing".to_string(),
-        // 進捗率は完了数ベース（処理開始時点では完了数）
-        progress_percent: (finished_counter.load(Ordering::SeqCst) as f64 / total as f64) * 100.0,
-    };
-    let _ = app_handle.emit("convert-progress", &progress);
+    tracker.start_track(index, &track.title);
 
     let mut ffmpeg_args: Vec<String> = vec![
         "-i".to_string(),
         crate::path_utils::prepare_cmd_arg(source_path),
     ];
 
-    let artwork_input_path = resolve_artwork_input_path(album_data);
-    let artwork_input_added = if let Some(path) = &artwork_input_path {
+    let format_upper = output_settings.format.to_ascii_uppercase();
+
+    let source_probe = probe_source(source_path).await.unwrap_or_default();
+
+    // "copy" 指定時、元のコーデックが出力形式と一致すればストリームコピーする（不一致なら既定品質で変換）
+    let audio_copy = output_settings.quality.eq_ignore_ascii_case("copy")
+        && source_probe
+            .codec
+            .as_deref()
+            .map(|codec| is_passthrough_compatible(&format_upper, codec))
+            .unwrap_or(false);
+
+    // 画像の一覧が指定されている場合は FFmpeg には渡さず、変換後に種別付きですべて書き込む
+    let artwork_input_path = if pictures.is_empty() { resolve_artwork_input_path(album_data) } else { None };
+    // Opus は画像ストリームを受け付けないため、アートワークは METADATA_BLOCK_PICTURE で埋め込む（入力には追加しない）
+    let artwork_input_added = if let Some(path) = artwork_input_path.as_ref().filter(|_| format_upper != "OPUS") {
         ffmpeg_args.push("-i".to_string());
         ffmpeg_args.push(crate::path_utils::prepare_cmd_arg(path));
         true
@@ -237,7 +410,14 @@ async fn convert_single_file(
     // allow overwrite
     ffmpeg_args.push("-y".to_string());
 
-    match output_settings.format.to_ascii_uppercase().as_str() {
+    // 進捗は stdout に key=value 形式で出力させる（stderr は統計表示を抑えてエラー出力のみにする）
+    ffmpeg_args.extend(vec![
+        "-progress".to_string(),
+        "pipe:1".to_string(),
+        "-nostats".to_string(),
+    ]);
+
+    match format_upper.as_str() {
         "M4A" => {
             m4a::append_format_specific_args(
                 &mut ffmpeg_args,
@@ -245,6 +425,28 @@ async fn convert_single_file(
                 track,
                 album_data,
                 output_settings,
+                audio_copy,
+            );
+        }
+        "FLAC" => {
+            flac::append_format_specific_args(
+                &mut ffmpeg_args,
+                artwork_input_added,
+                track,
+                album_data,
+                output_settings,
+                artwork_input_path.as_deref(),
+                audio_copy,
+            );
+        }
+        "OPUS" => {
+            opus::append_format_specific_args(
+                &mut ffmpeg_args,
+                track,
+                album_data,
+                output_settings,
+                artwork_input_path.as_deref(),
+                audio_copy,
             );
         }
         _ => {
@@ -254,49 +456,287 @@ async fn convert_single_file(
                 track,
                 album_data,
                 output_settings,
+                audio_copy,
             );
         }
     }
 
-    ffmpeg_args.push(crate::path_utils::prepare_cmd_arg(&output_path.to_string_lossy()));
+    // M4A は FFmpeg が任意のキーを ilst に書けないため、変換後にフリーフォームとして書き込む
+    let product_id = resolve_product_id(album_data, track);
+    if let Some(product_id) = product_id.as_deref().filter(|_| format_upper != "M4A") {
+        ffmpeg_args.extend(vec![
+            "-metadata".to_string(),
+            format!("{}={}", crate::utils::PRODUCT_ID_TAG, product_id),
+        ]);
+    }
 
-    let ffmpeg_path = crate::system_check::get_ffmpeg_path()
-        .await
-        .unwrap_or_else(|| std::path::PathBuf::from("ffmpeg"));
-    let mut cmd = Command::new(ffmpeg_path);
-    #[cfg(windows)]
-    {
-        use std::os::windows::process::CommandExt;
-        cmd.creation_flags(CREATE_NO_WINDOW);
+    // 拡張タグは FFmpeg が正しいフレームに書けるものだけを -metadata で渡し、残りは変換後に直接書き込む
+    let metadata_key: fn(StandardField) -> Option<&'static str> = match format_upper.as_str() {
+        "M4A" => m4a::metadata_key,
+        "FLAC" => flac::metadata_key,
+        "OPUS" => opus::metadata_key,
+        _ => mp3::metadata_key,
+    };
+    let extended = resolve_extended_tags(album_data, track);
+    let voice_actors = extended.voice_actors.clone().unwrap_or_default();
+    // 声優が 1 人なら他の拡張タグと同じく書き、複数なら変換後に 1 人ずつ別の値として書く
+    let single_voice_actor = (voice_actors.len() == 1).then(|| (StandardField::VoiceActor, voice_actors.first().cloned()));
+    let mut native_fields = Vec::new();
+    for (field, value) in crate::tags::extended_field_values(&extended).into_iter().chain(single_voice_actor) {
+        let Some(value) = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) else { continue };
+        match metadata_key(field) {
+            Some(key) => ffmpeg_args.extend(vec!["-metadata".to_string(), format!("{}={}", key, value)]),
+            None => native_fields.push((field, value)),
+        }
     }
-    let output = cmd
-        .args(&ffmpeg_args)
-        .output()
-        .await
-        .map_err(|e| format!("ffmpegの実行に失敗しました: {}", e))?;
 
-    if !output.status.success() {
-        let error_msg = String::from_utf8_lossy(&output.stderr);
+    // 既存のファイルを上書きする場合も、完成するまで元のファイルに触れないよう一時ファイルに書き出す
+    let temp_path = crate::path_utils::temp_sibling_path(&output_path);
+    let temp_ep = crate::path_utils::to_extended_length_path_if_needed(&temp_path);
+    ffmpeg_args.push(crate::path_utils::prepare_cmd_arg(&temp_path.to_string_lossy()));
+
+    // 失敗・キャンセル時は書きかけの一時ファイルを削除する（既存の出力ファイルはそのまま残る）
+    let partial_output = PartialOutput::new(&temp_path);
+
+    let (status, stderr) = run_ffmpeg_with_progress(
+        tracker,
+        index,
+        &track.title,
+        source_probe.duration_seconds,
+        &ffmpeg_args,
+    )
+    .await?;
+
+    if !status.success() {
+        let error_msg = String::from_utf8_lossy(&stderr);
         return Err(format!("ファイル変換に失敗しました: {}", error_msg));
     }
 
-    // 出力ファイルの検証
-    if let Err(verification_error) = verify_output_file(&output_path).await {
-        // 検証に失敗した場合、不正なファイルを削除
-        let _ = fs::remove_file(&output_path);
+    // 出力ファイルの検証（失敗した場合、不正なファイルは partial_output の破棄で削除される）
+    if let Err(verification_error) = verify_output_file(&temp_ep).await {
         return Err(format!("出力ファイルの検証に失敗しました: {}", verification_error));
     }
 
-    Ok(output_path.to_string_lossy().to_string())
+    // FFmpeg の -metadata では 1 つの文字列になってしまう複数のアーティスト・ジャンル・声優
+    let multi_values: Vec<(StandardField, &[String])> = [
+        (StandardField::Artist, track.artists.as_slice()),
+        (StandardField::Genre, album_data.tags.as_slice()),
+        (StandardField::VoiceActor, voice_actors.as_slice()),
+    ]
+    .into_iter()
+    .filter(|(_, values)| values.len() > 1)
+    .collect();
+
+    let product_id = product_id.filter(|_| format_upper == "M4A");
+    if product_id.is_some() || !native_fields.is_empty() || !multi_values.is_empty() || !pictures.is_empty() {
+        crate::tags::write_file(&temp_path, |block| {
+            if let Some(product_id) = product_id {
+                let id = format!("----:com.apple.iTunes:{}", crate::utils::PRODUCT_ID_TAG);
+                block.set(&id, FrameValue::Text(vec![product_id]));
+            }
+            for (field, value) in &native_fields {
+                block.set_field(*field, value);
+            }
+            for (field, values) in &multi_values {
+                block.set_values(*field, values);
+            }
+            if !pictures.is_empty() {
+                block.remove_pictures();
+                for picture in pictures {
+                    block.add_picture(picture.clone());
+                }
+            }
+        })?;
+    }
+
+    // 検証とタグの書き込みが済んでから出力先に置き換える
+    let output_ep = crate::path_utils::to_extended_length_path_if_needed(&output_path);
+    fs::rename(&temp_ep, &output_ep).map_err(|e| format!("出力ファイルの書き込みに失敗しました: {}", e))?;
+    partial_output.keep();
+    Ok((output_path.to_string_lossy().to_string(), audio_copy))
+}
+
+/// アルバムに指定された商品 ID、無ければ元ファイルのパスから検出した商品 ID
+fn resolve_product_id(album_data: &ConvertAlbumData, track: &ConvertTrack) -> Option<String> {
+    album_data
+        .product_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .or_else(|| crate::utils::detect_product_id(&track.source_path))
+}
+
+/// トラックに指定された拡張タグ。未指定の項目はアルバムの値を使う
+fn resolve_extended_tags(album_data: &ConvertAlbumData, track: &ConvertTrack) -> ExtendedTags {
+    let album = &album_data.extended;
+    let track = &track.extended;
+    let pick = |track: &Option<String>, album: &Option<String>| track.clone().or_else(|| album.clone());
+    ExtendedTags {
+        composer: pick(&track.composer, &album.composer),
+        lyricist: pick(&track.lyricist, &album.lyricist),
+        illustrator: pick(&track.illustrator, &album.illustrator),
+        voice_actors: track.voice_actors.clone().or_else(|| album.voice_actors.clone()),
+        circle: pick(&track.circle, &album.circle),
+        publisher: pick(&track.publisher, &album.publisher),
+        copyright: pick(&track.copyright, &album.copyright),
+        isrc: pick(&track.isrc, &album.isrc),
+        catalog_number: pick(&track.catalog_number, &album.catalog_number),
+        bpm: pick(&track.bpm, &album.bpm),
+        compilation: track.compilation.or(album.compilation),
+        title_sort: pick(&track.title_sort, &album.title_sort),
+        artist_sort: pick(&track.artist_sort, &album.artist_sort),
+        album_sort: pick(&track.album_sort, &album.album_sort),
+        album_artist_sort: pick(&track.album_artist_sort, &album.album_artist_sort),
+        composer_sort: pick(&track.composer_sort, &album.composer_sort),
+    }
+}
+
+/// "3/12" や "03" を番号と総数に分けて track_total / disk_total に入れる（指定済みの総数を優先する）。
+/// auto_totals なら、総数の無いトラックにディスクごとのトラック数と最大のディスク番号を入れる
+fn resolve_track_numbers(tracks: &mut [ConvertTrack], auto_totals: bool) {
+    let split = |number: &mut String, total: &mut Option<u32>| {
+        if let (Some(parsed), parsed_total) = crate::utils::parse_number_pair(number) {
+            *number = parsed.to_string();
+            *total = total.filter(|&t| t > 0).or(parsed_total);
+        }
+    };
+    for track in tracks.iter_mut() {
+        split(&mut track.track_number, &mut track.track_total);
+        split(&mut track.disk_number, &mut track.disk_total);
+    }
+    if !auto_totals {
+        return;
+    }
+
+    let track_totals = template::count_tracks_per_disc(tracks);
+    // 一部のディスクだけを変換する場合もあるため、枚数ではなく最大の番号を総数にする
+    let disc_total = track_totals.keys().copied().max().unwrap_or(1);
+    for track in tracks.iter_mut() {
+        let disc = crate::utils::parse_number(&track.disk_number).unwrap_or(1);
+        if track.track_total.is_none() {
+            track.track_total = track_totals.get(&disc).map(|&count| count as u32);
+        }
+        if track.disk_total.is_none() {
+            track.disk_total = Some(disc_total);
+        }
+    }
+}
+
+/// テンプレートから各トラックの出力パス（出力ディレクトリからの相対パス）を求める
+fn plan_output_paths(
+    tracks: &[ConvertTrack],
+    album_data: &ConvertAlbumData,
+    output_settings: &ConvertOutputSettings,
+) -> Result<Vec<PathBuf>, String> {
+    let template_str = output_settings
+        .output_template
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or(template::DEFAULT_OUTPUT_TEMPLATE);
+    let output_template = OutputTemplate::parse(template_str)?;
+
+    let track_totals = template::count_tracks_per_disc(tracks);
+    let target = FilenameTarget::from_setting(output_settings.filename_target.as_deref());
+    let context = TemplateContext::new(album_data, &track_totals, target);
+    let extension = resolve_output_extension(&output_settings.format);
+
+    tracks
+        .iter()
+        .map(|track| output_template.render(track, &context, extension))
+        .collect()
+}
+
+/// 変換前に出力パスを確認する（テンプレートの検証・プレビュー用）
+#[tauri::command]
+pub async fn preview_output_paths(mut request: ConvertRequest) -> Result<Vec<OutputPathPreview>, String> {
+    resolve_track_numbers(&mut request.tracks, request.auto_totals);
+    let relative_paths = plan_output_paths(&request.tracks, &request.album_data, &request.output_settings)?;
+    let collisions = template::find_collisions(&relative_paths);
+
+    Ok(request
+        .tracks
+        .iter()
+        .zip(relative_paths)
+        .enumerate()
+        .map(|(index, (track, relative_path))| {
+            let output_path = Path::new(&request.output_settings.output_path).join(&relative_path);
+            OutputPathPreview {
+                source_path: track.source_path.clone(),
+                relative_path: relative_path.to_string_lossy().to_string(),
+                exists: crate::path_utils::path_exists(&output_path),
+                output_path: output_path.to_string_lossy().to_string(),
+                collision: collisions.contains(&index),
+            }
+        })
+        .collect())
+}
+
+/// 1トラック分の変換結果
+enum TrackOutcome {
+    Converted(ConvertFileReport),
+    Failed(ConvertError),
+    Cancelled(String),
 }
 
 #[tauri::command]
 pub async fn convert_audio_files(
     app_handle: AppHandle,
-    request: ConvertRequest,
+    mut request: ConvertRequest,
+    job_id: Option<String>,
 ) -> Result<ConvertResult, String> {
     let total = request.tracks.len();
 
+    // 準備（出力パスの確定・アートワークの変換）の間もキャンセルできるよう、最初に登録する
+    let job = crate::jobs::register(job_id);
+    let _ = app_handle.emit(
+        "job-started",
+        &JobStartedEvent {
+            job_id: job.id().to_string(),
+            kind: "convert".to_string(),
+            total,
+        },
+    );
+    if job.is_cancelled() {
+        return Ok(cancelled_result(&job, request.tracks));
+    }
+
+    resolve_track_numbers(&mut request.tracks, request.auto_totals);
+
+    // エンコードを始める前に、全トラックの出力パスを確定して重複を検出する
+    let relative_paths = plan_output_paths(&request.tracks, &request.album_data, &request.output_settings)?;
+    let collisions = template::find_collisions(&relative_paths);
+    if !collisions.is_empty() {
+        let mut indices: Vec<usize> = collisions.into_iter().collect();
+        indices.sort_unstable();
+        let listed = indices
+            .iter()
+            .map(|&i| format!("{} → {}", request.tracks[i].source_path, relative_paths[i].to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(format!("出力パスが重複するトラックがあります:\n{}", listed));
+    }
+
+    let limits = ArtworkLimits::from_settings(
+        request.output_settings.artwork_max_dimension,
+        request.output_settings.artwork_max_bytes,
+    );
+    // アートワークの変換は画像ごとに FFmpeg を起動するため、キャンセルできるようにする
+    let album_data = &mut request.album_data;
+    let prepared = crate::jobs::run_cancellable(&job, async move {
+        let pictures = load_pictures(album_data, limits).await?;
+        if pictures.is_empty() {
+            normalize_album_artwork(album_data, limits).await?;
+        }
+        Ok::<_, String>(pictures)
+    })
+    .await;
+    let pictures = match prepared {
+        Some(pictures) => pictures?,
+        None => return Ok(cancelled_result(&job, request.tracks)),
+    };
+
     let output_dir = Path::new(&request.output_settings.output_path);
     if !crate::path_utils::path_exists(output_dir) {
         crate::path_utils::create_dir_all_extended(output_dir)
@@ -314,57 +754,49 @@ pub async fn convert_audio_files(
         .map(|v| v.clamp(1, 8))
         .unwrap_or(default_concurrency);
 
-    let app_handle = Arc::new(app_handle);
+    let tracker = Arc::new(ProgressTracker::new(app_handle, Arc::clone(&job), total));
+    let pictures = Arc::new(pictures);
     let album_data = Arc::new(request.album_data);
     let output_settings = Arc::new(request.output_settings);
-    let finished_counter = Arc::new(AtomicUsize::new(0));
 
     let mut converted_files: Vec<String> = Vec::new();
     let mut failed_files: Vec<ConvertError> = Vec::new();
+    let mut cancelled_files: Vec<String> = Vec::new();
+    let mut file_reports: Vec<ConvertFileReport> = Vec::new();
 
     // 並列変換
-    let results: Vec<Result<String, (String, String, usize)>> = stream::iter(request.tracks.into_iter().enumerate())
-        .map(|(index, track)| {
-            let app_handle = Arc::clone(&app_handle);
+    let results: Vec<TrackOutcome> = stream::iter(request.tracks.into_iter().zip(relative_paths).enumerate())
+        .map(|(index, (track, relative_path))| {
+            let tracker = Arc::clone(&tracker);
             let album_data = Arc::clone(&album_data);
+            let pictures = Arc::clone(&pictures);
             let output_settings = Arc::clone(&output_settings);
-            let finished_counter = Arc::clone(&finished_counter);
             async move {
-                let current = index + 1;
-                match convert_single_file(
-                    &app_handle,
-                    &track,
-                    &album_data,
-                    &output_settings,
-                    current,
-                    total,
-                    &finished_counter,
+                // キャンセル済みなら未着手のトラックは開始しない。実行中なら ffmpeg/ffprobe ごと中断する
+                let result = crate::jobs::run_cancellable(
+                    tracker.job(),
+                    convert_single_file(&tracker, index, &track, &relative_path, &album_data, &pictures, &output_settings),
                 )
-                .await {
-                    Ok(path) => {
-                        let finished = finished_counter.fetch_add(1, Ordering::SeqCst) + 1;
-                        let progress = ConvertProgress {
-                            current: finished,
-                            total,
-                            current_file: track.title.clone(),
-                            status: "completed".to_string(),
-                            progress_percent: (finished as f64 / total as f64) * 100.0,
-                        };
-                        let _ = app_handle.emit("convert-progress", &progress);
-                        Ok(path)
-                    }
-                    Err(err) => {
-                        let finished = finished_counter.fetch_add(1, Ordering::SeqCst) + 1;
-                        let progress = ConvertProgress {
-                            current: finished,
-                            total,
-                            current_file: track.title.clone(),
-                            status: "error".to_string(),
-                            progress_percent: (finished as f64 / total as f64) * 100.0,
-                        };
-                        let _ = app_handle.emit("convert-progress", &progress);
-                        Err((track.source_path.clone(), err, current))
-                    }
+                .await;
+
+                let status = match &result {
+                    Some(Ok(_)) => "completed",
+                    Some(Err(_)) => "error",
+                    None => "cancelled",
+                };
+                tracker.finish_track(index, &track.title, status);
+
+                match result {
+                    Some(Ok((path, copied))) => TrackOutcome::Converted(ConvertFileReport {
+                        source_path: track.source_path,
+                        output_path: path,
+                        mode: if copied { "copied" } else { "transcoded" }.to_string(),
+                    }),
+                    Some(Err(error_message)) => TrackOutcome::Failed(ConvertError {
+                        source_path: track.source_path,
+                        error_message,
+                    }),
+                    None => TrackOutcome::Cancelled(track.source_path),
                 }
             }
         })
@@ -374,15 +806,107 @@ pub async fn convert_audio_files(
 
     for r in results {
         match r {
-            Ok(path) => converted_files.push(path),
-            Err((source_path, error_message, _current)) => failed_files.push(ConvertError { source_path, error_message }),
+            TrackOutcome::Converted(report) => {
+                converted_files.push(report.output_path.clone());
+                file_reports.push(report);
+            }
+            TrackOutcome::Failed(error) => failed_files.push(error),
+            TrackOutcome::Cancelled(source_path) => cancelled_files.push(source_path),
         }
     }
 
     Ok(ConvertResult {
-        success: failed_files.is_empty(),
+        job_id: job.id().to_string(),
+        success: failed_files.is_empty() && cancelled_files.is_empty(),
+        cancelled: job.is_cancelled(),
         converted_files,
         failed_files,
+        cancelled_files,
+        file_reports,
         total_processed: total,
     })
 }
+
+/// 変換を始める前にキャンセルされた場合の結果（すべてのトラックをキャンセル扱いにする）
+fn cancelled_result(job: &crate::jobs::Job, tracks: Vec<ConvertTrack>) -> ConvertResult {
+    let total = tracks.len();
+    ConvertResult {
+        job_id: job.id().to_string(),
+        success: false,
+        cancelled: true,
+        converted_files: Vec::new(),
+        failed_files: Vec::new(),
+        cancelled_files: tracks.into_iter().map(|track| track.source_path).collect(),
+        file_reports: Vec::new(),
+        total_processed: total,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(disc: &str, number: &str) -> ConvertTrack {
+        ConvertTrack {
+            source_path: String::new(),
+            disk_number: disc.to_string(),
+            track_number: number.to_string(),
+            disk_total: None,
+            track_total: None,
+            title: String::new(),
+            artists: Vec::new(),
+            extended: ExtendedTags::default(),
+        }
+    }
+
+    fn numbers(tracks: &[ConvertTrack]) -> Vec<(&str, Option<u32>, &str, Option<u32>)> {
+        tracks
+            .iter()
+            .map(|t| (t.track_number.as_str(), t.track_total, t.disk_number.as_str(), t.disk_total))
+            .collect()
+    }
+
+    #[test]
+    fn track_numbers_are_split_from_their_totals() {
+        let mut tracks = [track("1/2", "3/12"), track("１", "０３"), track("2", "３／１２"), track("", "3 of 12"), track("1", "A1")];
+        resolve_track_numbers(&mut tracks, false);
+        assert_eq!(
+            numbers(&tracks),
+            [
+                ("3", Some(12), "1", Some(2)),
+                ("3", None, "1", None),
+                ("3", Some(12), "2", None),
+                ("3", Some(12), "", None),
+                // 数字として読めない番号はそのまま
+                ("A1", None, "1", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn explicit_totals_win_and_zero_means_unset() {
+        let mut tracks = [track("1", "3/12"), track("1", "4/0"), track("1", "5/12")];
+        tracks[0].track_total = Some(10);
+        tracks[2].track_total = Some(0);
+        resolve_track_numbers(&mut tracks, false);
+        assert_eq!(numbers(&tracks), [("3", Some(10), "1", None), ("4", None, "1", None), ("5", Some(12), "1", None)]);
+    }
+
+    #[test]
+    fn auto_totals_count_tracks_per_disc() {
+        let mut tracks = [track("1", "1"), track("1", "2"), track("3", "1"), track("", "3"), track("3", "2/5")];
+        tracks[2].disk_total = Some(4);
+        resolve_track_numbers(&mut tracks, true);
+        assert_eq!(
+            numbers(&tracks),
+            [
+                // ディスク番号の無いトラックはディスク 1 として数える
+                ("1", Some(3), "1", Some(3)),
+                ("2", Some(3), "1", Some(3)),
+                ("1", Some(2), "3", Some(4)),
+                ("3", Some(3), "", Some(3)),
+                ("2", Some(5), "3", Some(3)),
+            ]
+        );
+    }
+}
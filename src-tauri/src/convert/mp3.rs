@@ -1,4 +1,6 @@
 use crate::models::{ConvertAlbumData, ConvertOutputSettings, ConvertTrack};
+use crate::tags::StandardField;
+use crate::utils::format_number_pair;
 
 pub fn append_format_specific_args(
     ffmpeg_args: &mut Vec<String>,
@@ -6,6 +8,7 @@ pub fn append_format_specific_args(
     track: &ConvertTrack,
     album_data: &ConvertAlbumData,
     output_settings: &ConvertOutputSettings,
+    audio_copy: bool,
 ) {
     if artwork_input_added {
         ffmpeg_args.extend(vec![
@@ -30,20 +33,21 @@ pub fn append_format_specific_args(
         "-metadata".to_string(),
         format!("album_artist={}", album_data.album_artist),
         "-metadata".to_string(),
-        format!("track={}", track.track_number),
+        format!("track={}", format_number_pair(&track.track_number, track.track_total)),
         "-metadata".to_string(),
-        format!("disc={}", track.disk_number),
+        format!("disc={}", format_number_pair(&track.disk_number, track.disk_total)),
         "-metadata".to_string(),
         format!("date={}", album_data.release_date),
-        "-metadata".to_string(),
-        format!("genre={}", album_data.tags.join(";")),
     ]);
 
-    if !track.artists.is_empty() {
-        ffmpeg_args.extend(vec![
-            "-metadata".to_string(),
-            format!("artist={}", track.artists.join(";")),
-        ]);
+    // 複数のアーティスト・ジャンルは 1 つの文字列になってしまうため、変換後に複数の値として書き込む
+    if let [artist] = track.artists.as_slice() {
+        ffmpeg_args.extend(vec!["-metadata".to_string(), format!("artist={}", artist)]);
+    }
+    // ジャンルが空なら空文字で渡し、元ファイルのジャンルを引き継がない
+    if album_data.tags.len() <= 1 {
+        let genre = album_data.tags.first().map(String::as_str).unwrap_or_default();
+        ffmpeg_args.extend(vec!["-metadata".to_string(), format!("genre={}", genre)]);
     }
 
     if !album_data.tags.is_empty() {
@@ -53,12 +57,20 @@ pub fn append_format_specific_args(
         ]);
     }
 
-    ffmpeg_args.extend(vec![
-        "-c:a".to_string(),
-        "libmp3lame".to_string(),
-        "-id3v2_version".to_string(),
-        "3".to_string(),
-    ]);
+    // 既定は古いプレイヤーでも読める ID3v2.3。複数のアーティストを null 区切りで書きたい場合は 2.4 を選ぶ
+    let id3v2_version = match output_settings.id3v2_version {
+        Some(4) => "4",
+        _ => "3",
+    };
+    ffmpeg_args.extend(vec!["-id3v2_version".to_string(), id3v2_version.to_string()]);
+
+    // 元がMP3ならストリームコピー（再エンコードしない）
+    if audio_copy {
+        ffmpeg_args.extend(vec!["-c:a".to_string(), "copy".to_string()]);
+        return;
+    }
+
+    ffmpeg_args.extend(vec!["-c:a".to_string(), "libmp3lame".to_string()]);
 
     match output_settings.quality.as_str() {
         "320" => ffmpeg_args.extend(vec!["-b:a".to_string(), "320k".to_string()]),
@@ -71,3 +83,22 @@ pub fn append_format_specific_args(
     }
 }
 
+/// 拡張タグを渡す `-metadata` のキー。FFmpeg は ID3v2 のフレーム ID をキーにするとそのフレームに、
+/// それ以外のキーは TXXX（説明 = キー）に書く。ソート用フレームは v2.3 で、TSO2 / TSOC と iTunes の TCMP は
+/// どちらのバージョンでも TXXX になってしまうため None（変換後に直接書き込む）
+pub fn metadata_key(field: StandardField) -> Option<&'static str> {
+    use StandardField::*;
+    match field {
+        Composer => Some("TCOM"),
+        Lyricist => Some("TEXT"),
+        Publisher => Some("TPUB"),
+        Copyright => Some("TCOP"),
+        Isrc => Some("TSRC"),
+        Bpm => Some("TBPM"),
+        Illustrator => Some("ILLUSTRATOR"),
+        VoiceActor => Some("VOICE_ACTOR"),
+        Circle => Some("LABEL"),
+        CatalogNumber => Some("CATALOGNUMBER"),
+        _ => None,
+    }
+}
@@ -1,6 +1,5 @@
 use crate::models::{ConvertAlbumData, ConvertOutputSettings, ConvertTrack};
-use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
-use base64::Engine;
+use crate::tags::{StandardField, TagFormat};
 use std::fs;
 use std::path::Path;
 
@@ -10,6 +9,7 @@ pub fn append_format_specific_args(
     album_data: &ConvertAlbumData,
     output_settings: &ConvertOutputSettings,
     artwork_input_path: Option<&str>,
+    audio_copy: bool,
 ) {
     // Ogg Opusはvideo/attached_picストリームを受け付けないため、画像はマッピングしない
     ffmpeg_args.extend(vec!["-map".to_string(), "0:a".to_string()]);
@@ -26,17 +26,23 @@ pub fn append_format_specific_args(
         format!("TRACKNUMBER={}", track.track_number),
         "-metadata".to_string(),
         format!("DISCNUMBER={}", track.disk_number),
+        // 総数が無い場合も空文字で渡し、元ファイルの総数を引き継がない
         "-metadata".to_string(),
-        format!("DATE={}", album_data.release_date),
+        format!("TRACKTOTAL={}", track.track_total.map(|t| t.to_string()).unwrap_or_default()),
+        "-metadata".to_string(),
+        format!("DISCTOTAL={}", track.disk_total.map(|t| t.to_string()).unwrap_or_default()),
         "-metadata".to_string(),
-        format!("GENRE={}", album_data.tags.join(", ")),
+        format!("DATE={}", album_data.release_date),
     ]);
 
-    if !track.artists.is_empty() {
-        ffmpeg_args.extend(vec![
-            "-metadata".to_string(),
-            format!("ARTIST={}", track.artists.join(";")),
-        ]);
+    // 複数のアーティスト・ジャンルは 1 つの文字列になってしまうため、変換後に複数の値として書き込む
+    if let [artist] = track.artists.as_slice() {
+        ffmpeg_args.extend(vec!["-metadata".to_string(), format!("ARTIST={}", artist)]);
+    }
+    // ジャンルが空なら空文字で渡し、元ファイルのジャンルを引き継がない
+    if album_data.tags.len() <= 1 {
+        let genre = album_data.tags.first().map(String::as_str).unwrap_or_default();
+        ffmpeg_args.extend(vec!["-metadata".to_string(), format!("GENRE={}", genre)]);
     }
 
     if !album_data.tags.is_empty() {
@@ -50,40 +56,25 @@ pub fn append_format_specific_args(
     if let Some(img_path) = artwork_input_path {
         if !img_path.trim().is_empty() && crate::path_utils::path_exists(img_path) {
             if let Ok(image_bytes) = fs::read(crate::path_utils::to_extended_length_path_if_needed(Path::new(img_path))) {
-                // picture type = 3 (Cover front)
-                let mime_bytes: &[u8] = if img_path.to_ascii_lowercase().ends_with(".png") {
-                    b"image/png"
-                } else {
-                    b"image/jpeg"
-                };
-                let description: &[u8] = b"";
-                let width: u32 = 0;
-                let height: u32 = 0;
-                let depth: u32 = 24; // bits-per-pixel (unknownでも可)
-                let colors: u32 = 0; // indexed palette colors (0 for non-indexed)
-
-                let mut block: Vec<u8> = Vec::new();
-                block.extend_from_slice(&3u32.to_be_bytes());
-                block.extend_from_slice(&(mime_bytes.len() as u32).to_be_bytes());
-                block.extend_from_slice(mime_bytes);
-                block.extend_from_slice(&(description.len() as u32).to_be_bytes());
-                block.extend_from_slice(description);
-                block.extend_from_slice(&width.to_be_bytes());
-                block.extend_from_slice(&height.to_be_bytes());
-                block.extend_from_slice(&depth.to_be_bytes());
-                block.extend_from_slice(&colors.to_be_bytes());
-                block.extend_from_slice(&(image_bytes.len() as u32).to_be_bytes());
-                block.extend_from_slice(&image_bytes);
-
-                let b64 = BASE64_STANDARD.encode(&block);
-                ffmpeg_args.extend(vec![
-                    "-metadata".to_string(),
-                    format!("METADATA_BLOCK_PICTURE={}", b64),
-                ]);
+                // 拡張子ではなく中身から形式を判定する（判別できない画像は誤った MIME で埋め込まない）
+                let mime = crate::utils::sniff_image(&image_bytes).mime;
+                if mime != crate::utils::UNKNOWN_IMAGE_MIME {
+                    let b64 = crate::utils::build_metadata_block_picture(&image_bytes, mime);
+                    ffmpeg_args.extend(vec![
+                        "-metadata".to_string(),
+                        format!("METADATA_BLOCK_PICTURE={}", b64),
+                    ]);
+                }
             }
         }
     }
 
+    // 元がOpusならストリームコピー（エンコーダ用のフラグも不要）
+    if audio_copy {
+        ffmpeg_args.extend(vec!["-c:a".to_string(), "copy".to_string()]);
+        return;
+    }
+
     ffmpeg_args.extend(vec![
         "-c:a".to_string(),
         "libopus".to_string(),
@@ -112,3 +103,7 @@ pub fn append_format_specific_args(
     ]);
 }
 
+/// 拡張タグを渡す `-metadata` のキー。Vorbis コメントはキーがそのまま書かれる
+pub fn metadata_key(field: StandardField) -> Option<&'static str> {
+    field.frame_ids(TagFormat::VorbisComment).first().copied()
+}
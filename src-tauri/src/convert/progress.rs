@@ -0,0 +1,180 @@
+use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+use std::time::Instant;
+
+use tauri::{AppHandle, Emitter};
+
+use crate::jobs::Job;
+use crate::models::ConvertProgress;
+
+/// バッチ全体の変換進捗。トラックごとの進捗（0.0〜1.0）を合算して全体の割合と残り時間を求める
+pub struct ProgressTracker {
+    app_handle: AppHandle,
+    job: Arc<Job>,
+    total: usize,
+    finished: AtomicUsize,
+    track_fractions: Mutex<Vec<f64>>,
+    started_at: Instant,
+}
+
+impl ProgressTracker {
+    pub fn new(app_handle: AppHandle, job: Arc<Job>, total: usize) -> Self {
+        Self {
+            app_handle,
+            job,
+            total,
+            finished: AtomicUsize::new(0),
+            track_fractions: Mutex::new(vec![0.0; total]),
+            started_at: Instant::now(),
+        }
+    }
+
+    pub fn job(&self) -> &Job {
+        &self.job
+    }
+
+    fn set_fraction(&self, index: usize, fraction: f64) -> f64 {
+        let mut fractions = self.track_fractions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(slot) = fractions.get_mut(index) {
+            // ffmpeg の out_time は前後することがあるため、後退させない
+            *slot = slot.max(fraction.clamp(0.0, 1.0));
+        }
+        if self.total == 0 {
+            return 100.0;
+        }
+        fractions.iter().sum::<f64>() / self.total as f64 * 100.0
+    }
+
+    fn eta_seconds(&self, overall_percent: f64) -> Option<f64> {
+        if overall_percent <= 0.0 || overall_percent >= 100.0 {
+            return None;
+        }
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        Some(elapsed * (100.0 - overall_percent) / overall_percent)
+    }
+
+    /// index はこのイベントのトラック。current には終了したトラック数を入れる
+    fn emit(
+        &self,
+        index: usize,
+        current_file: &str,
+        status: &str,
+        progress_percent: f64,
+        file_progress_percent: Option<f64>,
+        speed: Option<f64>,
+    ) {
+        let progress = ConvertProgress {
+            job_id: self.job.id().to_string(),
+            current: self.finished.load(Ordering::SeqCst),
+            track_index: index + 1,
+            total: self.total,
+            current_file: current_file.to_string(),
+            status: status.to_string(),
+            progress_percent,
+            file_progress_percent,
+            speed,
+            eta_seconds: self.eta_seconds(progress_percent),
+        };
+        let _ = self.app_handle.emit("convert-progress", &progress);
+    }
+
+    /// トラックの変換開始
+    pub fn start_track(&self, index: usize, current_file: &str) {
+        let overall = self.set_fraction(index, 0.0);
+        self.emit(index, current_file, "processing", overall, Some(0.0), None);
+    }
+
+    /// ffmpeg の -progress 出力によるトラック内の進捗
+    pub fn update_track(&self, index: usize, current_file: &str, fraction: Option<f64>, speed: Option<f64>) {
+        let overall = self.set_fraction(index, fraction.unwrap_or(0.0));
+        self.emit(
+            index,
+            current_file,
+            "encoding",
+            overall,
+            fraction.map(|f| f.clamp(0.0, 1.0) * 100.0),
+            speed,
+        );
+    }
+
+    /// トラックの終了（status: "completed" | "error" | "cancelled"）
+    pub fn finish_track(&self, index: usize, current_file: &str, status: &str) {
+        self.finished.fetch_add(1, Ordering::SeqCst);
+        let overall = self.set_fraction(index, 1.0);
+        let file_percent = if status == "completed" { Some(100.0) } else { None };
+        self.emit(index, current_file, status, overall, file_percent, None);
+    }
+}
+
+/// ffmpeg の `-progress pipe:1` が出力する key=value ブロックを集計する
+#[derive(Default)]
+pub struct FfmpegProgressParser {
+    out_time_seconds: Option<f64>,
+    speed: Option<f64>,
+}
+
+impl FfmpegProgressParser {
+    /// 1行を取り込み、ブロックの終端（progress=...）に達したら (経過秒, 速度) を返す
+    pub fn push_line(&mut self, line: &str) -> Option<(Option<f64>, Option<f64>)> {
+        let (key, value) = line.trim().split_once('=')?;
+        let value = value.trim();
+        match key {
+            // out_time_ms も実際にはマイクロ秒単位
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<i64>() {
+                    if us >= 0 {
+                        self.out_time_seconds = Some(us as f64 / 1_000_000.0);
+                    }
+                }
+            }
+            "speed" => {
+                self.speed = value.trim_end_matches('x').trim().parse::<f64>().ok();
+            }
+            "progress" => return Some((self.out_time_seconds, self.speed)),
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_all(parser: &mut FfmpegProgressParser, lines: &str) -> Vec<(Option<f64>, Option<f64>)> {
+        lines.lines().filter_map(|line| parser.push_line(line)).collect()
+    }
+
+    #[test]
+    fn blocks_end_at_the_progress_line() {
+        let mut parser = FfmpegProgressParser::default();
+        let blocks = push_all(
+            &mut parser,
+            "frame=0\nout_time_us=1500000\nout_time=00:00:01.500000\nspeed=2.5x\nprogress=continue\n\
+             out_time_us=3000000\nspeed= 3x\nprogress=end\n",
+        );
+        assert_eq!(blocks, [(Some(1.5), Some(2.5)), (Some(3.0), Some(3.0))]);
+        // ブロックの途中では何も返さない
+        assert_eq!(parser.push_line("out_time_us=4000000"), None);
+        assert_eq!(parser.push_line("garbage"), None);
+    }
+
+    #[test]
+    fn out_time_ms_is_also_microseconds() {
+        let mut parser = FfmpegProgressParser::default();
+        assert_eq!(push_all(&mut parser, "out_time_ms=2000000\nprogress=continue"), [(Some(2.0), None)]);
+    }
+
+    #[test]
+    fn unknown_values_do_not_break_the_block() {
+        let mut parser = FfmpegProgressParser::default();
+        // 開始直後の ffmpeg は負の out_time_us と speed=N/A を出す
+        let blocks = push_all(
+            &mut parser,
+            "out_time_us=-9223372036854775807\nout_time_us=N/A\nspeed=N/A\nprogress=continue\n\
+             out_time_us=500000\nspeed=1.2x\nprogress=continue\n\
+             speed=N/A\nprogress=end",
+        );
+        // 読めない経過時間は直前の値を残し、速度は読めなければ None にする
+        assert_eq!(blocks, [(None, None), (Some(0.5), Some(1.2)), (Some(0.5), None)]);
+    }
+}
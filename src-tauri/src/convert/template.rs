@@ -0,0 +1,458 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::models::{ConvertAlbumData, ConvertTrack};
+use crate::utils::{fit_file_name, replace_invalid_chars, sanitize_path_component, FilenameTarget};
+
+/// テンプレート未指定時の出力パス（従来の `{album_artist}/{album_title}/{disc:02}-{track:02} {title}` と同じ）
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{albumartist}/{album}/{disc:02}-{track:02} {title}";
+
+/// 出力パステンプレート
+///
+/// - `{field}` / `{field:spec}` でフィールドを埋め込む。`/` はディレクトリ区切り
+///   - フィールド: title, artist, albumartist, album, track, disc, disctotal, tracktotal, date, genre
+///   - 数値フィールドの spec は桁数（`{track:02}`）、date の spec は書式（`{date:yyyy}`, `{date:yyyy-mm-dd}`）
+/// - `[...]` は条件付きセグメントで、中のフィールドがひとつでも空なら丸ごと省略する。
+///   ディスクが1枚だけのアルバムでは `{disc}` も空として扱う（例: `[{disc}-]{track:02} {title}`）
+#[derive(Debug)]
+pub struct OutputTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Literal(String),
+    Field { name: Field, spec: Option<String> },
+    Optional(Vec<Segment>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Title,
+    Artist,
+    AlbumArtist,
+    Album,
+    Track,
+    TrackTotal,
+    Disc,
+    DiscTotal,
+    Date,
+    Genre,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "title" => Some(Field::Title),
+            "artist" => Some(Field::Artist),
+            "albumartist" | "album_artist" => Some(Field::AlbumArtist),
+            "album" => Some(Field::Album),
+            "track" => Some(Field::Track),
+            "tracktotal" => Some(Field::TrackTotal),
+            "disc" | "disk" => Some(Field::Disc),
+            "disctotal" => Some(Field::DiscTotal),
+            "date" | "year" => Some(Field::Date),
+            "genre" => Some(Field::Genre),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(self, Field::Track | Field::TrackTotal | Field::Disc | Field::DiscTotal)
+    }
+}
+
+/// テンプレートを展開するときに参照するバッチ全体の情報
+pub struct TemplateContext<'a> {
+    pub album_data: &'a ConvertAlbumData,
+    pub disc_count: usize,
+    pub track_totals: &'a HashMap<u32, usize>,
+    pub target: FilenameTarget,
+}
+
+impl<'a> TemplateContext<'a> {
+    pub fn new(
+        album_data: &'a ConvertAlbumData,
+        track_totals: &'a HashMap<u32, usize>,
+        target: FilenameTarget,
+    ) -> Self {
+        Self {
+            album_data,
+            disc_count: track_totals.len(),
+            track_totals,
+            target,
+        }
+    }
+}
+
+/// ディスクごとのトラック数（テンプレートの disctotal / tracktotal と、ディスク枚数の判定に使う）
+pub fn count_tracks_per_disc(tracks: &[ConvertTrack]) -> HashMap<u32, usize> {
+    let mut totals: HashMap<u32, usize> = HashMap::new();
+    for track in tracks {
+        *totals.entry(parse_number(&track.disk_number)).or_insert(0) += 1;
+    }
+    totals
+}
+
+fn parse_number(value: &str) -> u32 {
+    crate::utils::parse_number_pair(value).0.unwrap_or(1)
+}
+
+impl OutputTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let template = template.trim();
+        if template.is_empty() {
+            return Err("出力テンプレートが空です".to_string());
+        }
+
+        let mut chars = template.chars().peekable();
+        let segments = parse_segments(&mut chars, false)?;
+
+        let has_title_or_track = contains_field(&segments, &|f| matches!(f, Field::Title | Field::Track));
+        if !has_title_or_track {
+            return Err("出力テンプレートには {title} か {track} を含めてください".to_string());
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// テンプレートを展開し、出力ディレクトリからの相対パス（拡張子付き）を返す
+    pub fn render(&self, track: &ConvertTrack, context: &TemplateContext, extension: &str) -> Result<PathBuf, String> {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            if let Some(text) = render_segment(segment, track, context) {
+                rendered.push_str(&text);
+            }
+        }
+
+        // テンプレート中の区切り文字でディレクトリに分割する
+        let components: Vec<&str> = rendered
+            .split(['/', '\\'])
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .collect();
+
+        let Some((file_stem, dirs)) = components.split_last() else {
+            return Err("出力ファイル名が空になりました".to_string());
+        };
+
+        // ディレクトリは要素ごとに、ファイル名は拡張子を残して長さ制限に収める
+        let mut path = PathBuf::new();
+        for dir in dirs {
+            path.push(sanitize_path_component(dir, context.target));
+        }
+        path.push(fit_file_name(file_stem, "", extension, context.target));
+        Ok(path)
+    }
+}
+
+type CharStream<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn parse_segments(chars: &mut CharStream, in_optional: bool) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                let mut body = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => body.push(ch),
+                        None => return Err("テンプレートの { が閉じられていません".to_string()),
+                    }
+                }
+                segments.push(parse_field(&body)?);
+            }
+            '}' => return Err("テンプレートに対応しない } があります".to_string()),
+            '[' => {
+                if in_optional {
+                    return Err("条件付きセグメント [...] は入れ子にできません".to_string());
+                }
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                let inner = parse_segments(chars, true)?;
+                segments.push(Segment::Optional(inner));
+            }
+            ']' => {
+                if !in_optional {
+                    return Err("テンプレートに対応しない ] があります".to_string());
+                }
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(literal));
+                }
+                return Ok(segments);
+            }
+            _ => literal.push(c),
+        }
+    }
+
+    if in_optional {
+        return Err("テンプレートの [ が閉じられていません".to_string());
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+fn parse_field(body: &str) -> Result<Segment, String> {
+    let (name, spec) = match body.split_once(':') {
+        Some((name, spec)) => (name.trim(), Some(spec.trim().to_string())),
+        None => (body.trim(), None),
+    };
+
+    let field = Field::parse(name).ok_or_else(|| format!("不明なテンプレート項目です: {{{}}}", name))?;
+
+    if let Some(spec) = &spec {
+        let valid = if field.is_numeric() {
+            !spec.is_empty() && spec.chars().all(|c| c.is_ascii_digit())
+        } else {
+            matches!(field, Field::Date)
+        };
+        if !valid {
+            return Err(format!("テンプレート項目の書式が不正です: {{{}}}", body));
+        }
+    }
+
+    Ok(Segment::Field { name: field, spec })
+}
+
+fn contains_field(segments: &[Segment], pred: &dyn Fn(Field) -> bool) -> bool {
+    segments.iter().any(|segment| match segment {
+        Segment::Field { name, .. } => pred(*name),
+        Segment::Optional(inner) => contains_field(inner, pred),
+        Segment::Literal(_) => false,
+    })
+}
+
+fn render_segment(segment: &Segment, track: &ConvertTrack, context: &TemplateContext) -> Option<String> {
+    match segment {
+        Segment::Literal(text) => Some(text.clone()),
+        Segment::Field { name, spec } => Some(render_field(*name, spec.as_deref(), track, context, false).unwrap_or_default()),
+        Segment::Optional(inner) => {
+            let mut text = String::new();
+            for segment in inner {
+                match segment {
+                    Segment::Field { name, spec } => {
+                        text.push_str(&render_field(*name, spec.as_deref(), track, context, true)?);
+                    }
+                    other => text.push_str(&render_segment(other, track, context)?),
+                }
+            }
+            Some(text)
+        }
+    }
+}
+
+/// フィールドの値を返す。空の場合は None（conditional が true のときはディスク1枚の {disc} も None）
+fn render_field(
+    field: Field,
+    spec: Option<&str>,
+    track: &ConvertTrack,
+    context: &TemplateContext,
+    conditional: bool,
+) -> Option<String> {
+    let album = context.album_data;
+    let disc = parse_number(&track.disk_number);
+
+    let value = match field {
+        Field::Title => track.title.clone(),
+        Field::Artist => track.artists.join(", "),
+        Field::AlbumArtist => album.album_artist.clone(),
+        Field::Album => album.album_title.clone(),
+        Field::Genre => album.tags.join(", "),
+        Field::Date => format_date(&album.release_date, spec),
+        Field::Disc if conditional && context.disc_count <= 1 => return None,
+        Field::Track => pad_number(parse_number(&track.track_number) as usize, spec),
+        Field::TrackTotal => match track.track_total {
+            Some(total) => pad_number(total as usize, spec),
+            None => pad_number(context.track_totals.get(&disc).copied().unwrap_or(0), spec),
+        },
+        Field::Disc => pad_number(disc as usize, spec),
+        Field::DiscTotal => pad_number(context.disc_count, spec),
+    };
+
+    // 値の中のパス区切りなどはディレクトリ構造を崩さないよう置換する
+    let value = replace_invalid_chars(&value);
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn pad_number(value: usize, spec: Option<&str>) -> String {
+    let width = spec.and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
+    format!("{:0width$}", value, width = width)
+}
+
+/// "2023-04-05" / "2023/4/5" / "2023" などを spec（yyyy, mm, dd を含む書式）で整形する
+fn format_date(date: &str, spec: Option<&str>) -> String {
+    let date = date.trim();
+    let Some(spec) = spec else {
+        return date.to_string();
+    };
+
+    let parts: Vec<&str> = date
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let year = parts.first().filter(|y| y.len() == 4).copied();
+    let Some(year) = year else {
+        return String::new();
+    };
+    let month = parts.get(1).and_then(|m| m.parse::<u32>().ok());
+    let day = parts.get(2).and_then(|d| d.parse::<u32>().ok());
+
+    let mut result = spec.replace("yyyy", year);
+    if result.contains("mm") {
+        let Some(month) = month else { return String::new() };
+        result = result.replace("mm", &format!("{:02}", month));
+    }
+    if result.contains("dd") {
+        let Some(day) = day else { return String::new() };
+        result = result.replace("dd", &format!("{:02}", day));
+    }
+    result
+}
+
+/// バッチ内で同じ出力パスになるトラックのインデックスを返す（大文字小文字を区別しないファイルシステムを考慮）
+pub fn find_collisions(paths: &[PathBuf]) -> HashSet<usize> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut collisions = HashSet::new();
+    for (index, path) in paths.iter().enumerate() {
+        let key = normalize_for_compare(path);
+        if let Some(&first) = seen.get(&key) {
+            collisions.insert(first);
+            collisions.insert(index);
+        } else {
+            seen.insert(key, index);
+        }
+    }
+    collisions
+}
+
+fn normalize_for_compare(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ExtendedTags;
+
+    fn album(release_date: &str) -> ConvertAlbumData {
+        ConvertAlbumData {
+            album_title: "アルバム".to_string(),
+            album_artist: "サークル".to_string(),
+            release_date: release_date.to_string(),
+            tags: Vec::new(),
+            album_artwork_path: None,
+            album_artwork_cache_path: None,
+            album_artwork: None,
+            pictures: Vec::new(),
+            product_id: None,
+            extended: ExtendedTags::default(),
+        }
+    }
+
+    fn track(disc: &str, number: &str, title: &str) -> ConvertTrack {
+        ConvertTrack {
+            source_path: format!("/src/{}.wav", title),
+            disk_number: disc.to_string(),
+            track_number: number.to_string(),
+            disk_total: None,
+            track_total: None,
+            title: title.to_string(),
+            artists: vec!["声優".to_string()],
+            extended: ExtendedTags::default(),
+        }
+    }
+
+    /// tracks をまとめて変換するときと同じ文脈で、各トラックのパスを "/" 区切りで返す
+    fn render_all(template: &str, album: &ConvertAlbumData, tracks: &[ConvertTrack]) -> Vec<String> {
+        let template = OutputTemplate::parse(template).unwrap();
+        let totals = count_tracks_per_disc(tracks);
+        let context = TemplateContext::new(album, &totals, FilenameTarget::Portable);
+        tracks
+            .iter()
+            .map(|track| {
+                let path = template.render(track, &context, "mp3").unwrap();
+                path.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect::<Vec<_>>().join("/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn optional_disc_is_omitted_for_a_single_disc() {
+        let album = album("2023-04-05");
+        let single = [track("1", "1", "はじめに"), track("1", "2", "耳かき")];
+        assert_eq!(
+            render_all("[{disc}-]{track:02} {title}", &album, &single),
+            ["01 はじめに.mp3", "02 耳かき.mp3"]
+        );
+
+        let double = [track("1", "1", "はじめに"), track("2/2", "1", "おまけ")];
+        assert_eq!(
+            render_all("[{disc}-]{track:02} {title}", &album, &double),
+            ["1-01 はじめに.mp3", "2-01 おまけ.mp3"]
+        );
+        // 条件付きでなければディスク 1 枚でも番号を出す
+        assert_eq!(render_all("{disc}-{track} {title}", &album, &single[..1]), ["1-1 はじめに.mp3"]);
+    }
+
+    #[test]
+    fn date_spec_with_a_partial_date() {
+        let tracks = [track("1", "3", "曲")];
+        assert_eq!(render_all("{date:yyyy}/{title}", &album("2023"), &tracks), ["2023/曲.mp3"]);
+        assert_eq!(render_all("{date:yyyy.mm}/{title}", &album("2023/4/5"), &tracks), ["2023.04/曲.mp3"]);
+        // 月が無い日付は書式ごと空になり、条件付きセグメントなら省略される
+        assert_eq!(render_all("[{date:yyyy-mm} ]{title}", &album("2023"), &tracks), ["曲.mp3"]);
+        assert_eq!(render_all("[({date:yyyy}) ]{album}/{title}", &album(""), &tracks), ["アルバム/曲.mp3"]);
+    }
+
+    #[test]
+    fn values_cannot_add_directories() {
+        let tracks = [track("1", "1", "A/B: C")];
+        assert_eq!(render_all("{albumartist}/{title}", &album(""), &tracks), ["サークル/A_B_ C.mp3"]);
+    }
+
+    #[test]
+    fn parse_rejects_unbalanced_and_unknown_syntax() {
+        let cases = [
+            ("{title", "{ が閉じられていません"),
+            ("title}", "対応しない }"),
+            ("[{disc}-{track}", "[ が閉じられていません"),
+            ("{track}] {title}", "対応しない ]"),
+            ("[[{disc}]]{title}", "入れ子にできません"),
+            ("{titel}", "不明なテンプレート項目です: {titel}"),
+            ("{track:ab} {title}", "書式が不正です"),
+            ("{title:02}", "書式が不正です"),
+            ("{album}/{artist}", "{title} か {track}"),
+            ("   ", "空です"),
+        ];
+        for (template, message) in cases {
+            let error = OutputTemplate::parse(template).unwrap_err();
+            assert!(error.contains(message), "{}: {}", template, error);
+        }
+        assert!(OutputTemplate::parse(DEFAULT_OUTPUT_TEMPLATE).is_ok());
+        assert!(OutputTemplate::parse("[{disc}-]{TRACK:2}").is_ok());
+    }
+
+    #[test]
+    fn collisions_ignore_case_and_separators() {
+        let paths: Vec<PathBuf> = ["Album/01 Song.mp3", "album/01 song.MP3", "Album/02 Song.mp3", "ALBUM\\01 SONG.mp3"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        let mut collisions: Vec<usize> = find_collisions(&paths).into_iter().collect();
+        collisions.sort();
+        assert_eq!(collisions, [0, 1, 3]);
+    }
+}
@@ -0,0 +1,279 @@
+//! フォルダ内の画像からアルバムの表紙らしいものを選ぶ
+
+use std::path::Path;
+
+use crate::models::{AlbumGroup, CoverCandidate, CoverScoreBreakdown, CoverSelection};
+
+/// 表紙らしい名前と点数（含まれる語のうち最も高いものを使う）
+const COVER_WORDS: [(&str, f32); 10] = [
+    ("cover", 40.0),
+    ("jacket", 40.0),
+    ("表紙", 40.0),
+    ("ジャケット", 40.0),
+    ("front", 35.0),
+    ("folder", 35.0),
+    ("カバー", 35.0),
+    ("jk", 30.0),
+    ("album", 20.0),
+    ("artwork", 20.0),
+];
+/// 表紙ではないことが多い名前（裏表紙・ブックレット・盤面・特典など）
+const NON_COVER_WORDS: [&str; 20] = [
+    "back", "rear", "裏", "背表紙", "booklet", "ブックレット", "disc", "cd", "label", "obi", "帯", "inlay",
+    "tray", "spine", "特典", "bonus", "lyrics", "歌詞", "credit", "壁紙",
+];
+const NON_COVER_PENALTY: f32 = 30.0;
+/// アルバムのフォルダより下のフォルダ名の語はファイル名の半分の重みにする
+const FOLDER_WEIGHT: f32 = 0.5;
+
+/// 縦横比の点数の上限（短辺 / 長辺 が ASPECT_FULL 以上で満点、ASPECT_ZERO 以下で 0）
+const ASPECT_POINTS: f32 = 20.0;
+const ASPECT_FULL: f32 = 0.95;
+const ASPECT_ZERO: f32 = 0.6;
+/// 解像度の点数の上限（短辺が RESOLUTION_FULL px 以上で満点、RESOLUTION_ZERO px 以下で 0）
+const RESOLUTION_POINTS: f32 = 25.0;
+const RESOLUTION_FULL: f32 = 1000.0;
+const RESOLUTION_ZERO: f32 = 200.0;
+/// アルバムのフォルダ直下で満点、1 階層深くなるごとに DEPTH_STEP 減らす。1 つ上のフォルダは PARENT_POINTS
+const DEPTH_POINTS: f32 = 15.0;
+const DEPTH_STEP: f32 = 5.0;
+const PARENT_POINTS: f32 = 5.0;
+
+/// 画像をアルバムごとに振り分けて表紙らしさの点数を付け、アルバムごとに最も良い候補を返す。
+/// 画像はフォルダが最も近いアルバム（アルバムのフォルダの中、無ければ 1 つ上のフォルダ）に割り当て、
+/// どのアルバムにも近くない画像は使わない
+#[tauri::command]
+pub async fn rank_cover_images(
+    groups: Vec<AlbumGroup>,
+    image_paths: Vec<String>,
+) -> Result<Vec<CoverSelection>, String> {
+    let roots: Vec<Vec<&str>> = groups.iter().map(|group| split_path(&group.root_dir)).collect();
+
+    let mut candidates: Vec<Vec<CoverCandidate>> = vec![Vec::new(); groups.len()];
+    for path in &image_paths {
+        let mut dirs = split_path(path);
+        let file_name = dirs.pop().unwrap_or_default();
+
+        let closest = roots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, root)| relative_depth(root, &dirs).map(|depth| (index, depth)))
+            .min_by_key(|&(_, depth)| if depth < 0 { i32::MAX } else { depth });
+        let Some((index, depth)) = closest else { continue };
+
+        let folders = dirs.get(roots[index].len()..).unwrap_or_default();
+        candidates[index].push(score(path, file_name, folders, depth));
+    }
+
+    let selections = groups
+        .into_iter()
+        .zip(candidates)
+        .map(|(group, mut candidates)| {
+            candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+            CoverSelection {
+                album: group.name,
+                root_dir: group.root_dir,
+                best: candidates.first().cloned(),
+                candidates,
+            }
+        })
+        .collect();
+    Ok(selections)
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.split(['/', '\\']).collect()
+}
+
+/// アルバムのフォルダから画像のフォルダまでの深さ（直下が 0、1 つ上のフォルダが -1、それ以外は None）。
+/// フォルダの無いアルバム（root_dir が空）は、分割した [""] がすべての絶対パスの先頭に一致してしまうため対象にしない
+fn relative_depth(root: &[&str], dirs: &[&str]) -> Option<i32> {
+    if root.iter().all(|component| component.is_empty()) {
+        return None;
+    }
+    if dirs.starts_with(root) {
+        return Some((dirs.len() - root.len()) as i32);
+    }
+    match root.split_last() {
+        Some((_, parent)) if !parent.is_empty() && dirs == parent => Some(-1),
+        _ => None,
+    }
+}
+
+fn score(path: &str, file_name: &str, folders: &[&str], depth: i32) -> CoverCandidate {
+    let mut hints = Vec::new();
+    let stem = match file_name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => file_name,
+    };
+    let mut filename = name_score(stem, &mut hints);
+    for folder in folders {
+        filename += name_score(folder, &mut hints) * FOLDER_WEIGHT;
+    }
+
+    // 寸法が読めない画像は縦横比・解像度を 0 点にする
+    let info = crate::utils::read_image_info(Path::new(path)).ok();
+    let width = info.and_then(|info| info.width);
+    let height = info.and_then(|info| info.height);
+    let (aspect_ratio, resolution) = match width.zip(height) {
+        Some((width, height)) if width > 0 && height > 0 => {
+            let short = width.min(height) as f32;
+            let ratio = short / width.max(height) as f32;
+            (
+                ASPECT_POINTS * ((ratio - ASPECT_ZERO) / (ASPECT_FULL - ASPECT_ZERO)).clamp(0.0, 1.0),
+                RESOLUTION_POINTS * ((short - RESOLUTION_ZERO) / (RESOLUTION_FULL - RESOLUTION_ZERO)).clamp(0.0, 1.0),
+            )
+        }
+        _ => (0.0, 0.0),
+    };
+
+    let depth = if depth < 0 {
+        PARENT_POINTS
+    } else {
+        (DEPTH_POINTS - DEPTH_STEP * depth as f32).max(0.0)
+    };
+
+    let breakdown = CoverScoreBreakdown {
+        filename,
+        aspect_ratio,
+        resolution,
+        depth,
+    };
+    CoverCandidate {
+        path: path.to_string(),
+        score: breakdown.filename + breakdown.aspect_ratio + breakdown.resolution + breakdown.depth,
+        breakdown,
+        hints,
+        width,
+        height,
+    }
+}
+
+/// 名前の点数。表紙らしい語のうち最も高いものを加点し、表紙ではない語があれば減点する
+fn name_score(name: &str, hints: &mut Vec<String>) -> f32 {
+    let name = name.to_lowercase();
+    let mut score = 0.0;
+    let cover = COVER_WORDS
+        .iter()
+        .filter(|(word, _)| contains_word(&name, word))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((word, points)) = cover {
+        add_hint(hints, word);
+        score += points;
+    }
+    if let Some(word) = NON_COVER_WORDS.iter().find(|word| contains_word(&name, word)) {
+        add_hint(hints, word);
+        score -= NON_COVER_PENALTY;
+    }
+    score
+}
+
+fn add_hint(hints: &mut Vec<String>, word: &str) {
+    if !hints.iter().any(|hint| hint == word) {
+        hints.push(word.to_string());
+    }
+}
+
+/// 英語の語は単語として含まれるか（"cover2" / "front_cover" は一致、"discover" は不一致）、
+/// 日本語の語は部分一致で調べる
+fn contains_word(name: &str, word: &str) -> bool {
+    if !word.is_ascii() {
+        return name.contains(word);
+    }
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter_map(|token| token.strip_prefix(word))
+        .any(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(name: &str, root_dir: &str) -> AlbumGroup {
+        AlbumGroup {
+            name: name.to_string(),
+            root_dir: root_dir.to_string(),
+            product_id: None,
+            variants: Vec::new(),
+            tracks: Vec::new(),
+        }
+    }
+
+    /// 存在しないパスの画像は寸法が読めないため、名前と深さだけの点数になる
+    fn name_only(path: &str, root: &str) -> CoverCandidate {
+        let mut dirs = split_path(path);
+        let file_name = dirs.pop().unwrap();
+        let root = split_path(root);
+        let depth = relative_depth(&root, &dirs).unwrap();
+        score(path, file_name, dirs.get(root.len()..).unwrap_or_default(), depth)
+    }
+
+    #[test]
+    fn relative_depth_inside_parent_and_unrelated() {
+        let root = split_path("/music/作品");
+        assert_eq!(relative_depth(&root, &split_path("/music/作品")), Some(0));
+        assert_eq!(relative_depth(&root, &split_path("/music/作品/scans/hi")), Some(2));
+        assert_eq!(relative_depth(&root, &split_path("/music")), Some(-1));
+        assert_eq!(relative_depth(&root, &split_path("/music/別の作品")), None);
+        assert_eq!(relative_depth(&root, &split_path("/")), None);
+        // root_dir の無いアルバムにはどの画像も割り当てない
+        assert_eq!(relative_depth(&split_path(""), &split_path("/music/作品")), None);
+        assert_eq!(relative_depth(&split_path(""), &split_path("C:\\music")), None);
+    }
+
+    #[test]
+    fn cover_names_outrank_back_and_booklet() {
+        let folder = name_only("/a/folder.jpg", "/a");
+        let back = name_only("/a/back.jpg", "/a");
+        let plain = name_only("/a/IMG_0001.jpg", "/a");
+        assert!(folder.score > plain.score && plain.score > back.score);
+        assert_eq!(folder.hints, ["folder"]);
+        assert_eq!(back.hints, ["back"]);
+        assert_eq!(folder.breakdown.filename, 35.0);
+        assert_eq!(back.breakdown.filename, -NON_COVER_PENALTY);
+
+        // 表紙の語と表紙ではない語の両方があれば差し引く
+        assert_eq!(name_only("/a/cover_back.png", "/a").breakdown.filename, 40.0 - NON_COVER_PENALTY);
+        // 下のフォルダ名は半分の重み、深さは 1 階層ごとに減点
+        let nested = name_only("/a/jacket/01.jpg", "/a");
+        assert_eq!(nested.breakdown.filename, 40.0 * FOLDER_WEIGHT);
+        assert_eq!(nested.breakdown.depth, DEPTH_POINTS - DEPTH_STEP);
+        assert_eq!(name_only("/cover.jpg", "/a").breakdown.depth, PARENT_POINTS);
+    }
+
+    #[test]
+    fn words_match_whole_tokens() {
+        assert!(contains_word("discover", "discover"));
+        assert!(!contains_word("discover", "disc"));
+        assert!(!contains_word("recovery", "cover"));
+        assert!(contains_word("disc2", "disc"));
+        assert!(contains_word("front_cover", "cover"));
+        assert!(contains_word("cover02", "cover"));
+        assert!(contains_word("作品の表紙画像", "表紙"));
+        assert_eq!(name_only("/a/Discover.jpg", "/a").breakdown.filename, 0.0);
+        assert_eq!(name_only("/a/CD1.jpg", "/a").breakdown.filename, -NON_COVER_PENALTY);
+    }
+
+    #[test]
+    fn images_go_to_the_closest_album_and_skip_albums_without_a_folder() {
+        let groups = vec![group("フォルダ無し", ""), group("作品", "/music/作品"), group("CD2", "/music/作品/CD2")];
+        let images = [
+            "/music/作品/cover.jpg",
+            "/music/作品/back.jpg",
+            "/music/作品/CD2/folder.jpg",
+            "/elsewhere/cover.jpg",
+        ];
+        let selections = futures::executor::block_on(rank_cover_images(
+            groups,
+            images.iter().map(|p| p.to_string()).collect(),
+        ))
+        .unwrap();
+
+        assert!(selections[0].candidates.is_empty());
+        assert!(selections[0].best.is_none());
+        let paths = |index: usize| selections[index].candidates.iter().map(|c| c.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths(1), ["/music/作品/cover.jpg", "/music/作品/back.jpg"]);
+        assert_eq!(paths(2), ["/music/作品/CD2/folder.jpg"]);
+        assert_eq!(selections[1].best.as_ref().map(|c| c.path.as_str()), Some("/music/作品/cover.jpg"));
+    }
+}
@@ -1,8 +1,25 @@
-use std::path::Path;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use encoding_rs::SHIFT_JIS;
+use sha2::{Digest, Sha256};
 use walkdir::WalkDir;
+use zip::result::ZipError;
+use zip::{ZipArchive, SUPPORTED_COMPRESSION_METHODS};
+
+use crate::jobs::PartialOutput;
+use crate::models::{ScannedAudioFile, ZipEntryInfo, ZipImportResult, ZipSkippedEntry};
+use crate::utils::{fit_file_name, sanitize_path_component, FilenameTarget};
+
+const AUDIO_EXTENSIONS: [&str; 7] = ["wav", "mp3", "flac", "m4a", "opus", "ogg", "oga"];
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+/// 未指定のときの ZIP から展開するファイルの合計の上限（MiB）
+const DEFAULT_MAX_EXTRACT_MB: u64 = 16 * 1024;
 
 #[tauri::command]
-pub async fn scan_directory_for_audio_files(directory_path: String) -> Result<Vec<String>, String> {
+pub async fn scan_directory_for_audio_files(directory_path: String) -> Result<Vec<ScannedAudioFile>, String> {
     let path = Path::new(&directory_path);
     if !crate::path_utils::path_exists(path) {
         return Err("指定されたディレクトリが存在しません".to_string());
@@ -12,10 +29,8 @@ pub async fn scan_directory_for_audio_files(directory_path: String) -> Result<Ve
         return Err("指定されたパスはディレクトリではありません".to_string());
     }
 
-    let supported_extensions = ["wav", "mp3", "flac", "m4a"];
-
     // WalkDir で高速・安全に再帰走査（シンボリックリンクを追わない）
-    let mut audio_files: Vec<String> = WalkDir::new(path)
+    let mut audio_files: Vec<ScannedAudioFile> = WalkDir::new(path)
         .follow_links(false)
         .into_iter()
         .filter_map(|e| e.ok())
@@ -23,15 +38,19 @@ pub async fn scan_directory_for_audio_files(directory_path: String) -> Result<Ve
         .filter_map(|e| {
             let p = e.path();
             let ext = p.extension()?.to_str()?.to_lowercase();
-            if supported_extensions.contains(&ext.as_str()) {
-                Some(p.to_string_lossy().to_string())
+            if AUDIO_EXTENSIONS.contains(&ext.as_str()) {
+                let path = p.to_string_lossy().to_string();
+                Some(ScannedAudioFile {
+                    product_id: crate::utils::detect_product_id(&path),
+                    path,
+                })
             } else {
                 None
             }
         })
         .collect();
 
-    audio_files.sort();
+    audio_files.sort_by(|a, b| a.path.cmp(&b.path));
     Ok(audio_files)
 }
 
@@ -46,8 +65,6 @@ pub async fn scan_directory_for_image_files(directory_path: String) -> Result<Ve
         return Err("指定されたパスはディレクトリではありません".to_string());
     }
 
-    let supported_extensions = ["png", "jpg", "jpeg", "webp"];
-
     let mut image_files: Vec<String> = WalkDir::new(path)
         .follow_links(false)
         .into_iter()
@@ -56,7 +73,7 @@ pub async fn scan_directory_for_image_files(directory_path: String) -> Result<Ve
         .filter_map(|e| {
             let p = e.path();
             let ext = p.extension()?.to_str()?.to_lowercase();
-            if supported_extensions.contains(&ext.as_str()) {
+            if IMAGE_EXTENSIONS.contains(&ext.as_str()) {
                 Some(p.to_string_lossy().to_string())
             } else {
                 None
@@ -67,3 +84,304 @@ pub async fn scan_directory_for_image_files(directory_path: String) -> Result<Ve
     image_files.sort();
     Ok(image_files)
 }
+
+/// ZIP 内の音声・画像ファイルを一覧する（展開はしない）
+#[tauri::command]
+pub async fn list_zip_archive(archive_path: String) -> Result<Vec<ZipEntryInfo>, String> {
+    let mut archive = open_zip(&archive_path)?;
+    let encoding = detect_name_encoding(&mut archive)?;
+
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let file = archive
+            .by_index_raw(index)
+            .map_err(|e| format!("ZIP の読み込みに失敗しました: {}", e))?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = decode_entry_name(file.name_raw(), file.name(), encoding);
+        let Some(kind) = entry_kind(&name) else { continue };
+        if entry_relative_path(&name).is_none() {
+            continue;
+        }
+        entries.push(ZipEntryInfo {
+            name,
+            size: file.size(),
+            kind: kind.to_string(),
+            encrypted: file.encrypted(),
+        });
+    }
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// ZIP 内の音声・画像ファイルをステージングディレクトリに展開する。
+/// 同じアーカイブを再度取り込んだ場合は、前回展開したものを消してから展開し直す。
+/// 暗号化されたエントリや未対応の圧縮方式のエントリは展開せず、skipped_entries で返す
+#[tauri::command]
+pub async fn import_zip_archive(archive_path: String) -> Result<ZipImportResult, String> {
+    // 展開は同期 I/O のため、非同期ランタイムのスレッドを塞がないよう別スレッドで行う
+    tokio::task::spawn_blocking(move || extract_zip_archive(archive_path))
+        .await
+        .map_err(|e| format!("ZIP の展開に失敗しました: {}", e))?
+}
+
+fn extract_zip_archive(archive_path: String) -> Result<ZipImportResult, String> {
+    let mut archive = open_zip(&archive_path)?;
+    let encoding = detect_name_encoding(&mut archive)?;
+
+    // 展開するエントリを決める（展開前にサイズの合計を確かめるため、先に一覧にする）
+    let mut entries = Vec::new();
+    let mut skipped_entries = Vec::new();
+    let mut total_size = 0u64;
+    for index in 0..archive.len() {
+        let file = archive
+            .by_index_raw(index)
+            .map_err(|e| format!("ZIP の読み込みに失敗しました: {}", e))?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = decode_entry_name(file.name_raw(), file.name(), encoding);
+        let Some(kind) = entry_kind(&name) else { continue };
+        let Some(relative) = entry_relative_path(&name) else { continue };
+
+        if file.encrypted() {
+            skipped_entries.push(ZipSkippedEntry { name, reason: "暗号化されています".to_string() });
+            continue;
+        }
+        if !SUPPORTED_COMPRESSION_METHODS.contains(&file.compression()) {
+            let reason = format!("対応していない圧縮方式です（{}）", file.compression());
+            skipped_entries.push(ZipSkippedEntry { name, reason });
+            continue;
+        }
+
+        total_size = total_size.saturating_add(file.size());
+        entries.push((index, name, kind, relative));
+    }
+
+    // サニタイズで同じパスになったエントリは、出力ファイル名の重複と同じく "_1", "_2" を付けて分ける。
+    // 元から "_1" の付いたエントリと重ならないよう、先に全エントリのパスを使用済みにしておく
+    let mut used_paths: HashSet<String> = entries.iter().map(|(.., relative)| path_key(relative)).collect();
+    let mut seen = HashSet::new();
+    for (.., relative) in &mut entries {
+        if !seen.insert(path_key(relative)) {
+            *relative = unique_relative_path(relative, &mut used_paths);
+        }
+    }
+
+    let max_bytes = max_extract_bytes();
+    if max_bytes > 0 && total_size > max_bytes {
+        return Err(format!(
+            "展開後のサイズ（{} MB）が上限（{} MB）を超えるため取り込めません",
+            total_size / (1024 * 1024),
+            max_bytes / (1024 * 1024)
+        ));
+    }
+
+    let staging_dir = staging_dir_for(Path::new(&archive_path))?;
+    let staging_ep = crate::path_utils::to_extended_length_path_if_needed(&staging_dir);
+    if staging_ep.exists() {
+        fs::remove_dir_all(&staging_ep).map_err(|e| format!("前回展開したファイルの削除に失敗しました: {}", e))?;
+    }
+
+    let mut audio_files = Vec::new();
+    let mut image_files = Vec::new();
+    let mut extracted = 0u64;
+    for (index, name, kind, relative) in entries {
+        let file = match archive.by_index(index) {
+            Ok(file) => file,
+            // 暗号化・圧縮方式の確認をすり抜けたもの（強い暗号化など）
+            Err(ZipError::UnsupportedArchive(reason)) => {
+                skipped_entries.push(ZipSkippedEntry { name, reason: reason.to_string() });
+                continue;
+            }
+            Err(e) => return Err(format!("ZIP の読み込みに失敗しました: {}", e)),
+        };
+
+        let output_path = staging_dir.join(relative);
+        if let Some(parent) = output_path.parent() {
+            crate::path_utils::create_dir_all_extended(parent)
+                .map_err(|e| format!("展開先フォルダの作成に失敗しました: {}", e))?;
+        }
+        let partial_output = PartialOutput::new(&output_path);
+        let output_ep = crate::path_utils::to_extended_length_path_if_needed(&output_path);
+        // ヘッダーのサイズを偽ったエントリ（ZIP 爆弾）に備え、展開した量でも上限を確かめる
+        let remaining = if max_bytes > 0 { max_bytes - extracted } else { u64::MAX };
+        let written = File::create(&output_ep)
+            .and_then(|mut output| std::io::copy(&mut file.take(remaining.saturating_add(1)), &mut output))
+            .map_err(|e| format!("{} の展開に失敗しました: {}", name, e))?;
+        extracted += written;
+        if written > remaining {
+            drop(partial_output);
+            let _ = fs::remove_dir_all(&staging_ep);
+            return Err(format!(
+                "展開後のサイズが上限（{} MB）を超えるため取り込めません",
+                max_bytes / (1024 * 1024)
+            ));
+        }
+        partial_output.keep();
+
+        let output_path = output_path.to_string_lossy().to_string();
+        if kind == "audio" {
+            audio_files.push(output_path);
+        } else {
+            image_files.push(output_path);
+        }
+    }
+
+    audio_files.sort();
+    image_files.sort();
+    let product_id = crate::utils::detect_product_id(&archive_path);
+    Ok(ZipImportResult {
+        archive_path,
+        staging_dir: staging_dir.to_string_lossy().to_string(),
+        name_encoding: encoding.to_string(),
+        product_id,
+        audio_files,
+        image_files,
+        skipped_entries,
+    })
+}
+
+/// 展開するファイルの合計の上限（VTE_ZIP_MAX_EXTRACT_MB で変更できる。0 は無制限）
+fn max_extract_bytes() -> u64 {
+    std::env::var("VTE_ZIP_MAX_EXTRACT_MB")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_MAX_EXTRACT_MB)
+        .saturating_mul(1024 * 1024)
+}
+
+/// 大文字小文字を区別しないファイルシステムを考慮した比較用のキー
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/").to_lowercase()
+}
+
+/// relative に "_1", "_2" … を付けた、used_paths に無いパス（見つけたものは used_paths に加える）
+fn unique_relative_path(relative: &Path, used_paths: &mut HashSet<String>) -> PathBuf {
+    let stem = relative
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = relative
+        .extension()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut counter = 1;
+    loop {
+        let candidate = relative.with_file_name(fit_file_name(
+            &stem,
+            &format!("_{}", counter),
+            &extension,
+            FilenameTarget::current(),
+        ));
+        if used_paths.insert(path_key(&candidate)) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+fn open_zip(archive_path: &str) -> Result<ZipArchive<BufReader<File>>, String> {
+    let path = Path::new(archive_path);
+    if !crate::path_utils::path_exists(path) {
+        return Err("指定されたファイルが存在しません".to_string());
+    }
+    let file = File::open(crate::path_utils::to_extended_length_path_if_needed(path))
+        .map_err(|e| format!("ZIP ファイルを開けませんでした: {}", e))?;
+    ZipArchive::new(BufReader::new(file)).map_err(|e| format!("ZIP ファイルとして読み込めませんでした: {}", e))
+}
+
+/// UTF-8 フラグの無いエントリ名の文字コードをアーカイブ単位で判定する（フラグ付きのエントリは判定に使わない）。
+/// UTF-8 フラグの無い日本語の ZIP はほぼ CP932 で、どちらでも読めなければ ZIP 標準の CP437 とみなす
+fn detect_name_encoding(archive: &mut ZipArchive<BufReader<File>>) -> Result<&'static str, String> {
+    let mut all_utf8 = true;
+    let mut all_cp932 = true;
+    for index in 0..archive.len() {
+        let file = archive
+            .by_index_raw(index)
+            .map_err(|e| format!("ZIP の読み込みに失敗しました: {}", e))?;
+        let raw = file.name_raw();
+        if has_utf8_flag(raw, file.name()) {
+            continue;
+        }
+        all_utf8 &= std::str::from_utf8(raw).is_ok();
+        all_cp932 &= SHIFT_JIS.decode_without_bom_handling_and_without_replacement(raw).is_some();
+    }
+
+    Ok(if all_utf8 {
+        "UTF-8"
+    } else if all_cp932 {
+        "CP932"
+    } else {
+        "CP437"
+    })
+}
+
+/// UTF-8 フラグ付きのエントリはそのまま、それ以外はアーカイブ単位で判定した文字コードでデコードする。
+/// fallback は zip クレートが UTF-8 フラグに従ってデコードした名前（フラグ付きと CP437 の場合に使う）
+fn decode_entry_name(raw: &[u8], fallback: &str, encoding: &str) -> String {
+    if has_utf8_flag(raw, fallback) {
+        return fallback.to_string();
+    }
+    match encoding {
+        "UTF-8" => String::from_utf8_lossy(raw).into_owned(),
+        "CP932" => SHIFT_JIS.decode_without_bom_handling(raw).0.into_owned(),
+        _ => fallback.to_string(),
+    }
+}
+
+/// UTF-8 フラグ（汎用フラグのビット 11、または Info-ZIP の Unicode Path 拡張フィールド）付きのエントリか。
+/// zip クレートはフラグがあれば名前を UTF-8 で、無ければ CP437 でデコードし、フラグ自体は公開していないため、
+/// デコードした名前で判定する（ASCII だけの名前はどちらでも同じ結果になるため区別しなくてよい）
+fn has_utf8_flag(raw: &[u8], decoded: &str) -> bool {
+    std::str::from_utf8(raw) == Ok(decoded)
+}
+
+/// 取り込む対象のエントリなら "audio" / "image" を返す（macOS のリソースフォークは除く）
+fn entry_kind(name: &str) -> Option<&'static str> {
+    let mut components = name.split(['/', '\\']);
+    if name.starts_with("__MACOSX") || components.any(|c| c.starts_with("._")) {
+        return None;
+    }
+    let ext = Path::new(name).extension()?.to_str()?.to_lowercase();
+    if AUDIO_EXTENSIONS.contains(&ext.as_str()) {
+        Some("audio")
+    } else if IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+        Some("image")
+    } else {
+        None
+    }
+}
+
+/// エントリ名を展開先からの相対パスにする。親ディレクトリへの参照を含む名前（Zip Slip）は展開しない
+fn entry_relative_path(name: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in name.split(['/', '\\']).filter(|c| !c.is_empty() && *c != ".") {
+        if component == ".." {
+            return None;
+        }
+        relative.push(sanitize_path_component(component, FilenameTarget::current()));
+    }
+    if relative.as_os_str().is_empty() {
+        None
+    } else {
+        Some(relative)
+    }
+}
+
+/// アーカイブごとの展開先（キャッシュ内の staging/<アーカイブ名>-<パスのハッシュ>）
+fn staging_dir_for(archive_path: &Path) -> Result<PathBuf, String> {
+    let stem = archive_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let hash: String = Sha256::digest(archive_path.to_string_lossy().as_bytes())
+        .iter()
+        .take(4)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let dir_name = crate::utils::fit_file_name(&stem, &format!("-{}", hash), "", FilenameTarget::current());
+    Ok(crate::cache::cache_root()?.join("staging").join(dir_name))
+}
@@ -0,0 +1,553 @@
+use std::collections::BTreeMap;
+
+use crate::models::{AlbumGroup, TrackGroup, TrackVariantFile, VariantMarker, VariantSet};
+
+/// 有り・無しで切り替わる差分（SE・BGM・ノイズ）
+struct ToggleRule {
+    kind: &'static str,
+    keywords: &'static [&'static str],
+    on: &'static str,
+    off: &'static str,
+    /// 変換元として優先する側（SE・BGM は本編に近い「有り」、ノイズは「無し」）
+    prefer_on: bool,
+}
+
+const TOGGLE_RULES: [ToggleRule; 3] = [
+    ToggleRule { kind: "se", keywords: &["se", "効果音"], on: "SE有り", off: "SE無し", prefer_on: true },
+    ToggleRule { kind: "bgm", keywords: &["bgm"], on: "BGM有り", off: "BGM無し", prefer_on: true },
+    ToggleRule { kind: "noise", keywords: &["ノイズ"], on: "ノイズ有り", off: "ノイズ無し", prefer_on: false },
+];
+
+// 長い語から順に照合する（"無し" を "無" より先に）
+const OFF_WORDS: [&str; 10] = ["無し", "なし", "ナシ", "抜き", "カット", "除去", "不使用", "オフ", "off", "無"];
+const ON_WORDS: [&str; 9] = ["有り", "あり", "アリ", "付き", "入り", "使用", "オン", "on", "有"];
+
+/// 形式ごとに分けたフォルダ名（"mp3"、"WAV版"、"mp3_320kbps" など）
+const FORMAT_NAMES: [&str; 8] = ["wav", "flac", "alac", "mp3", "m4a", "aac", "ogg", "opus"];
+const LOSSLESS_FORMATS: [&str; 3] = ["wav", "flac", "alac"];
+const HIRES_NAMES: [&str; 4] = ["ハイレゾ", "hi-res", "hi_res", "hires"];
+
+/// 差分フォルダ名に付く飾り（"SE無し版"、"mp3 (320kbps)" など）
+const DECORATION_WORDS: [&str; 10] = ["バージョン", "version", "ver", "音源", "形式", "フォルダ", "版", "kbps", "khz", "bit"];
+
+const MARKER_ORDER: [&str; 5] = ["format", "quality", "se", "bgm", "noise"];
+const DEFAULT_VARIANT_LABEL: &str = "標準";
+
+/// 走査したファイルをフォルダ構成とファイル名からアルバム・差分・曲にまとめる。
+/// "SE無し" や "mp3" などの差分フォルダはアルバムの区切りとみなさず、同じアルバムの差分として扱う
+#[tauri::command]
+pub async fn group_audio_files(file_paths: Vec<String>) -> Result<Vec<AlbumGroup>, String> {
+    Ok(group_files(&file_paths))
+}
+
+/// 1 ファイル分の解析結果
+struct Entry {
+    path: String,
+    /// 差分フォルダを除いたフォルダ（比較用キーと表示名）
+    album_key: Vec<(String, String)>,
+    markers: Vec<VariantMarker>,
+    track_key: String,
+    title: String,
+    extension: String,
+}
+
+fn group_files(file_paths: &[String]) -> Vec<AlbumGroup> {
+    let mut albums: BTreeMap<Vec<String>, Vec<Entry>> = BTreeMap::new();
+    for path in file_paths {
+        let entry = analyze_path(path);
+        let key = entry.album_key.iter().map(|(key, _)| key.clone()).collect();
+        albums.entry(key).or_default().push(entry);
+    }
+
+    let mut groups: Vec<AlbumGroup> = albums.into_values().map(build_album).collect();
+    groups.sort_by(|a, b| a.root_dir.cmp(&b.root_dir));
+    groups
+}
+
+fn analyze_path(path: &str) -> Entry {
+    let mut components: Vec<&str> = path.split(['/', '\\']).collect();
+    let file_name = components.pop().unwrap_or_default();
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, ext.to_ascii_lowercase()),
+        _ => (file_name, String::new()),
+    };
+
+    let mut markers = Vec::new();
+    let mut album_key = Vec::new();
+    for component in components.iter().filter(|c| !c.is_empty()) {
+        let analyzed = analyze_name(component, true);
+        let variant_only = !analyzed.markers.is_empty() && is_decoration(&analyzed.residue);
+        merge_markers(&mut markers, analyzed.markers);
+        if !variant_only {
+            album_key.push((comparison_key(&analyzed.residue), clean_title(&analyzed.residue)));
+        }
+    }
+
+    let analyzed = analyze_name(stem, false);
+    merge_markers(&mut markers, analyzed.markers);
+
+    Entry {
+        path: path.to_string(),
+        album_key,
+        markers,
+        track_key: comparison_key(&analyzed.residue),
+        title: clean_title(&analyzed.residue),
+        extension,
+    }
+}
+
+/// ファイルに近い側の目印を優先する（"SE有り/SE無し/..." のような入れ子では内側を採用）
+fn merge_markers(markers: &mut Vec<VariantMarker>, found: Vec<VariantMarker>) {
+    for marker in found {
+        markers.retain(|m| m.kind != marker.kind);
+        markers.push(marker);
+    }
+}
+
+struct AnalyzedName {
+    markers: Vec<VariantMarker>,
+    /// 目印を取り除いた残り（元の表記のまま）
+    residue: String,
+}
+
+fn analyze_name(name: &str, is_dir: bool) -> AnalyzedName {
+    let original: Vec<char> = name.chars().collect();
+    let folded: Vec<char> = original.iter().map(|&c| fold_char(c)).collect();
+    let mut removed = vec![false; original.len()];
+    let mut markers = Vec::new();
+
+    find_toggles(&folded, &mut removed, &mut markers);
+    if is_dir {
+        // 形式名は "mp3" や "WAV版" のようにフォルダ名がほぼ形式名だけのときに限る（"RJ123456 mp3" はアルバム名）
+        find_format(&original, &folded, &mut removed, &mut markers);
+    }
+
+    AnalyzedName {
+        markers,
+        residue: residue_of(&original, &removed),
+    }
+}
+
+fn find_toggles(chars: &[char], removed: &mut [bool], markers: &mut Vec<VariantMarker>) {
+    for rule in &TOGGLE_RULES {
+        let mut i = 0;
+        while i < chars.len() {
+            let Some(len) = rule.keywords.iter().find_map(|k| match_at(chars, i, k)) else {
+                i += 1;
+                continue;
+            };
+            // "base" や "close" の "se" は目印ではない
+            if chars[i].is_ascii() && i > 0 && chars[i - 1].is_ascii_alphabetic() {
+                i += 1;
+                continue;
+            }
+
+            let mut end = i + len;
+            while end < chars.len() && matches!(chars[end], ' ' | '_' | '-' | '・' | ':') {
+                end += 1;
+            }
+            let found = OFF_WORDS
+                .iter()
+                .find_map(|w| match_at(chars, end, w).map(|l| (l, rule.off)))
+                .or_else(|| ON_WORDS.iter().find_map(|w| match_at(chars, end, w).map(|l| (l, rule.on))));
+            match found {
+                // "SE only" の "on" のように英単語の途中で切れる場合は除く
+                Some((word_len, value)) if !chars.get(end + word_len).is_some_and(|c| c.is_ascii_alphabetic()) => {
+                    let stop = end + word_len;
+                    removed[i..stop].iter_mut().for_each(|r| *r = true);
+                    if !markers.iter().any(|m| m.kind == rule.kind) {
+                        markers.push(VariantMarker {
+                            kind: rule.kind.to_string(),
+                            value: value.to_string(),
+                        });
+                    }
+                    i = stop;
+                }
+                _ => i += 1,
+            }
+        }
+    }
+}
+
+fn find_format(original: &[char], folded: &[char], removed: &mut [bool], markers: &mut Vec<VariantMarker>) {
+    let candidates = FORMAT_NAMES
+        .iter()
+        .map(|name| ("format", *name, *name))
+        .chain(HIRES_NAMES.iter().map(|name| ("quality", *name, "ハイレゾ")));
+    for (kind, name, value) in candidates {
+        let Some(start) = (0..folded.len()).find(|&i| {
+            match_at(folded, i, name).is_some_and(|len| {
+                let before = i.checked_sub(1).map(|p| folded[p]);
+                let after = folded.get(i + len);
+                !before.is_some_and(|c| c.is_ascii_alphanumeric()) && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+            })
+        }) else {
+            continue;
+        };
+
+        let mut candidate = removed.to_vec();
+        let len = name.chars().count();
+        candidate[start..start + len].iter_mut().for_each(|r| *r = true);
+        if is_decoration(&residue_of(original, &candidate)) {
+            removed.copy_from_slice(&candidate);
+            markers.push(VariantMarker {
+                kind: kind.to_string(),
+                value: value.to_string(),
+            });
+            return;
+        }
+    }
+}
+
+fn match_at(chars: &[char], at: usize, word: &str) -> Option<usize> {
+    let len = word.chars().count();
+    let slice = chars.get(at..at + len)?;
+    slice.iter().copied().eq(word.chars()).then_some(len)
+}
+
+fn residue_of(original: &[char], removed: &[bool]) -> String {
+    original
+        .iter()
+        .zip(removed)
+        .filter(|(_, &removed)| !removed)
+        .map(|(&c, _)| c)
+        .collect()
+}
+
+/// 全角英数字・記号を半角に、英字を小文字にそろえる（1 文字ずつ置き換えるので位置は変わらない）
+fn fold_char(c: char) -> char {
+    let c = match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        c => c,
+    };
+    c.to_ascii_lowercase()
+}
+
+/// 飾り（数字・区切り・括弧・"版" など）しか残っていないか
+fn is_decoration(text: &str) -> bool {
+    let mut folded: String = text.chars().map(fold_char).collect();
+    for word in DECORATION_WORDS {
+        folded = folded.replace(word, "");
+    }
+    folded.chars().all(|c| c.is_ascii_digit() || !c.is_alphanumeric())
+}
+
+/// 比較用のキー。英数字・かな・漢字だけを残す
+fn comparison_key(text: &str) -> String {
+    text.chars().map(fold_char).filter(|c| c.is_alphanumeric()).collect()
+}
+
+/// 目印を取り除いた後に残る空の括弧や端の区切りを整える
+fn clean_title(text: &str) -> String {
+    let mut title = text.to_string();
+    for empty in ["()", "（）", "[]", "［］", "【】", "「」"] {
+        title = title.replace(empty, "");
+    }
+    title
+        .trim_matches(|c: char| c.is_whitespace() || matches!(c, '_' | '-' | '・' | '.'))
+        .to_string()
+}
+
+fn build_album(mut entries: Vec<Entry>) -> AlbumGroup {
+    add_extension_markers(&mut entries);
+
+    let root_dir = common_dir(&entries);
+    let name = entries
+        .first()
+        .and_then(|e| e.album_key.last())
+        .map(|(_, display)| display.clone())
+        .unwrap_or_default();
+    let product_id = crate::utils::detect_product_id(&root_dir)
+        .or_else(|| entries.iter().find_map(|e| crate::utils::detect_product_id(&e.path)));
+
+    let mut variants: BTreeMap<String, VariantSet> = BTreeMap::new();
+    let mut tracks: BTreeMap<String, TrackGroup> = BTreeMap::new();
+    for entry in entries {
+        let mut markers = entry.markers;
+        markers.sort_by_key(|m| MARKER_ORDER.iter().position(|k| *k == m.kind));
+        let label = variant_label(&markers);
+
+        tracks
+            .entry(entry.track_key.clone())
+            .or_insert_with(|| TrackGroup {
+                key: entry.track_key.clone(),
+                title: entry.title.clone(),
+                files: Vec::new(),
+            })
+            .files
+            .push(TrackVariantFile {
+                variant: label.clone(),
+                path: entry.path.clone(),
+            });
+
+        variants
+            .entry(label.clone())
+            .or_insert_with(|| VariantSet {
+                label,
+                markers,
+                files: Vec::new(),
+                recommended: false,
+            })
+            .files
+            .push(entry.path);
+    }
+
+    let mut variants: Vec<VariantSet> = variants.into_values().collect();
+    for variant in &mut variants {
+        variant.files.sort();
+    }
+    variants.sort_by_key(|v| v.markers.iter().map(marker_rank).collect::<Vec<_>>());
+    if let Some(best) = variants
+        .iter_mut()
+        .enumerate()
+        .max_by_key(|(index, v)| (v.files.len(), preference(v), std::cmp::Reverse(*index)))
+        .map(|(_, v)| v)
+    {
+        best.recommended = true;
+    }
+
+    let mut tracks: Vec<TrackGroup> = tracks.into_values().collect();
+    for track in &mut tracks {
+        track.files.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    tracks.sort_by(|a, b| a.files[0].path.cmp(&b.files[0].path));
+
+    AlbumGroup {
+        name,
+        root_dir,
+        product_id,
+        variants,
+        tracks,
+    }
+}
+
+/// 同じフォルダに "01.mp3" と "01.wav" が並ぶような場合は、拡張子を形式の目印にする
+fn add_extension_markers(entries: &mut [Entry]) {
+    let mut extensions: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+    for entry in entries.iter() {
+        extensions
+            .entry((variant_label(&entry.markers), entry.track_key.clone()))
+            .or_default()
+            .push(entry.extension.clone());
+    }
+
+    for entry in entries.iter_mut() {
+        if entry.extension.is_empty() || entry.markers.iter().any(|m| m.kind == "format") {
+            continue;
+        }
+        let mixed = extensions
+            .get(&(variant_label(&entry.markers), entry.track_key.clone()))
+            .is_some_and(|exts| exts.iter().any(|ext| *ext != entry.extension));
+        if mixed {
+            entry.markers.push(VariantMarker {
+                kind: "format".to_string(),
+                value: entry.extension.clone(),
+            });
+        }
+    }
+}
+
+fn variant_label(markers: &[VariantMarker]) -> String {
+    if markers.is_empty() {
+        return DEFAULT_VARIANT_LABEL.to_string();
+    }
+    let mut sorted: Vec<&VariantMarker> = markers.iter().collect();
+    sorted.sort_by_key(|m| MARKER_ORDER.iter().position(|k| *k == m.kind));
+    sorted.iter().map(|m| m.value.as_str()).collect::<Vec<_>>().join(" / ")
+}
+
+/// 差分の並び順（種類順、同じ種類なら優先する側が先）
+fn marker_rank(marker: &VariantMarker) -> (usize, bool, String) {
+    let kind = MARKER_ORDER.iter().position(|k| *k == marker.kind).unwrap_or(MARKER_ORDER.len());
+    (kind, !is_preferred(marker), marker.value.clone())
+}
+
+/// 変換元としての優先度。可逆形式、本編に近い側の目印が多いほど高い
+fn preference(variant: &VariantSet) -> i32 {
+    variant
+        .markers
+        .iter()
+        .map(|m| match m.kind.as_str() {
+            "format" if LOSSLESS_FORMATS.contains(&m.value.as_str()) => 2,
+            "format" => 0,
+            "quality" => 1,
+            _ if is_preferred(m) => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+fn is_preferred(marker: &VariantMarker) -> bool {
+    TOGGLE_RULES
+        .iter()
+        .find(|rule| rule.kind == marker.kind)
+        .map(|rule| (marker.value == rule.on) == rule.prefer_on)
+        .unwrap_or(true)
+}
+
+/// アルバムに属するファイルに共通する最も深いフォルダ
+fn common_dir(entries: &[Entry]) -> String {
+    let dirs: Vec<Vec<&str>> = entries
+        .iter()
+        .map(|e| {
+            let mut parts: Vec<&str> = e.path.split(['/', '\\']).collect();
+            parts.pop();
+            parts
+        })
+        .collect();
+    let Some(first) = dirs.first() else {
+        return String::new();
+    };
+
+    let len = (0..first.len())
+        .take_while(|&i| dirs.iter().all(|d| d.get(i) == Some(&first[i])))
+        .count();
+    // 元のパスの区切り文字を使って組み立て直す
+    let separator = if entries[0].path.contains('\\') && !entries[0].path.contains('/') { "\\" } else { "/" };
+    first[..len].join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn labels(group: &AlbumGroup) -> Vec<&str> {
+        group.variants.iter().map(|v| v.label.as_str()).collect()
+    }
+
+    fn recommended(group: &AlbumGroup) -> &str {
+        group.variants.iter().find(|v| v.recommended).map(|v| v.label.as_str()).unwrap_or_default()
+    }
+
+    fn markers(name: &str, is_dir: bool) -> Vec<(String, String)> {
+        analyze_name(name, is_dir).markers.into_iter().map(|m| (m.kind, m.value)).collect()
+    }
+
+    fn marker(kind: &str, value: &str) -> VariantMarker {
+        VariantMarker {
+            kind: kind.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn se_folders_are_variants_of_one_album() {
+        let groups = group_files(&paths(&[
+            "/music/RJ123456 作品/SE有り/01_はじめに.wav",
+            "/music/RJ123456 作品/SE有り/02_耳かき.wav",
+            "/music/RJ123456 作品/SE無し版/01_はじめに.wav",
+            "/music/RJ123456 作品/SE無し版/02_耳かき.wav",
+        ]));
+        assert_eq!(groups.len(), 1);
+        let group = &groups[0];
+        assert_eq!(group.name, "RJ123456 作品");
+        assert_eq!(group.root_dir, "/music/RJ123456 作品");
+        assert_eq!(group.product_id.as_deref(), Some("RJ123456"));
+        assert_eq!(labels(group), ["SE有り", "SE無し"]);
+        assert_eq!(recommended(group), "SE有り");
+        assert_eq!(group.tracks.len(), 2);
+        assert!(group.tracks.iter().all(|t| t.files.len() == 2));
+        assert_eq!(group.tracks[0].title, "01_はじめに");
+    }
+
+    #[test]
+    fn format_folders_prefer_lossless() {
+        let groups = group_files(&paths(&[
+            "C:\\作品\\mp3\\01 トラック.mp3",
+            "C:\\作品\\wav\\01 トラック.wav",
+        ]));
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].root_dir, "C:\\作品");
+        assert_eq!(labels(&groups[0]), ["mp3", "wav"]);
+        assert_eq!(recommended(&groups[0]), "wav");
+        assert_eq!(groups[0].tracks.len(), 1);
+    }
+
+    #[test]
+    fn noise_prefers_the_cleaned_variant() {
+        let groups = group_files(&paths(&["/a/作品/ノイズ有り/01.flac", "/a/作品/ノイズ無し/01.flac"]));
+        assert_eq!(labels(&groups[0]), ["ノイズ無し", "ノイズ有り"]);
+        assert_eq!(recommended(&groups[0]), "ノイズ無し");
+    }
+
+    #[test]
+    fn mixed_extensions_in_one_folder_become_format_variants() {
+        let groups = group_files(&paths(&["/a/作品/01.mp3", "/a/作品/01.wav", "/a/作品/02.wav"]));
+        assert_eq!(groups.len(), 1);
+        // 02 は wav しか無いため目印を付けない
+        assert_eq!(labels(&groups[0]), ["標準", "mp3", "wav"]);
+        assert_eq!(groups[0].tracks.len(), 2);
+        assert_eq!(groups[0].tracks[0].files.len(), 2);
+    }
+
+    #[test]
+    fn add_extension_markers_only_marks_tracks_with_several_extensions() {
+        let mut entries: Vec<Entry> = ["/a/01.mp3", "/a/01.wav", "/a/02.wav", "/a/wav/03.wav", "/a/03.mp3"]
+            .into_iter()
+            .map(analyze_path)
+            .collect();
+        add_extension_markers(&mut entries);
+        let formats: Vec<Option<&str>> = entries
+            .iter()
+            .map(|e| e.markers.iter().find(|m| m.kind == "format").map(|m| m.value.as_str()))
+            .collect();
+        // 03 は差分（wav フォルダ / 標準）が違うので拡張子の目印は付けない
+        assert_eq!(formats, [Some("mp3"), Some("wav"), None, Some("wav"), None]);
+    }
+
+    #[test]
+    fn toggles_need_a_word_boundary() {
+        assert_eq!(markers("SE無し", true), [("se".to_string(), "SE無し".to_string())]);
+        assert_eq!(markers("効果音なし", true), [("se".to_string(), "SE無し".to_string())]);
+        assert_eq!(markers("ＳＥ付き", false), [("se".to_string(), "SE有り".to_string())]);
+        assert_eq!(markers("BGM_off", true), [("bgm".to_string(), "BGM無し".to_string())]);
+        // "base" / "close" の "se" や "SE only" の "on" は目印ではない
+        assert!(markers("baseなし", true).is_empty());
+        assert!(markers("close on", false).is_empty());
+        assert!(markers("SE only", true).is_empty());
+    }
+
+    #[test]
+    fn format_names_only_when_the_folder_is_little_more_than_the_name() {
+        assert_eq!(markers("mp3", true), [("format".to_string(), "mp3".to_string())]);
+        assert_eq!(markers("ＷＡＶ版", true), [("format".to_string(), "wav".to_string())]);
+        assert_eq!(markers("mp3 (320kbps)", true), [("format".to_string(), "mp3".to_string())]);
+        assert_eq!(markers("ハイレゾ音源", true), [("quality".to_string(), "ハイレゾ".to_string())]);
+        assert!(markers("RJ123456 mp3", true).is_empty());
+        assert!(markers("mp3s", true).is_empty());
+        // ファイル名は形式名で分けない
+        assert!(markers("mp3", false).is_empty());
+    }
+
+    #[test]
+    fn decoration_is_digits_symbols_and_known_words() {
+        assert!(is_decoration(""));
+        assert!(is_decoration("版"));
+        assert!(is_decoration(" (320kbps)"));
+        assert!(is_decoration("_24bit_96kHz"));
+        assert!(!is_decoration("作品"));
+        assert!(!is_decoration("RJ123456 "));
+    }
+
+    #[test]
+    fn preference_favours_lossless_and_main_variants() {
+        let variant = |markers: Vec<VariantMarker>| VariantSet {
+            label: variant_label(&markers),
+            markers,
+            files: Vec::new(),
+            recommended: false,
+        };
+        assert_eq!(preference(&variant(vec![marker("format", "wav")])), 2);
+        assert_eq!(preference(&variant(vec![marker("format", "mp3")])), 0);
+        assert_eq!(preference(&variant(vec![marker("quality", "ハイレゾ")])), 1);
+        assert_eq!(preference(&variant(vec![marker("se", "SE有り"), marker("format", "flac")])), 3);
+        assert_eq!(preference(&variant(vec![marker("se", "SE無し")])), 0);
+        assert_eq!(preference(&variant(vec![marker("noise", "ノイズ無し")])), 1);
+        assert_eq!(preference(&variant(vec![marker("noise", "ノイズ有り")])), 0);
+    }
+}
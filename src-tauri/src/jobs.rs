@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, atomic::{AtomicU64, Ordering}};
+
+use futures::future::{self, Either};
+use tokio::sync::watch;
+
+/// 実行中のジョブ（変換・メタデータ読み込み・タグ書き込み）
+pub struct Job {
+    id: String,
+    cancel_tx: watch::Sender<bool>,
+}
+
+impl Job {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel_tx.borrow()
+    }
+
+    fn cancel(&self) {
+        let _ = self.cancel_tx.send(true);
+    }
+
+    /// キャンセルされるまで待機する
+    pub async fn cancelled(&self) {
+        let mut rx = self.cancel_tx.subscribe();
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// スコープを抜けるとレジストリからジョブを取り除く
+pub struct JobGuard(Arc<Job>);
+
+impl std::ops::Deref for JobGuard {
+    type Target = Arc<Job>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        if let Ok(mut jobs) = registry().lock() {
+            jobs.remove(&self.0.id);
+        }
+    }
+}
+
+static JOBS: OnceLock<Mutex<HashMap<String, Arc<Job>>>> = OnceLock::new();
+static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(1);
+
+fn registry() -> &'static Mutex<HashMap<String, Arc<Job>>> {
+    JOBS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// ジョブを登録する。フロントエンドがIDを指定した場合はそれを使い（結果を待たずにキャンセルできるように）、
+/// 未指定・重複時は採番する
+pub fn register(requested_id: Option<String>) -> JobGuard {
+    let mut jobs = registry().lock().unwrap_or_else(|e| e.into_inner());
+
+    let id = requested_id
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty() && !jobs.contains_key(id))
+        .unwrap_or_else(|| format!("job-{}", NEXT_JOB_ID.fetch_add(1, Ordering::SeqCst)));
+
+    let (cancel_tx, _) = watch::channel(false);
+    let job = Arc::new(Job { id: id.clone(), cancel_tx });
+    jobs.insert(id, Arc::clone(&job));
+    JobGuard(job)
+}
+
+/// future をキャンセル可能にして実行する。キャンセルされた場合は None を返す
+/// （future は破棄されるため、kill_on_drop を指定した子プロセスも終了する）
+pub async fn run_cancellable<F: Future>(job: &Job, fut: F) -> Option<F::Output> {
+    if job.is_cancelled() {
+        return None;
+    }
+
+    let fut = std::pin::pin!(fut);
+    let cancelled = std::pin::pin!(job.cancelled());
+    match future::select(fut, cancelled).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+#[tauri::command]
+pub async fn cancel_job(job_id: String) -> Result<bool, String> {
+    let job = registry()
+        .lock()
+        .map_err(|_| "ジョブ一覧の取得に失敗しました".to_string())?
+        .get(&job_id)
+        .cloned();
+
+    match job {
+        Some(job) => {
+            job.cancel();
+            Ok(true)
+        }
+        // 既に完了しているジョブ
+        None => Ok(false),
+    }
+}
+
+/// 書き込み途中の出力ファイル。keep() されずに破棄された場合（失敗・キャンセル）は削除する
+pub struct PartialOutput {
+    path: Option<PathBuf>,
+}
+
+impl PartialOutput {
+    pub fn new(path: &Path) -> Self {
+        Self { path: Some(path.to_path_buf()) }
+    }
+
+    pub fn keep(mut self) {
+        self.path = None;
+    }
+}
+
+impl Drop for PartialOutput {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let ep = crate::path_utils::to_extended_length_path_if_needed(&path);
+            if ep.exists() {
+                let _ = std::fs::remove_file(&ep);
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use tauri_plugin_fs::init as init_fs;
 use tauri_plugin_dialog::init as init_dialog;
 use tauri_plugin_opener::init as init_opener;
+use tauri::Manager;
 
 mod models;
 mod metadata;
@@ -11,6 +12,13 @@ mod processing;
 mod cache;
 mod convert;
 mod path_utils;
+mod retag;
+mod jobs;
+mod tags;
+mod grouping;
+mod artwork;
+mod thumbnail;
+mod cover;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -25,6 +33,10 @@ pub fn run() {
             #[cfg(desktop)]
             app.handle()
                 .plugin(tauri_plugin_updater::Builder::new().build())?;
+            // キャッシュは Tauri のアプリ用キャッシュディレクトリに置く
+            if let Ok(dir) = app.path().app_cache_dir() {
+                cache::set_app_cache_dir(dir);
+            }
             Ok(())
         })
         .plugin(init_fs())
@@ -35,11 +47,24 @@ pub fn run() {
             system_check::check_ffmpeg,
             system_check::ensure_ffmpeg_installed,
             metadata::extract_metadata,
+            metadata::write_tags,
+            metadata::parse_filename_tags,
             processing::process_audio_files,
             fs_scan::scan_directory_for_audio_files,
             fs_scan::scan_directory_for_image_files,
+            fs_scan::list_zip_archive,
+            fs_scan::import_zip_archive,
+            grouping::group_audio_files,
             cache::save_album_art_to_cache,
-            convert::convert_audio_files
+            cache::list_cache,
+            cache::cache_usage,
+            cache::clear_cache,
+            thumbnail::get_image_thumbnails,
+            cover::rank_cover_images,
+            convert::convert_audio_files,
+            convert::preview_output_paths,
+            retag::write_metadata,
+            jobs::cancel_job
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,190 @@
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS};
+
+use crate::models::{AudioMetadata, ExtendedTags, RedecodedField, WriteMetadataItem};
+
+/// Latin-1 として読まれた文字列（ID3v2 のエンコーディング 0 や ID3v1、ffprobe の出力）を元のバイト列に戻し、
+/// Shift_JIS / CP932 / EUC-JP（と UTF-8）として読み直す。日本語として自然に読めた場合だけ (読み直した文字列, 文字コード名) を返す
+pub(crate) fn redecode_latin1(text: &str) -> Option<(String, &'static str)> {
+    // U+0100 以上の文字があれば Latin-1 で読まれた文字列ではない
+    let bytes: Vec<u8> = text.chars().map(|c| u8::try_from(c).ok()).collect::<Option<_>>()?;
+    // 2 バイト文字を作れるだけの非 ASCII バイトが無ければ対象外
+    if bytes.iter().filter(|&&b| b >= 0x80).count() < 2 {
+        return None;
+    }
+    // UTF-8 として正しく読めるバイト列が Latin-1 の文章である可能性は低い（Shift_JIS として読むと誤判定しやすい）
+    if let Ok(utf8) = std::str::from_utf8(&bytes) {
+        return Some((utf8.to_string(), "UTF-8"));
+    }
+
+    // スコアが同じなら、Windows で作られたファイルに多い Shift_JIS を優先する
+    [SHIFT_JIS, EUC_JP]
+        .into_iter()
+        .filter_map(|encoding| {
+            let decoded = decode_strict(encoding, &bytes)?;
+            let score = japanese_score(&decoded)?;
+            Some((score, encoding, decoded))
+        })
+        .fold(None, |best: Option<(i32, &'static Encoding, String)>, candidate| match best {
+            Some(best) if best.0 >= candidate.0 => Some(best),
+            _ => Some(candidate),
+        })
+        .map(|(_, encoding, decoded)| {
+            let name = if encoding == SHIFT_JIS && uses_cp932_extensions(&bytes) {
+                "CP932"
+            } else {
+                encoding.name()
+            };
+            (decoded, name)
+        })
+}
+
+/// 不正なバイト列を含む場合は None（置換文字で埋めない）
+fn decode_strict(encoding: &'static Encoding, bytes: &[u8]) -> Option<String> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|s| s.into_owned())
+}
+
+/// 日本語らしさのスコア。かな・漢字・全角記号を含み、半角カナや Latin-1 の記号が目立たないものだけを採用する
+fn japanese_score(text: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut has_japanese = false;
+    for c in text.chars() {
+        score += match c {
+            c if c.is_ascii() => 0,
+            // ひらがな・カタカナ・CJK 記号・漢字・全角英数
+            '\u{3000}'..='\u{30FF}' | '\u{4E00}'..='\u{9FFF}' | '\u{FF01}'..='\u{FF60}' => {
+                has_japanese = true;
+                2
+            }
+            // CP932 の丸数字・ローマ数字
+            '\u{2160}'..='\u{217F}' | '\u{2460}'..='\u{24FF}' => 1,
+            // Latin-1 のアクセント付き文字は Shift_JIS では半角カナになりやすい
+            '\u{FF61}'..='\u{FF9F}' => -1,
+            _ => -2,
+        };
+    }
+    (has_japanese && score > 0).then_some(score)
+}
+
+/// NEC 特殊文字・IBM 拡張文字（CP932 で追加された領域）の先行バイトを含むか
+fn uses_cp932_extensions(bytes: &[u8]) -> bool {
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            0x87 | 0xED | 0xEE | 0xFA..=0xFC => return true,
+            0x81..=0x9F | 0xE0..=0xFC => i += 2,
+            _ => i += 1,
+        }
+    }
+    false
+}
+
+/// 文字化けしたテキスト項目を読み直し、読み直した項目を redecoded_fields に記録する
+pub(super) fn redecode_metadata(metadata: &mut AudioMetadata) {
+    let mut report = Vec::new();
+    let extended = &mut metadata.extended;
+    let fields: [(&str, &mut Option<String>); 21] = [
+        ("title", &mut metadata.title),
+        ("artist", &mut metadata.artist),
+        ("album_artist", &mut metadata.album_artist),
+        ("album", &mut metadata.album),
+        ("date", &mut metadata.date),
+        ("genre", &mut metadata.genre),
+        ("comment", &mut metadata.comment),
+        ("composer", &mut extended.composer),
+        ("lyricist", &mut extended.lyricist),
+        ("illustrator", &mut extended.illustrator),
+        ("circle", &mut extended.circle),
+        ("publisher", &mut extended.publisher),
+        ("copyright", &mut extended.copyright),
+        ("isrc", &mut extended.isrc),
+        ("catalog_number", &mut extended.catalog_number),
+        ("bpm", &mut extended.bpm),
+        ("title_sort", &mut extended.title_sort),
+        ("artist_sort", &mut extended.artist_sort),
+        ("album_sort", &mut extended.album_sort),
+        ("album_artist_sort", &mut extended.album_artist_sort),
+        ("composer_sort", &mut extended.composer_sort),
+    ];
+    for (name, value) in fields {
+        if let Some(text) = value {
+            if let Some((decoded, encoding)) = redecode_latin1(text) {
+                report.push(RedecodedField {
+                    field: name.to_string(),
+                    encoding: encoding.to_string(),
+                    original: std::mem::replace(text, decoded),
+                });
+            }
+        }
+    }
+
+    let lists = [("tags", &mut metadata.tags), ("voice_actors", &mut metadata.extended.voice_actors)];
+    for (name, values) in lists {
+        for value in values.iter_mut().flatten() {
+            if let Some((decoded, encoding)) = redecode_latin1(value) {
+                report.push(RedecodedField {
+                    field: name.to_string(),
+                    encoding: encoding.to_string(),
+                    original: std::mem::replace(value, decoded),
+                });
+            }
+        }
+    }
+
+    // 分けた値も読み直した文字列から作り直す
+    if report.iter().any(|f| f.field == "artist") {
+        metadata.artists = metadata.artist.as_deref().map(super::split_names).unwrap_or_default();
+    }
+    if report.iter().any(|f| f.field == "genre") {
+        metadata.genres = metadata.genre.as_deref().map(super::split_names).unwrap_or_default();
+    }
+    metadata.redecoded_fields = report;
+}
+
+/// 読み直した項目だけを書き戻す。ネイティブの書き込みでは非 ASCII の文字列は UTF-16（ID3v2.3）/ UTF-8 で書かれる
+pub(super) async fn write_back(file_path: &str, metadata: &AudioMetadata) -> Result<(), String> {
+    if metadata.redecoded_fields.is_empty() {
+        return Ok(());
+    }
+
+    let redecoded = |field: &str| metadata.redecoded_fields.iter().any(|f| f.field == field);
+    let pick = |field: &str, value: &Option<String>| if redecoded(field) { value.clone() } else { None };
+    let extended = &metadata.extended;
+    let item = WriteMetadataItem {
+        file_path: file_path.to_string(),
+        title: pick("title", &metadata.title),
+        artists: pick("artist", &metadata.artist).map(|artist| super::split_names(&artist)),
+        album_artist: pick("album_artist", &metadata.album_artist),
+        album: pick("album", &metadata.album),
+        track_number: None,
+        disk_number: None,
+        track_total: None,
+        disk_total: None,
+        date: pick("date", &metadata.date),
+        genre: pick("genre", &metadata.genre),
+        comment: pick("comment", &metadata.comment),
+        tags: if redecoded("tags") { metadata.tags.clone() } else { None },
+        album_artwork_path: None,
+        remove_artwork: false,
+        extended: ExtendedTags {
+            composer: pick("composer", &extended.composer),
+            lyricist: pick("lyricist", &extended.lyricist),
+            illustrator: pick("illustrator", &extended.illustrator),
+            voice_actors: if redecoded("voice_actors") { extended.voice_actors.clone() } else { None },
+            circle: pick("circle", &extended.circle),
+            publisher: pick("publisher", &extended.publisher),
+            copyright: pick("copyright", &extended.copyright),
+            isrc: pick("isrc", &extended.isrc),
+            catalog_number: pick("catalog_number", &extended.catalog_number),
+            bpm: pick("bpm", &extended.bpm),
+            compilation: None,
+            title_sort: pick("title_sort", &extended.title_sort),
+            artist_sort: pick("artist_sort", &extended.artist_sort),
+            album_sort: pick("album_sort", &extended.album_sort),
+            album_artist_sort: pick("album_artist_sort", &extended.album_artist_sort),
+            composer_sort: pick("composer_sort", &extended.composer_sort),
+        },
+    };
+    crate::retag::write_single_file(&item).await.map(|_| ())
+}
@@ -0,0 +1,377 @@
+use std::sync::OnceLock;
+
+use regex::{Captures, Regex};
+
+use crate::models::{AudioMetadata, FieldChange, FilenamePattern, FilenameTagProposal};
+use crate::utils::{parse_number, parse_number_pair};
+
+/// プレースホルダー名と AudioMetadata の項目名（正規表現パターンではグループ名に使う）
+const PLACEHOLDERS: [(&str, &str); 9] = [
+    ("title", "title"),
+    ("artist", "artist"),
+    ("album", "album"),
+    ("album_artist", "album_artist"),
+    ("track", "track_number"),
+    ("disc", "disk_number"),
+    ("date", "date"),
+    ("genre", "genre"),
+    ("comment", "comment"),
+];
+
+/// "tr03"、"Track 1"、"トラック1"、"第3話"、"#2" などの番号の前後に付く語
+const TRACK_PREFIX: &str = r"(?:(?i:tr(?:ack)?|no)\.?|トラック|第|#)";
+const TRACK_SUFFIX: &str = r"(?:話|章|曲|幕|夜|節|部|日目)";
+/// 番号とタイトルの区切り
+const SEPARATOR: &str = r"[\s_\-.．、,:：]";
+
+/// ユーザー定義のパターンの確からしさ（指定どおりに読めたものは組み込みより信頼する）
+const USER_PATTERN_CONFIDENCE: f32 = 0.9;
+
+struct CompiledPattern {
+    label: String,
+    regex: Regex,
+    /// 照合するパスの要素数（"{album}/{track}_{title}" なら親フォルダ名とファイル名の 2）
+    depth: usize,
+    confidence: f32,
+    /// 空の項目だけを埋める
+    fill_only: bool,
+}
+
+static BUILTIN_PATTERNS: OnceLock<Vec<CompiledPattern>> = OnceLock::new();
+
+/// 組み込みパターン。上から順に試し、最初に一致したものを使う
+fn builtin_patterns() -> &'static [CompiledPattern] {
+    BUILTIN_PATTERNS.get_or_init(|| {
+        // 番号だけのファイル名（"第3話"）は、"話" をタイトルとして読まないよう先に試す
+        let sources = [
+            ("{track}", format!(r"^{TRACK_PREFIX}?\s*(?P<track>\d{{1,3}}){TRACK_SUFFIX}?$"), 0.6),
+            ("{disc}-{track} {title}", format!(r"^(?:(?i:disc|cd)\s*)?(?P<disc>\d{{1,2}})[-_](?P<track>\d{{2,3}}){SEPARATOR}+(?P<title>.+)$"), 0.7),
+            ("トラック{track} {title}", format!(r"^{TRACK_PREFIX}\s*(?P<track>\d{{1,3}}){TRACK_SUFFIX}?{SEPARATOR}*(?P<title>.+)$"), 0.9),
+            ("{track}話 {title}", format!(r"^(?P<track>\d{{1,3}}){TRACK_SUFFIX}{SEPARATOR}*(?P<title>.+)$"), 0.85),
+            ("[{track}] {title}", r"^[\[(（【](?P<track>\d{1,3})[\])）】]\s*(?P<title>.+)$".to_string(), 0.8),
+            ("{track}_{title}", format!(r"^(?P<track>\d{{1,3}}){SEPARATOR}+(?P<title>.+)$"), 0.8),
+            ("{title}", r"^(?P<title>.+)$".to_string(), 0.3),
+        ];
+        sources
+            .into_iter()
+            .map(|(label, source, confidence)| CompiledPattern {
+                label: label.to_string(),
+                regex: Regex::new(&source).expect("組み込みパターンの正規表現が不正です"),
+                depth: 1,
+                confidence,
+                // ファイル名全体をタイトルにするだけのパターンで既存のタイトルを上書きしない
+                fill_only: label == "{title}",
+            })
+            .collect()
+    })
+}
+
+/// ファイル名から読み取った値を現在のタグに重ね、変わる項目を返す。ファイルには書き込まない
+pub(super) async fn propose(
+    file_paths: &[String],
+    patterns: &[FilenamePattern],
+    use_builtin: bool,
+) -> Result<Vec<FilenameTagProposal>, String> {
+    let user_patterns = patterns.iter().map(compile_user_pattern).collect::<Result<Vec<_>, _>>()?;
+    let builtin: &[CompiledPattern] = if use_builtin { builtin_patterns() } else { &[] };
+
+    let mut proposals = Vec::with_capacity(file_paths.len());
+    for file_path in file_paths {
+        // タグが読めないファイル（タグ無しの WAV など）は空のメタデータとの差分にする
+        let current = super::extract_metadata_internal(file_path).await.ok();
+        let mut metadata = current.unwrap_or_default();
+
+        let matched = user_patterns
+            .iter()
+            .chain(builtin)
+            .find_map(|pattern| match_path(pattern, file_path).map(|values| (pattern, values)));
+
+        let mut changes = Vec::new();
+        let (pattern, confidence) = match matched {
+            Some((pattern, values)) => {
+                for (field, value) in values {
+                    apply_value(&mut metadata, field, value, pattern.fill_only, &mut changes);
+                }
+                (Some(pattern.label.clone()), pattern.confidence)
+            }
+            None => (None, 0.0),
+        };
+
+        proposals.push(FilenameTagProposal {
+            file_path: file_path.clone(),
+            pattern,
+            confidence,
+            metadata,
+            changes,
+        });
+    }
+    Ok(proposals)
+}
+
+fn compile_user_pattern(pattern: &FilenamePattern) -> Result<CompiledPattern, String> {
+    let source = if pattern.is_regex {
+        pattern.pattern.clone()
+    } else {
+        placeholder_to_regex(&pattern.pattern)?
+    };
+    let regex = Regex::new(&source).map_err(|e| format!("パターンが正しくありません（{}）: {}", pattern.pattern, e))?;
+    Ok(CompiledPattern {
+        label: pattern.pattern.clone(),
+        regex,
+        depth: pattern.pattern.matches('/').count() + 1,
+        confidence: USER_PATTERN_CONFIDENCE,
+        fill_only: false,
+    })
+}
+
+/// "{track}_{title}" を正規表現にする。空白は全角を含む空白の並びに一致させる
+fn placeholder_to_regex(pattern: &str) -> Result<String, String> {
+    let mut source = String::from("^");
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        let Some(len) = rest[open..].find('}') else { break };
+        push_literal(&mut source, &rest[..open]);
+        source.push_str(&placeholder_regex(&rest[open + 1..open + len])?);
+        rest = &rest[open + len + 1..];
+    }
+    push_literal(&mut source, rest);
+    source.push('$');
+    Ok(source)
+}
+
+fn push_literal(source: &mut String, literal: &str) {
+    let mut chars = literal.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            source.push_str(r"\s+");
+        } else {
+            source.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+        }
+    }
+}
+
+fn placeholder_regex(name: &str) -> Result<String, String> {
+    Ok(match name {
+        "track" => format!(r"{TRACK_PREFIX}?\s*(?P<track>\d+){TRACK_SUFFIX}?"),
+        "disc" => r"(?:(?i:disc|cd)\s*)?(?P<disc>\d+)".to_string(),
+        "date" => r"(?P<date>\d{4}(?:[-./]\d{1,2}){0,2})".to_string(),
+        // 読み飛ばす部分
+        "*" => r"[^/]*?".to_string(),
+        name if PLACEHOLDERS.iter().any(|(placeholder, _)| *placeholder == name) => format!(r"(?P<{}>[^/]+?)", name),
+        name => return Err(format!("不明なプレースホルダーです: {{{}}}", name)),
+    })
+}
+
+/// パスの末尾（拡張子を除く）をパターンに照合し、(項目名, 値) を返す。値が空になる項目があれば不一致とする
+fn match_path(pattern: &CompiledPattern, file_path: &str) -> Option<Vec<(&'static str, String)>> {
+    let components: Vec<&str> = file_path.split(['/', '\\']).filter(|c| !c.is_empty()).collect();
+    if components.len() < pattern.depth {
+        return None;
+    }
+    let mut tail = components[components.len() - pattern.depth..].to_vec();
+    if let Some(file_name) = tail.last_mut() {
+        if let Some((stem, _)) = file_name.rsplit_once('.').filter(|(stem, _)| !stem.is_empty()) {
+            *file_name = stem;
+        }
+    }
+    let target = tail.join("/");
+
+    let captures = pattern.regex.captures(&target)?;
+    let mut values = Vec::new();
+    for (name, field) in PLACEHOLDERS {
+        if let Some(value) = captured_value(&captures, name) {
+            values.push((field, value?));
+        }
+    }
+    (!values.is_empty()).then_some(values)
+}
+
+/// グループが無ければ None、あるのに空なら Some(None)
+fn captured_value(captures: &Captures, name: &str) -> Option<Option<String>> {
+    let raw = captures.name(name)?.as_str();
+    let value = match name {
+        "track" | "disc" => parse_number(raw).map(|n| n.to_string()),
+        _ => {
+            let trimmed = raw.trim_matches(|c: char| c.is_whitespace() || matches!(c, '_' | '-'));
+            (!trimmed.is_empty()).then(|| trimmed.to_string())
+        }
+    };
+    Some(value)
+}
+
+fn apply_value(
+    metadata: &mut AudioMetadata,
+    field: &'static str,
+    value: String,
+    fill_only: bool,
+    changes: &mut Vec<FieldChange>,
+) {
+    let slot = match field {
+        "title" => &mut metadata.title,
+        "artist" => &mut metadata.artist,
+        "album" => &mut metadata.album,
+        "album_artist" => &mut metadata.album_artist,
+        "track_number" => &mut metadata.track_number,
+        "disk_number" => &mut metadata.disk_number,
+        "date" => &mut metadata.date,
+        "genre" => &mut metadata.genre,
+        "comment" => &mut metadata.comment,
+        _ => return,
+    };
+
+    let unchanged = match field {
+        // "3/10" と "3" は同じ番号として扱い、総数を残す
+        "track_number" | "disk_number" => {
+            let current = slot.as_deref().and_then(|v| parse_number_pair(v).0);
+            current.is_some() && current == value.parse().ok()
+        }
+        _ => slot.as_deref().map(str::trim) == Some(value.as_str()),
+    };
+    if unchanged || (fill_only && slot.as_deref().is_some_and(|v| !v.trim().is_empty())) {
+        return;
+    }
+
+    changes.push(FieldChange {
+        field: field.to_string(),
+        current: slot.clone(),
+        proposed: value.clone(),
+    });
+    *slot = Some(value);
+
+    match field {
+        "artist" => metadata.artists = metadata.artist.as_deref().map(super::split_names).unwrap_or_default(),
+        "genre" => metadata.genres = metadata.genre.as_deref().map(super::split_names).unwrap_or_default(),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 一致したパターンのラベルと、読み取った (項目名, 値)
+    type Matched = (&'static str, Vec<(&'static str, String)>);
+
+    /// 最初に一致した組み込みパターン
+    fn builtin_match(file_path: &str) -> Option<Matched> {
+        builtin_patterns()
+            .iter()
+            .find_map(|pattern| match_path(pattern, file_path).map(|values| (pattern.label.as_str(), values)))
+    }
+
+    fn values(list: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        list.iter().map(|(field, value)| (*field, value.to_string())).collect()
+    }
+
+    fn user_pattern(pattern: &str) -> CompiledPattern {
+        compile_user_pattern(&FilenamePattern {
+            pattern: pattern.to_string(),
+            is_regex: false,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn builtin_patterns_are_tried_in_order() {
+        let cases = [
+            ("/a/01_はじめに.wav", "{track}_{title}", values(&[("title", "はじめに"), ("track_number", "1")])),
+            ("/a/tr03 耳かき.mp3", "トラック{track} {title}", values(&[("title", "耳かき"), ("track_number", "3")])),
+            ("/a/Track.12 - Outro.flac", "トラック{track} {title}", values(&[("title", "Outro"), ("track_number", "12")])),
+            ("/a/トラック1.wav", "{track}", values(&[("track_number", "1")])),
+            ("/a/第3話.wav", "{track}", values(&[("track_number", "3")])),
+            ("/a/第3話 添い寝.wav", "トラック{track} {title}", values(&[("title", "添い寝"), ("track_number", "3")])),
+            ("/a/2話_お風呂.wav", "{track}話 {title}", values(&[("title", "お風呂"), ("track_number", "2")])),
+            ("/a/1-02 二曲目.mp3", "{disc}-{track} {title}", values(&[("title", "二曲目"), ("track_number", "2"), ("disk_number", "1")])),
+            ("/a/【04】おやすみ.wav", "[{track}] {title}", values(&[("title", "おやすみ"), ("track_number", "4")])),
+        ];
+        for (path, label, expected) in cases {
+            assert_eq!(builtin_match(path), Some((label, expected)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn full_width_digits_are_read_as_numbers() {
+        assert_eq!(
+            builtin_match("/a/０５ 朝.wav"),
+            Some(("{track}_{title}", values(&[("title", "朝"), ("track_number", "5")])))
+        );
+        assert_eq!(builtin_match("/a/第１２話.wav"), Some(("{track}", values(&[("track_number", "12")]))));
+    }
+
+    #[test]
+    fn leading_year_is_not_a_track_number() {
+        assert_eq!(
+            builtin_match("/a/2023_夏の思い出.wav"),
+            Some(("{title}", values(&[("title", "2023_夏の思い出")])))
+        );
+        assert_eq!(
+            builtin_match("/a/2023-08 ライブ.wav"),
+            Some(("{title}", values(&[("title", "2023-08 ライブ")])))
+        );
+    }
+
+    #[test]
+    fn title_fallback_only_fills_an_empty_title() {
+        let (label, found) = builtin_match("/a/タイトルだけ.wav").unwrap();
+        assert_eq!(label, "{title}");
+        let pattern = builtin_patterns().iter().find(|p| p.label == label).unwrap();
+        assert!(pattern.fill_only);
+        assert_eq!(builtin_patterns().iter().filter(|p| p.fill_only).count(), 1);
+
+        let mut metadata = AudioMetadata {
+            title: Some("既存のタイトル".to_string()),
+            ..Default::default()
+        };
+        let mut changes = Vec::new();
+        for (field, value) in found.clone() {
+            apply_value(&mut metadata, field, value, pattern.fill_only, &mut changes);
+        }
+        assert!(changes.is_empty());
+        assert_eq!(metadata.title.as_deref(), Some("既存のタイトル"));
+
+        let mut metadata = AudioMetadata::default();
+        for (field, value) in found {
+            apply_value(&mut metadata, field, value, pattern.fill_only, &mut changes);
+        }
+        assert_eq!(changes.len(), 1);
+        assert_eq!(metadata.title.as_deref(), Some("タイトルだけ"));
+    }
+
+    #[test]
+    fn same_track_number_keeps_the_total() {
+        let mut metadata = AudioMetadata {
+            track_number: Some("3/10".to_string()),
+            ..Default::default()
+        };
+        let mut changes = Vec::new();
+        apply_value(&mut metadata, "track_number", "3".to_string(), false, &mut changes);
+        assert!(changes.is_empty());
+        assert_eq!(metadata.track_number.as_deref(), Some("3/10"));
+    }
+
+    #[test]
+    fn placeholder_to_regex_escapes_literals_and_collapses_spaces() {
+        assert_eq!(
+            placeholder_to_regex("{title}  (CV.{artist})").unwrap(),
+            r"^(?P<title>[^/]+?)\s+\(CV\.(?P<artist>[^/]+?)\)$"
+        );
+        assert_eq!(placeholder_to_regex("{date}_{*}").unwrap(), r"^(?P<date>\d{4}(?:[-./]\d{1,2}){0,2})_[^/]*?$");
+        // 閉じていない "{" はそのまま文字として扱う
+        assert_eq!(placeholder_to_regex("a{title").unwrap(), r"^a\{title$");
+        assert!(placeholder_to_regex("{name}").unwrap_err().contains("{name}"));
+    }
+
+    #[test]
+    fn user_patterns_match_parent_folders() {
+        let pattern = user_pattern("{album}/{track} {title}");
+        assert_eq!(pattern.depth, 2);
+        assert_eq!(
+            match_path(&pattern, "C:\\音声\\作品名\\第2話　添い寝.wav"),
+            Some(values(&[("title", "添い寝"), ("album", "作品名"), ("track_number", "2")]))
+        );
+        // 項目が空になる一致は採用しない
+        let pattern = user_pattern("{artist}_{title}");
+        assert_eq!(match_path(&pattern, "/a/_曲名.wav"), None);
+    }
+}
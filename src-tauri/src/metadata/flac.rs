@@ -1,8 +1,9 @@
 use crate::models::AudioMetadata;
 
-pub async fn extract(file_path: &str) -> Result<AudioMetadata, String> {
+pub async fn extract(file_path: &str, covers: &super::CoverCache) -> Result<AudioMetadata, String> {
     let json = super::run_ffprobe(file_path).await?;
     let mut metadata = super::parse_common_metadata(&json).await;
-    metadata.album_art = super::extract_album_art(file_path).await;
+    metadata.pictures = super::extract_attached_pictures(file_path, &json, covers).await;
+    metadata.album_art = super::front_cover(&metadata.pictures);
     Ok(metadata)
 }
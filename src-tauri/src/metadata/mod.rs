@@ -1,30 +1,93 @@
-use base64::prelude::*;
 use serde_json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
 use std::process::Stdio;
+use std::sync::{Arc, Mutex};
 use tokio::process::Command;
+use tokio::sync::OnceCell;
 
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-use crate::models::AudioMetadata;
+use crate::models::{AlbumArt, AudioMetadata, EmbeddedPicture, ExtendedTags, FilenamePattern, FilenameTagProposal, WriteMetadataItem};
+use crate::tags::Picture;
 
 mod mp3;
 mod flac;
 mod wav;
 mod m4a;
+mod opus;
+mod native;
+mod encoding;
+mod filename;
 
-const SUPPORTED_EXTENSIONS: [&str; 4] = ["mp3", "flac", "wav", "m4a"];
+const SUPPORTED_EXTENSIONS: [&str; 7] = ["mp3", "flac", "wav", "m4a", "opus", "ogg", "oga"];
 
+/// repair_encoding が true なら、Shift_JIS などから読み直した項目を UTF-16 / UTF-8 のタグとして書き戻す
 #[tauri::command]
-pub async fn extract_metadata(file_path: String) -> Result<AudioMetadata, String> {
+pub async fn extract_metadata(file_path: String, repair_encoding: Option<bool>) -> Result<AudioMetadata, String> {
     if !crate::path_utils::path_exists(&file_path) {
         return Err("ファイルが見つかりません".to_string());
     }
 
-    extract_metadata_internal(&file_path).await
+    let metadata = extract_metadata_internal(&file_path).await?;
+    if repair_encoding.unwrap_or(false) {
+        encoding::write_back(&file_path, &metadata).await?;
+    }
+    Ok(metadata)
+}
+
+/// 1ファイルのタグを書き換え、書き込み後のメタデータを返す。
+/// 書き込みは write_metadata と同じ処理（MP3 / FLAC / Ogg / MP4 は FFmpeg を使わずタグ領域だけを書き換える）で、
+/// ジョブの登録や進捗イベントを伴わない 1 ファイル版
+#[tauri::command]
+pub async fn write_tags(item: WriteMetadataItem) -> Result<AudioMetadata, String> {
+    crate::retag::write_single_file(&item).await?;
+    extract_metadata_internal(&item.file_path).await
+}
+
+/// ファイル名（パターンに "/" を含めれば親フォルダ名も）からタグを読み取り、現在のタグとの差分を返す。
+/// patterns を先に、use_builtin が false でなければ組み込みパターン（"01_タイトル"、"tr03 タイトル"、"第3話" など）を後に試す
+#[tauri::command]
+pub async fn parse_filename_tags(
+    file_paths: Vec<String>,
+    patterns: Option<Vec<FilenamePattern>>,
+    use_builtin: Option<bool>,
+) -> Result<Vec<FilenameTagProposal>, String> {
+    filename::propose(&file_paths, &patterns.unwrap_or_default(), use_builtin.unwrap_or(true)).await
+}
+
+/// 同じカバー画像（埋め込み画像のハッシュが一致するもの）の抽出を1回にまとめるためのキャッシュ
+/// アルバム単位の一括読み込みで共有する
+#[derive(Default)]
+pub(crate) struct CoverCache {
+    covers: Mutex<HashMap<String, Arc<OnceCell<Option<AlbumArt>>>>>,
+}
+
+impl CoverCache {
+    async fn get_or_extract<F>(&self, key: Option<&str>, extract: F) -> Option<AlbumArt>
+    where
+        F: Future<Output = Option<AlbumArt>>,
+    {
+        let Some(key) = key else {
+            return extract.await;
+        };
+
+        let cell = {
+            let mut covers = self.covers.lock().unwrap_or_else(|e| e.into_inner());
+            Arc::clone(covers.entry(key.to_string()).or_default())
+        };
+        // 並行して同じカバーを要求された場合も、抽出は最初の1回だけ行われる
+        cell.get_or_init(|| extract).await.clone()
+    }
 }
 
 pub(crate) async fn extract_metadata_internal(file_path: &str) -> Result<AudioMetadata, String> {
+    extract_metadata_with_covers(file_path, &CoverCache::default()).await
+}
+
+pub(crate) async fn extract_metadata_with_covers(file_path: &str, covers: &CoverCache) -> Result<AudioMetadata, String> {
     let extension = std::path::Path::new(file_path)
         .extension()
         .and_then(|ext| ext.to_str())
@@ -35,13 +98,23 @@ pub(crate) async fn extract_metadata_internal(file_path: &str) -> Result<AudioMe
         _ => return Err("サポートされていないファイル形式です".to_string()),
     };
 
-    match ext.as_str() {
-        "mp3" => mp3::extract(file_path).await,
-        "flac" => flac::extract(file_path).await,
-        "wav" => wav::extract(file_path).await,
-        "m4a" => m4a::extract(file_path).await,
-        _ => Err("サポートされていないファイル形式です".to_string()),
-    }
+    // FFmpeg が無くても読め、プロセスも起動しないため、まずネイティブのタグリーダーを使う。
+    // 未対応の形式（ID3v2.2 など）や壊れたファイルの場合は ffprobe で読み直す
+    let mut metadata = match crate::tags::read_file(std::path::Path::new(file_path)) {
+        Ok(file_tags) => native::build_metadata(&file_tags, covers).await,
+        Err(_) => match ext.as_str() {
+            "mp3" => mp3::extract(file_path, covers).await?,
+            "flac" => flac::extract(file_path, covers).await?,
+            "wav" => wav::extract(file_path, covers).await?,
+            "m4a" => m4a::extract(file_path, covers).await?,
+            "opus" | "ogg" | "oga" => opus::extract(file_path, covers).await?,
+            _ => return Err("サポートされていないファイル形式です".to_string()),
+        },
+    };
+
+    // 古い同人音声作品などで Latin-1 として読まれた Shift_JIS のタグを読み直す
+    encoding::redecode_metadata(&mut metadata);
+    Ok(metadata)
 }
 
 pub(super) async fn run_ffprobe(file_path: &str) -> Result<serde_json::Value, String> {
@@ -49,6 +122,7 @@ pub(super) async fn run_ffprobe(file_path: &str) -> Result<serde_json::Value, St
         .await
         .unwrap_or_else(|| std::path::PathBuf::from("ffprobe"));
     let mut cmd = Command::new(ffprobe_path);
+    cmd.kill_on_drop(true);
     #[cfg(windows)]
     {
         use std::os::windows::process::CommandExt;
@@ -62,6 +136,12 @@ pub(super) async fn run_ffprobe(file_path: &str) -> Result<serde_json::Value, St
             "json",
             "-show_format",
             "-show_streams",
+            // 埋め込み画像のパケットは先頭に置かれるため、先頭数パケットのハッシュで画像の有無と同一性が分かる
+            "-show_packets",
+            "-show_data_hash",
+            "SHA256",
+            "-read_intervals",
+            "%+#16",
             &crate::path_utils::prepare_cmd_arg(file_path),
         ])
         .output()
@@ -120,18 +200,143 @@ pub(super) fn extract_txxx_tags(tags: &serde_json::Value) -> Option<Vec<String>>
     None
 }
 
-pub(super) async fn extract_album_art(file_path: &str) -> Option<String> {
+/// ffprobe の出力に含まれる埋め込み画像（attached_pic）ストリーム
+pub(super) struct AttachedPicture {
+    pub index: u64,
+    pub data_hash: Option<String>,
+    /// FFmpeg がストリームの comment に入れる画像種別（"Cover (back)" など）と title の説明
+    pub picture_type: u8,
+    pub description: String,
+}
+
+pub(super) fn find_attached_pictures(json_data: &serde_json::Value) -> Vec<AttachedPicture> {
+    let streams = json_data
+        .get("streams")
+        .and_then(|s| s.as_array())
+        .map(|s| s.as_slice())
+        .unwrap_or_default();
+    let packets = json_data
+        .get("packets")
+        .and_then(|p| p.as_array())
+        .map(|p| p.as_slice())
+        .unwrap_or_default();
+
+    streams
+        .iter()
+        .filter(|stream| {
+            stream.get("codec_type").and_then(|t| t.as_str()) == Some("video")
+                && stream
+                    .get("disposition")
+                    .and_then(|d| d.get("attached_pic"))
+                    .and_then(|v| v.as_i64())
+                    == Some(1)
+        })
+        .filter_map(|stream| {
+            let index = stream.get("index").and_then(|i| i.as_u64())?;
+            let data_hash = packets
+                .iter()
+                .find(|packet| packet.get("stream_index").and_then(|i| i.as_u64()) == Some(index))
+                .and_then(|packet| packet.get("data_hash"))
+                .and_then(|h| h.as_str())
+                .map(|h| h.to_string());
+            let tag = |key: &str| stream.get("tags").and_then(|tags| get_tag_value(tags, &[key]));
+            let picture_type = tag("comment")
+                .and_then(|name| crate::tags::PICTURE_TYPE_NAMES.iter().position(|n| n.eq_ignore_ascii_case(&name)))
+                .map(|t| t as u8)
+                .unwrap_or(3);
+            Some(AttachedPicture {
+                index,
+                data_hash,
+                picture_type,
+                description: tag("title").unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// 埋め込み画像があるときだけ ffmpeg で 1 枚ずつ取り出し、画像キャッシュに保存する。
+/// ハッシュが同じ画像は CoverCache で1回だけ抽出する
+pub(super) async fn extract_attached_pictures(
+    file_path: &str,
+    json_data: &serde_json::Value,
+    covers: &CoverCache,
+) -> Vec<EmbeddedPicture> {
+    let mut pictures = Vec::new();
+    for picture in find_attached_pictures(json_data) {
+        let image = covers
+            .get_or_extract(picture.data_hash.as_deref(), async {
+                let image = extract_album_art(file_path, Some(picture.index)).await?;
+                crate::cache::store_artwork(&image).ok()
+            })
+            .await;
+        if let Some(image) = image {
+            pictures.push(EmbeddedPicture {
+                picture_type: picture.picture_type,
+                description: picture.description,
+                image,
+            });
+        }
+    }
+    pictures
+}
+
+/// タグから直接読んだ画像を画像キャッシュに保存する。
+/// 同じアルバムのトラックは同じ画像を持つことが多いため、内容のハッシュが同じ画像は CoverCache で1回だけ保存する
+pub(super) async fn store_embedded_pictures<'a>(
+    pictures: impl IntoIterator<Item = &'a Picture>,
+    covers: &CoverCache,
+) -> Vec<EmbeddedPicture> {
+    let mut stored = Vec::new();
+    for picture in pictures {
+        // ffprobe の data_hash と混ざらないよう、種類を付けたキーにする
+        let hash: String = Sha256::digest(&picture.data)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let key = format!("sha256:{}", hash);
+        let image = covers
+            .get_or_extract(Some(&key), async { crate::cache::store_artwork(&picture.data).ok() })
+            .await;
+        if let Some(image) = image {
+            stored.push(EmbeddedPicture {
+                picture_type: picture.picture_type,
+                description: picture.description.clone(),
+                image,
+            });
+        }
+    }
+    stored
+}
+
+/// 表紙（種別 3）を優先し、無ければ最初の画像を使う
+pub(super) fn front_cover(pictures: &[EmbeddedPicture]) -> Option<AlbumArt> {
+    pictures
+        .iter()
+        .find(|p| p.picture_type == 3)
+        .or_else(|| pictures.first())
+        .map(|p| p.image.clone())
+}
+
+pub(super) async fn extract_album_art(file_path: &str, stream_index: Option<u64>) -> Option<Vec<u8>> {
     let ffmpeg_path = crate::system_check::get_ffmpeg_path()
         .await
         .unwrap_or_else(|| std::path::PathBuf::from("ffmpeg"));
     let mut cmd = Command::new(ffmpeg_path);
+    cmd.kill_on_drop(true);
     #[cfg(windows)]
     {
         use std::os::windows::process::CommandExt;
         cmd.creation_flags(CREATE_NO_WINDOW);
     }
+    let mut args: Vec<String> = vec!["-i".to_string(), crate::path_utils::prepare_cmd_arg(file_path)];
+    match stream_index {
+        Some(index) => args.extend(["-map".to_string(), format!("0:{}", index), "-frames:v".to_string(), "1".to_string()]),
+        None => args.push("-an".to_string()),
+    }
+    args.extend(["-vcodec", "copy", "-f", "image2pipe", "-"].map(String::from));
+
     let output = cmd
-        .args(["-i", &crate::path_utils::prepare_cmd_arg(file_path), "-an", "-vcodec", "copy", "-f", "image2pipe", "-"])
+        .args(&args)
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
         .output()
@@ -140,7 +345,7 @@ pub(super) async fn extract_album_art(file_path: &str) -> Option<String> {
     match output {
         Ok(output) => {
             if output.status.success() && !output.stdout.is_empty() {
-                Some(BASE64_STANDARD.encode(&output.stdout))
+                Some(output.stdout)
             } else {
                 None
             }
@@ -189,6 +394,8 @@ pub(super) async fn parse_common_metadata(json_data: &serde_json::Value) -> Audi
         album: None,
         track_number: None,
         disk_number: None,
+        track_total: None,
+        disk_total: None,
         date: None,
         genre: None,
         comment: None,
@@ -197,7 +404,13 @@ pub(super) async fn parse_common_metadata(json_data: &serde_json::Value) -> Audi
         sample_rate: None,
         codec: None,
         album_art: None,
+        pictures: Vec::new(),
         tags: None,
+        artists: Vec::new(),
+        genres: Vec::new(),
+        redecoded_fields: Vec::new(),
+        product_id: None,
+        extended: ExtendedTags::default(),
     };
 
     // OGG(=Opus)では stream.tags 側に主要タグがあることが多い。優先順位: stream.tags → format.tags
@@ -229,6 +442,7 @@ pub(super) async fn parse_common_metadata(json_data: &serde_json::Value) -> Audi
         metadata.date = get_tag_value(tags, &["date", "Date", "DATE", "year", "Year", "YEAR"]);
         metadata.genre = get_tag_value(tags, &["genre", "Genre", "GENRE"]);
         metadata.comment = get_tag_value(tags, &["comment", "Comment", "COMMENT", "DESCRIPTION"]);
+        metadata.product_id = get_tag_value(tags, &[crate::utils::PRODUCT_ID_TAG, "product_id"]);
 
         // TXXXフレームからカスタムタグを抽出（MP3向け）。他形式では存在しないことが多い
         let txxx = extract_txxx_tags(tags);
@@ -287,5 +501,79 @@ pub(super) async fn parse_common_metadata(json_data: &serde_json::Value) -> Audi
         }
     }
 
+    // FFmpeg は複数の値（Vorbis の同じキー・ID3v2.4 の null 区切り）を ";" で連結して返す
+    metadata.artists = metadata.artist.as_deref().map(split_names).unwrap_or_default();
+    metadata.genres = metadata.genre.as_deref().map(split_names).unwrap_or_default();
+    metadata.extended = parse_extended_tags(stream_tags, format_tags);
+
+    let lookup_total = |keys: &[&str]| {
+        stream_tags
+            .and_then(|tags| get_tag_value(tags, keys))
+            .or_else(|| format_tags.and_then(|tags| get_tag_value(tags, keys)))
+            .and_then(|total| crate::utils::parse_number(&total))
+    };
+    (metadata.track_number, metadata.track_total) = split_number(
+        metadata.track_number.take(),
+        lookup_total(&["TRACKTOTAL", "tracktotal", "TOTALTRACKS", "totaltracks"]),
+    );
+    (metadata.disk_number, metadata.disk_total) = split_number(
+        metadata.disk_number.take(),
+        lookup_total(&["DISCTOTAL", "disctotal", "TOTALDISCS", "totaldiscs"]),
+    );
     metadata
 }
+
+/// ffprobe のタグ名（FFmpeg が変換した名前・ID3 のフレーム ID・Vorbis / フリーフォームのキー）から拡張タグを読む
+fn parse_extended_tags(stream_tags: Option<&serde_json::Value>, format_tags: Option<&serde_json::Value>) -> ExtendedTags {
+    let lookup = |keys: &[&str]| {
+        stream_tags
+            .and_then(|tags| get_tag_value(tags, keys))
+            .or_else(|| format_tags.and_then(|tags| get_tag_value(tags, keys)))
+    };
+
+    ExtendedTags {
+        composer: lookup(&["composer", "Composer", "COMPOSER"]),
+        lyricist: lookup(&["lyricist", "LYRICIST", "TEXT"]),
+        illustrator: lookup(&["ILLUSTRATOR", "illustrator"]),
+        voice_actors: lookup(&["VOICE_ACTOR", "voice_actor", "CV"]).map(|names| split_names(&names)),
+        circle: lookup(&["LABEL", "label", "CIRCLE"]),
+        publisher: lookup(&["publisher", "PUBLISHER", "ORGANIZATION", "organization"]),
+        copyright: lookup(&["copyright", "Copyright", "COPYRIGHT"]),
+        isrc: lookup(&["ISRC", "isrc", "TSRC"]),
+        catalog_number: lookup(&["CATALOGNUMBER", "catalognumber"]),
+        bpm: lookup(&["BPM", "bpm", "TBPM", "tmpo"]),
+        compilation: lookup(&["compilation", "COMPILATION", "TCMP", "cpil"]).and_then(|v| parse_flag(&v)),
+        title_sort: lookup(&["title-sort", "sort_name", "TITLESORT", "TSOT"]),
+        artist_sort: lookup(&["artist-sort", "sort_artist", "ARTISTSORT", "TSOP"]),
+        album_sort: lookup(&["album-sort", "sort_album", "ALBUMSORT", "TSOA"]),
+        album_artist_sort: lookup(&["sort_album_artist", "ALBUMARTISTSORT", "TSO2"]),
+        composer_sort: lookup(&["sort_composer", "COMPOSERSORT", "TSOC"]),
+    }
+}
+
+/// ";" 区切りの名前の並びを分ける
+pub(super) fn split_names(text: &str) -> Vec<String> {
+    text.split(';')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// "3/12" や "03" を番号だけの文字列と総数に分ける。total は TRACKTOTAL などの別のタグから読んだ総数
+/// （"3/12" の総数を優先する）。数字として読めない番号はそのまま返す
+pub(super) fn split_number(raw: Option<String>, total: Option<u32>) -> (Option<String>, Option<u32>) {
+    let Some(raw) = raw else { return (None, total.filter(|&t| t > 0)) };
+    match crate::utils::parse_number_pair(&raw) {
+        (Some(number), pair_total) => (Some(number.to_string()), pair_total.or(total).filter(|&t| t > 0)),
+        (None, _) => (Some(raw), total.filter(|&t| t > 0)),
+    }
+}
+
+/// コンピレーションなどのフラグ（"1" / "true" / "0" / "false"）
+pub(super) fn parse_flag(text: &str) -> Option<bool> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
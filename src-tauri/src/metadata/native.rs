@@ -0,0 +1,108 @@
+use crate::models::{AudioMetadata, ExtendedTags};
+use crate::tags::{FileTags, FrameValue, StandardField, TagFormat};
+
+/// ネイティブのタグリーダーの結果を AudioMetadata に変換する（ffprobe 版の parse_common_metadata と同じ表記にする）
+pub(super) async fn build_metadata(file_tags: &FileTags, covers: &super::CoverCache) -> AudioMetadata {
+    let properties = &file_tags.properties;
+
+    let duration = match properties.duration_seconds {
+        Some(seconds) => super::parse_duration(&seconds.to_string()).await,
+        None => None,
+    };
+
+    let pictures = super::store_embedded_pictures(file_tags.pictures(), covers).await;
+
+    let total = |field| file_tags.text(field).and_then(|t| crate::utils::parse_number(&t));
+    let (track_number, track_total) =
+        super::split_number(file_tags.text(StandardField::TrackNumber), total(StandardField::TrackTotal));
+    let (disk_number, disk_total) =
+        super::split_number(file_tags.text(StandardField::DiscNumber), total(StandardField::DiscTotal));
+
+    AudioMetadata {
+        title: file_tags.text(StandardField::Title),
+        artist: file_tags.text(StandardField::Artist),
+        album_artist: file_tags.text(StandardField::AlbumArtist),
+        album: file_tags.text(StandardField::Album),
+        track_number,
+        disk_number,
+        track_total,
+        disk_total,
+        date: file_tags.text(StandardField::Date),
+        genre: file_tags.text(StandardField::Genre),
+        comment: file_tags.text(StandardField::Comment),
+        duration,
+        bitrate: properties.bit_rate.map(|b| format!("{} kbps", b / 1000)),
+        sample_rate: properties.sample_rate.map(|sr| format!("{} Hz", sr)),
+        codec: properties.codec.clone(),
+        album_art: super::front_cover(&pictures),
+        pictures,
+        tags: custom_tags(file_tags),
+        artists: file_tags.values(StandardField::Artist),
+        genres: file_tags.values(StandardField::Genre),
+        redecoded_fields: Vec::new(),
+        product_id: product_id(file_tags),
+        extended: extended_tags(file_tags),
+    }
+}
+
+fn extended_tags(file_tags: &FileTags) -> ExtendedTags {
+    let text = |field| file_tags.text(field);
+    ExtendedTags {
+        composer: text(StandardField::Composer),
+        lyricist: text(StandardField::Lyricist),
+        illustrator: text(StandardField::Illustrator),
+        voice_actors: Some(file_tags.values(StandardField::VoiceActor)).filter(|names| !names.is_empty()),
+        circle: text(StandardField::Circle),
+        publisher: text(StandardField::Publisher),
+        copyright: text(StandardField::Copyright),
+        isrc: text(StandardField::Isrc),
+        catalog_number: text(StandardField::CatalogNumber),
+        bpm: text(StandardField::Bpm),
+        compilation: text(StandardField::Compilation).and_then(|v| super::parse_flag(&v)),
+        title_sort: text(StandardField::TitleSort),
+        artist_sort: text(StandardField::ArtistSort),
+        album_sort: text(StandardField::AlbumSort),
+        album_artist_sort: text(StandardField::AlbumArtistSort),
+        composer_sort: text(StandardField::ComposerSort),
+    }
+}
+
+/// convert が書き込む商品 ID（TXXX:PRODUCT_ID / PRODUCT_ID / ----:com.apple.iTunes:PRODUCT_ID）
+fn product_id(file_tags: &FileTags) -> Option<String> {
+    let key = crate::utils::PRODUCT_ID_TAG;
+    [
+        (TagFormat::Id3v2, format!("TXXX:{}", key)),
+        (TagFormat::VorbisComment, key.to_string()),
+        (TagFormat::Mp4, format!("----:com.apple.iTunes:{}", key)),
+    ]
+    .iter()
+    .find_map(|(format, id)| match file_tags.find(*format, id) {
+        Some(FrameValue::Text(values)) => values.iter().map(|v| v.trim()).find(|v| !v.is_empty()).map(str::to_string),
+        _ => None,
+    })
+}
+
+/// convert / retag が書き込むカスタムタグ（";" 区切り）を読む
+fn custom_tags(file_tags: &FileTags) -> Option<Vec<String>> {
+    let text_of = |format: TagFormat, id: &str| match file_tags.find(format, id) {
+        Some(FrameValue::Text(values)) => Some(values.join(";")),
+        _ => None,
+    };
+
+    let raw = text_of(TagFormat::Id3v2, "TXXX:TAG")
+        // ffmpeg の `-metadata TXXX=TAG=...` は説明 "TXXX"、値 "TAG=..." の TXXX フレームになる
+        .or_else(|| text_of(TagFormat::Id3v2, "TXXX:TXXX").and_then(|v| v.strip_prefix("TAG=").map(str::to_string)))
+        .or_else(|| text_of(TagFormat::VorbisComment, "TAG"))
+        .or_else(|| text_of(TagFormat::Mp4, "----:com.apple.iTunes:TAG"))?;
+
+    let tags: Vec<String> = raw
+        .split(';')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if tags.is_empty() {
+        None
+    } else {
+        Some(tags)
+    }
+}
@@ -1,8 +1,42 @@
 use crate::models::AudioMetadata;
+use crate::tags::Picture;
 
-pub async fn extract(file_path: &str) -> Result<AudioMetadata, String> {
+/// Ogg コンテナ（Opus / Vorbis / .oga）のメタデータを抽出する
+pub async fn extract(file_path: &str, covers: &super::CoverCache) -> Result<AudioMetadata, String> {
     let json = super::run_ffprobe(file_path).await?;
     let mut metadata = super::parse_common_metadata(&json).await;
-    metadata.album_art = super::extract_album_art(file_path).await;
+
+    // Ogg のカバーアートは VorbisComment の METADATA_BLOCK_PICTURE に格納されるため、
+    // ffmpeg の image2pipe では取り出せないことが多い。まずタグから直接デコードし（プロセス起動なし）、
+    // 無ければ ffmpeg が画像ストリームとして認識している場合のみ抽出する。
+    metadata.pictures = super::store_embedded_pictures(&extract_block_pictures(&json), covers).await;
+    if metadata.pictures.is_empty() {
+        metadata.pictures = super::extract_attached_pictures(file_path, &json, covers).await;
+    }
+    metadata.album_art = super::front_cover(&metadata.pictures);
     Ok(metadata)
 }
+
+/// ffprobe の出力（stream.tags → format.tags の順）から METADATA_BLOCK_PICTURE を探し、すべての画像を返す。
+/// FFmpeg は同じキーの値を ";" で連結する（base64 には ";" が含まれない）
+fn extract_block_pictures(json_data: &serde_json::Value) -> Vec<Picture> {
+    let stream_tags = json_data
+        .get("streams")
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|stream| stream.get("tags"));
+    let format_tags = json_data.get("format").and_then(|f| f.get("tags"));
+
+    stream_tags
+        .chain(format_tags)
+        .filter_map(|tags| {
+            super::get_tag_value(
+                tags,
+                &["METADATA_BLOCK_PICTURE", "metadata_block_picture", "Metadata_Block_Picture"],
+            )
+        })
+        .map(|value| value.split(';').filter_map(crate::tags::decode_block_picture).collect::<Vec<_>>())
+        .find(|pictures| !pictures.is_empty())
+        .unwrap_or_default()
+}
@@ -1,13 +1,19 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct AudioMetadata {
     pub title: Option<String>,
     pub artist: Option<String>,
     pub album_artist: Option<String>,
     pub album: Option<String>,
+    /// 番号だけ（"3/12" は track_number = "3"、track_total = 12 に分ける。数字として読めない値はそのまま）
     pub track_number: Option<String>,
     pub disk_number: Option<String>,
+    /// トラック・ディスクの総数（Vorbis の TRACKTOTAL / DISCTOTAL も読む）
+    #[serde(default)]
+    pub track_total: Option<u32>,
+    #[serde(default)]
+    pub disk_total: Option<u32>,
     pub date: Option<String>,
     pub genre: Option<String>,
     pub comment: Option<String>,
@@ -15,8 +21,101 @@ pub struct AudioMetadata {
     pub bitrate: Option<String>,
     pub sample_rate: Option<String>,
     pub codec: Option<String>,
-    pub album_art: Option<String>,
+    /// 表紙（種別 3 の画像、無ければ最初の画像）
+    pub album_art: Option<AlbumArt>,
+    /// 埋め込まれているすべての画像（裏表紙・ブックレット・キャラクター画像など）
+    #[serde(default)]
+    pub pictures: Vec<EmbeddedPicture>,
     pub tags: Option<Vec<String>>,
+    /// アーティスト・ジャンルを 1 つずつ分けたもの（artist / genre は ";" で連結した表示用の値）
+    #[serde(default)]
+    pub artists: Vec<String>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    /// Shift_JIS などの文字化けを読み直した項目
+    #[serde(default)]
+    pub redecoded_fields: Vec<RedecodedField>,
+    /// PRODUCT_ID タグに書かれた商品 ID（RJ01234567 など）
+    #[serde(default)]
+    pub product_id: Option<String>,
+    #[serde(flatten)]
+    pub extended: ExtendedTags,
+}
+
+/// 作曲者・声優・サークル・ソート用の読みなど、基本項目以外のタグ。
+/// 書き込み時は None で既存値を維持、空文字（voice_actors は空配列）で削除する
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExtendedTags {
+    pub composer: Option<String>,
+    pub lyricist: Option<String>,
+    pub illustrator: Option<String>,
+    /// 声優（CV）
+    pub voice_actors: Option<Vec<String>>,
+    /// サークル・レーベル
+    pub circle: Option<String>,
+    pub publisher: Option<String>,
+    pub copyright: Option<String>,
+    pub isrc: Option<String>,
+    pub catalog_number: Option<String>,
+    pub bpm: Option<String>,
+    pub compilation: Option<bool>,
+    pub title_sort: Option<String>,
+    pub artist_sort: Option<String>,
+    pub album_sort: Option<String>,
+    pub album_artist_sort: Option<String>,
+    pub composer_sort: Option<String>,
+}
+
+/// Latin-1 として文字化けしていたため、別の文字コードで読み直したタグ項目
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedecodedField {
+    /// AudioMetadata の項目名（title / artist / ... / tags。拡張タグは composer / voice_actors などの ExtendedTags の項目名）
+    pub field: String,
+    /// 判定した文字コード（Shift_JIS / CP932 / EUC-JP / UTF-8）
+    pub encoding: String,
+    /// 読み直す前の文字列
+    pub original: String,
+}
+
+/// キャッシュに保存した埋め込み画像（内容の SHA-256 をキーにするため、同じ画像は1ファイルにまとまる）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlbumArt {
+    pub hash: String,
+    /// キャッシュファイルのパス（フロントエンドでは convertFileSrc で asset プロトコルの URL にする）
+    pub path: String,
+    pub mime: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size: u64,
+}
+
+/// 埋め込み画像 1 枚（画像はキャッシュに保存したもの）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmbeddedPicture {
+    /// APIC / FLAC の画像種別（3 = 表紙、4 = 裏表紙、5 = リーフレット、8 = 演奏者、18 = イラスト など）。
+    /// MP4 の covr には種別が無いため 3
+    pub picture_type: u8,
+    pub description: String,
+    #[serde(flatten)]
+    pub image: AlbumArt,
+}
+
+/// 変換時に埋め込む画像（EmbeddedPicture をそのまま渡してもよい）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PictureSource {
+    pub path: String,
+    #[serde(default = "default_picture_type")]
+    pub picture_type: u8,
+    #[serde(default)]
+    pub description: String,
+    /// 未指定なら画像の内容から判定する
+    #[serde(default)]
+    pub mime: Option<String>,
+}
+
+fn default_picture_type() -> u8 {
+    3
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,10 +123,12 @@ pub struct AudioFileResult {
     pub file_path: String,
     pub metadata: Option<AudioMetadata>,
     pub error: Option<String>,
+    pub cancelled: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProgressEvent {
+    pub job_id: String,
     pub current: usize,
     pub total: usize,
     pub file_path: String,
@@ -39,15 +140,26 @@ pub struct ConvertRequest {
     pub tracks: Vec<ConvertTrack>,
     pub album_data: ConvertAlbumData,
     pub output_settings: ConvertOutputSettings,
+    /// 総数が未指定のトラックに、変換するトラックから数えた総数（ディスクごとのトラック数・ディスク枚数）を書く
+    #[serde(default)]
+    pub auto_totals: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConvertTrack {
     pub source_path: String,
+    /// "3/12" のように総数を含めてもよい（disk_total / track_total の指定があればそちらを使う）
     pub disk_number: String,
     pub track_number: String,
+    #[serde(default)]
+    pub disk_total: Option<u32>,
+    #[serde(default)]
+    pub track_total: Option<u32>,
     pub title: String,
     pub artists: Vec<String>,
+    /// トラックごとの拡張タグ。未指定の項目はアルバムの値を使う
+    #[serde(flatten)]
+    pub extended: ExtendedTags,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -59,6 +171,15 @@ pub struct ConvertAlbumData {
     pub album_artwork_path: Option<String>,
     pub album_artwork_cache_path: Option<String>,
     pub album_artwork: Option<String>,
+    /// 埋め込む画像の一覧。指定した場合は album_artwork_path などの代わりにこれらをすべて埋め込む
+    #[serde(default)]
+    pub pictures: Vec<PictureSource>,
+    /// 商品 ID。未指定なら各トラックの元ファイルのパスから検出する
+    #[serde(default)]
+    pub product_id: Option<String>,
+    /// 全トラック共通の拡張タグ（サークル・著作権表示など）
+    #[serde(flatten)]
+    pub extended: ExtendedTags,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,27 +188,328 @@ pub struct ConvertOutputSettings {
     pub format: String,
     pub quality: String,
     pub overwrite_mode: String,
+    /// 出力パスのテンプレート（例: `{albumartist}/{date:yyyy} {album}/[{disc}-]{track:02} {title}`）
+    /// 未指定なら `{albumartist}/{album}/{disc:02}-{track:02} {title}`
+    #[serde(default)]
+    pub output_template: Option<String>,
+    /// ファイル名の制約を合わせる対象（"auto" | "windows" | "posix" | "portable"）。未指定は実行中のOS
+    #[serde(default)]
+    pub filename_target: Option<String>,
+    /// MP3 の ID3v2 のバージョン（3 / 4）。未指定は 3。
+    /// 3 では複数のアーティスト・ジャンルを 1 つのフレームに ";" 区切りで書く
+    #[serde(default)]
+    pub id3v2_version: Option<u8>,
+    /// 埋め込む画像の最大の幅・高さ（px）。超える画像は縮小する（PNG は上限のサイズに収まれば PNG、それ以外は JPEG）。未指定は 1500、0 は縮小しない
+    #[serde(default)]
+    pub artwork_max_dimension: Option<u32>,
+    /// 埋め込む画像の最大サイズ（バイト）。超える画像は JPEG の画質を下げて圧縮し直す。未指定は 1 MiB、0 は無制限
+    #[serde(default)]
+    pub artwork_max_bytes: Option<u64>,
+}
+
+/// 出力パスのプレビュー結果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutputPathPreview {
+    pub source_path: String,
+    pub relative_path: String,
+    pub output_path: String,
+    /// バッチ内の他のトラックと出力パスが重複している
+    pub collision: bool,
+    /// 出力先に同名のファイルが既に存在する
+    pub exists: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConvertProgress {
+    pub job_id: String,
+    /// 終了した（成功・失敗・キャンセルした）トラック数
     pub current: usize,
+    /// このイベントのトラックの番号（1 始まり）
+    pub track_index: usize,
     pub total: usize,
     pub current_file: String,
     pub status: String,
+    /// バッチ全体の進捗率（各トラックのエンコード進捗を含む）
     pub progress_percent: f64,
+    /// 現在のトラック内の進捗率（再生時間が取得できない場合は None）
+    pub file_progress_percent: Option<f64>,
+    /// エンコード速度（再生速度に対する倍率）
+    pub speed: Option<f64>,
+    /// バッチ全体の残り時間の見積もり（秒）
+    pub eta_seconds: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConvertResult {
+    pub job_id: String,
     pub success: bool,
+    pub cancelled: bool,
     pub converted_files: Vec<String>,
     pub failed_files: Vec<ConvertError>,
+    /// キャンセルにより変換されなかったトラックの入力パス
+    pub cancelled_files: Vec<String>,
+    pub file_reports: Vec<ConvertFileReport>,
     pub total_processed: usize,
 }
 
+/// 変換に成功したファイルごとの処理内容
+/// mode: "copied"（ストリームコピー） | "transcoded"（再エンコード）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConvertFileReport {
+    pub source_path: String,
+    pub output_path: String,
+    pub mode: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConvertError {
     pub source_path: String,
     pub error_message: String,
 }
+
+/// 再エンコードせずにタグを書き換える対象ファイルと、その値
+/// 各フィールドは None なら既存値を維持し、空文字なら既存のタグを削除する
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WriteMetadataItem {
+    pub file_path: String,
+    pub title: Option<String>,
+    pub artists: Option<Vec<String>>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<String>,
+    pub disk_number: Option<String>,
+    /// 総数。None は今の総数（track_number が "3/12" ならその総数）を残し、0 は消す
+    #[serde(default)]
+    pub track_total: Option<u32>,
+    #[serde(default)]
+    pub disk_total: Option<u32>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+    pub comment: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub album_artwork_path: Option<String>,
+    #[serde(default)]
+    pub remove_artwork: bool,
+    #[serde(flatten)]
+    pub extended: ExtendedTags,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WriteMetadataResult {
+    pub job_id: String,
+    pub success: bool,
+    pub cancelled: bool,
+    pub written_files: Vec<String>,
+    pub failed_files: Vec<ConvertError>,
+    pub cancelled_files: Vec<String>,
+    pub total_processed: usize,
+}
+
+/// ジョブ開始時に通知する。フロントエンドは job_id を cancel_job に渡して中断できる
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobStartedEvent {
+    pub job_id: String,
+    pub kind: String,
+    pub total: usize,
+}
+
+/// ZIP 内の取り込み対象のエントリ
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ZipEntryInfo {
+    /// 文字コードを判定してデコードしたエントリ名（ZIP 内のパス）
+    pub name: String,
+    pub size: u64,
+    /// "audio" / "image"
+    pub kind: String,
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZipImportResult {
+    pub archive_path: String,
+    pub staging_dir: String,
+    /// エントリ名の文字コード（UTF-8 / CP932 / CP437）
+    pub name_encoding: String,
+    /// アーカイブ名から検出した商品 ID
+    pub product_id: Option<String>,
+    pub audio_files: Vec<String>,
+    pub image_files: Vec<String>,
+    /// 展開できなかったエントリ（暗号化・未対応の圧縮方式）
+    pub skipped_entries: Vec<ZipSkippedEntry>,
+}
+
+/// ZIP の取り込みで展開しなかったエントリ
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZipSkippedEntry {
+    /// デコードしたエントリ名（ZIP 内のパス）
+    pub name: String,
+    pub reason: String,
+}
+
+/// ディレクトリ走査で見つけた音声ファイル
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScannedAudioFile {
+    pub path: String,
+    /// パス（ファイル名・フォルダ名）から検出した商品 ID
+    pub product_id: Option<String>,
+}
+
+/// 差分を見分ける目印（フォルダ名・ファイル名から検出）
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct VariantMarker {
+    /// "format" / "quality" / "se" / "bgm" / "noise"
+    pub kind: String,
+    /// "wav" / "ハイレゾ" / "SE無し" など
+    pub value: String,
+}
+
+/// 同じアルバムの 1 つの差分（"SE無し / wav" など）に属するファイル
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VariantSet {
+    /// 目印を並べた表示名。目印が無ければ "標準"
+    pub label: String,
+    pub markers: Vec<VariantMarker>,
+    pub files: Vec<String>,
+    /// 変換元としておすすめの差分（トラックが揃っていて、可逆形式・本編に近いもの）
+    pub recommended: bool,
+}
+
+/// 差分をまたいで同じ曲とみなしたファイル
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrackVariantFile {
+    /// VariantSet の label
+    pub variant: String,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrackGroup {
+    /// 目印を除いて正規化したファイル名（比較用）
+    pub key: String,
+    /// 目印を除いたファイル名（表示用）
+    pub title: String,
+    pub files: Vec<TrackVariantFile>,
+}
+
+/// フォルダ構成から推定したアルバム
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlbumGroup {
+    pub name: String,
+    /// アルバムに属するファイルに共通するフォルダ
+    pub root_dir: String,
+    pub product_id: Option<String>,
+    pub variants: Vec<VariantSet>,
+    pub tracks: Vec<TrackGroup>,
+}
+
+/// ファイル名からタグを読み取るパターン。
+/// is_regex が false なら "{track}_{title}" のようなプレースホルダー形式、true なら名前付きグループの正規表現
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilenamePattern {
+    pub pattern: String,
+    #[serde(default)]
+    pub is_regex: bool,
+}
+
+/// 現在のタグとファイル名から読み取った値の差分
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldChange {
+    /// AudioMetadata の項目名（title / track_number など）
+    pub field: String,
+    pub current: Option<String>,
+    pub proposed: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilenameTagProposal {
+    pub file_path: String,
+    /// 一致したパターン（どれにも一致しなければ None）
+    pub pattern: Option<String>,
+    /// 0.0〜1.0。組み込みパターンは曖昧なものほど低い
+    pub confidence: f32,
+    /// 現在のタグに読み取った値を反映したもの
+    pub metadata: AudioMetadata,
+    pub changes: Vec<FieldChange>,
+}
+
+/// キャッシュの索引に登録されたファイル
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheEntry {
+    pub path: String,
+    /// "artwork" / "thumbnails" / "album_art"（キャッシュ内のディレクトリ名）
+    pub kind: String,
+    pub size: u64,
+    /// 保存時に付けた名前（アルバム名など。無ければ None）
+    pub label: Option<String>,
+    /// UNIX 時刻（秒）
+    pub created_at: u64,
+    pub last_accessed_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheKindUsage {
+    pub kind: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// キャッシュの使用量（ディスク上の実際のファイルを数える）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheUsage {
+    pub root: String,
+    pub total_bytes: u64,
+    /// 索引に登録されたファイルの合計の上限（超えたら最近使われていないものから消す。0 は無制限）
+    pub max_bytes: u64,
+    pub kinds: Vec<CacheKindUsage>,
+}
+
+/// 画像一覧用のサムネイル
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageThumbnail {
+    pub path: String,
+    /// 縮小した JPEG のパス（小さい画像は元の画像のパス。作れなかった場合は None）
+    pub thumbnail_path: Option<String>,
+    /// 元の画像の形式・寸法・バイト数
+    pub mime: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size: u64,
+    pub error: Option<String>,
+}
+
+/// 表紙候補の点数の内訳
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CoverScoreBreakdown {
+    /// ファイル名・フォルダ名（"cover" / "表紙" などで加点、"back" / "ブックレット" などで減点）
+    pub filename: f32,
+    /// 正方形に近いほど高い
+    pub aspect_ratio: f32,
+    /// 短辺が大きいほど高い
+    pub resolution: f32,
+    /// アルバムのフォルダに近いほど高い
+    pub depth: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoverCandidate {
+    pub path: String,
+    /// 内訳の合計
+    pub score: f32,
+    pub breakdown: CoverScoreBreakdown,
+    /// 一致したファイル名・フォルダ名の語
+    pub hints: Vec<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// アルバムごとの表紙の候補
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoverSelection {
+    /// AlbumGroup の name / root_dir
+    pub album: String,
+    pub root_dir: String,
+    /// 最も点数の高い候補（候補が無ければ None）
+    pub best: Option<CoverCandidate>,
+    /// 点数の高い順
+    pub candidates: Vec<CoverCandidate>,
+}
@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[cfg(windows)]
 fn is_extended_prefix(p: &str) -> bool {
@@ -74,3 +75,18 @@ pub fn prepare_cmd_arg(path_str: &str) -> String {
     ep.to_string_lossy().to_string()
 }
 
+
+/// 一時ファイル名の連番（同じプロセス内で同時に作る一時ファイルを区別する）
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 書き換え用の一時ファイルパス（同一ディレクトリ・同一拡張子にして、rename を原子的にする。
+/// 拡張子を残すのは FFmpeg が出力形式を拡張子から決めるため）。
+/// 元の名前は上限の長さまで使われていることがあるため含めず、`.vte-<pid>-<連番>.<拡張子>` の短い名前にする
+pub fn temp_sibling_path(path: &Path) -> PathBuf {
+    let extension = path
+        .extension()
+        .map(|s| format!(".{}", s.to_string_lossy()))
+        .unwrap_or_default();
+    let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".vte-{}-{}.tmp{}", std::process::id(), counter, extension))
+}
@@ -2,13 +2,14 @@ use tauri::{AppHandle, Emitter};
 use futures::{stream, StreamExt};
 use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
 
-use crate::metadata::extract_metadata_internal;
-use crate::models::{AudioFileResult, ProgressEvent};
+use crate::metadata::{extract_metadata_with_covers, CoverCache};
+use crate::models::{AudioFileResult, JobStartedEvent, ProgressEvent};
 
 #[tauri::command]
 pub async fn process_audio_files(
     app_handle: AppHandle,
     file_paths: Vec<String>,
+    job_id: Option<String>,
 ) -> Result<Vec<AudioFileResult>, String> {
     let total = file_paths.len();
 
@@ -23,18 +24,34 @@ pub async fn process_audio_files(
         .map(|v| v.clamp(1, 64))
         .unwrap_or(default_concurrency);
 
+    let job = crate::jobs::register(job_id);
+    let _ = app_handle.emit(
+        "job-started",
+        &JobStartedEvent {
+            job_id: job.id().to_string(),
+            kind: "metadata".to_string(),
+            total,
+        },
+    );
+
     let app_handle = Arc::new(app_handle);
     let completed = Arc::new(AtomicUsize::new(0));
+    // 同じアルバムのトラックは同じカバーを持つことが多いため、抽出結果をバッチ内で共有する
+    let covers = Arc::new(CoverCache::default());
 
     let results: Vec<AudioFileResult> = stream::iter(file_paths.into_iter().enumerate())
         .map(|(index, file_path)| {
             let app_handle = Arc::clone(&app_handle);
             let completed = Arc::clone(&completed);
+            let job = Arc::clone(&job);
+            let covers = Arc::clone(&covers);
             async move {
                 let current_index = index + 1; // 表示用の現在処理中インデックス
+                let job_id = job.id().to_string();
 
                 // processing イベント
                 let progress = ProgressEvent {
+                    job_id: job_id.clone(),
                     current: current_index,
                     total,
                     file_path: file_path.clone(),
@@ -47,13 +64,38 @@ pub async fn process_audio_files(
                         file_path,
                         metadata: None,
                         error: Some("ファイルが見つかりません".to_string()),
+                        cancelled: false,
                     };
                 }
 
-                match extract_metadata_internal(&file_path).await {
+                // キャンセル時は未着手のファイルを飛ばし、実行中の ffprobe/ffmpeg も中断する
+                let extracted = match crate::jobs::run_cancellable(&job, extract_metadata_with_covers(&file_path, &covers)).await {
+                    Some(extracted) => extracted,
+                    None => {
+                        let finished = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                        let cancel_progress = ProgressEvent {
+                            job_id,
+                            current: finished,
+                            total,
+                            file_path: file_path.clone(),
+                            status: "cancelled".to_string(),
+                        };
+                        let _ = app_handle.emit("audio-processing-progress", &cancel_progress);
+
+                        return AudioFileResult {
+                            file_path,
+                            metadata: None,
+                            error: Some("キャンセルされました".to_string()),
+                            cancelled: true,
+                        };
+                    }
+                };
+
+                match extracted {
                     Ok(metadata) => {
                         let finished = completed.fetch_add(1, Ordering::SeqCst) + 1;
                         let final_progress = ProgressEvent {
+                            job_id: job_id.clone(),
                             current: finished,
                             total,
                             file_path: file_path.clone(),
@@ -65,11 +107,13 @@ pub async fn process_audio_files(
                             file_path,
                             metadata: Some(metadata),
                             error: None,
+                            cancelled: false,
                         }
                     }
                     Err(error) => {
                         let finished = completed.fetch_add(1, Ordering::SeqCst) + 1;
                         let error_progress = ProgressEvent {
+                            job_id: job_id.clone(),
                             current: finished,
                             total,
                             file_path: file_path.clone(),
@@ -81,6 +125,7 @@ pub async fn process_audio_files(
                             file_path,
                             metadata: None,
                             error: Some(error),
+                            cancelled: false,
                         }
                     }
                 }
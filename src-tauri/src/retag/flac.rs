@@ -0,0 +1,48 @@
+use super::{join_values, push_metadata, ArtworkAction};
+use crate::models::WriteMetadataItem;
+
+pub fn append_format_specific_args(
+    ffmpeg_args: &mut Vec<String>,
+    artwork_action: &ArtworkAction,
+    artwork_input_added: bool,
+    item: &WriteMetadataItem,
+) {
+    ffmpeg_args.extend(vec!["-map".to_string(), "0:a".to_string()]);
+    match artwork_action {
+        ArtworkAction::Replace(_) if artwork_input_added => {
+            // FLACの画像はMETADATA_BLOCK_PICTUREとして格納される
+            ffmpeg_args.extend(vec![
+                "-map".to_string(),
+                "1:v:0".to_string(),
+                "-disposition:v:0".to_string(),
+                "attached_pic".to_string(),
+                "-metadata:s:v:0".to_string(),
+                "title=Album cover".to_string(),
+                "-metadata:s:v:0".to_string(),
+                "comment=Cover (front)".to_string(),
+            ]);
+        }
+        ArtworkAction::Keep => {
+            ffmpeg_args.extend(vec!["-map".to_string(), "0:v?".to_string()]);
+        }
+        _ => {}
+    }
+
+    // メタデータ（VorbisComment）
+    push_metadata(ffmpeg_args, "-metadata", "TITLE", item.title.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "ARTIST", join_values(&item.artists).as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "ALBUM", item.album.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "ALBUMARTIST", item.album_artist.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "TRACKNUMBER", item.track_number.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "DISCNUMBER", item.disk_number.as_deref());
+    // 0 は空文字にして消す
+    let total = |total: Option<u32>| total.map(|t| if t > 0 { t.to_string() } else { String::new() });
+    push_metadata(ffmpeg_args, "-metadata", "TRACKTOTAL", total(item.track_total).as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "DISCTOTAL", total(item.disk_total).as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "DATE", item.date.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "GENRE", item.genre.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "COMMENT", item.comment.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "TAG", join_values(&item.tags).as_deref());
+
+    ffmpeg_args.extend(vec!["-c".to_string(), "copy".to_string()]);
+}
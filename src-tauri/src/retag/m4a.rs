@@ -0,0 +1,42 @@
+use super::{join_values, push_metadata, ArtworkAction};
+use crate::models::WriteMetadataItem;
+use crate::utils::format_number_pair;
+
+pub fn append_format_specific_args(
+    ffmpeg_args: &mut Vec<String>,
+    artwork_action: &ArtworkAction,
+    artwork_input_added: bool,
+    item: &WriteMetadataItem,
+) {
+    // Map audio and cover art (keep / replace / drop)
+    ffmpeg_args.extend(vec!["-map".to_string(), "0:a".to_string()]);
+    match artwork_action {
+        ArtworkAction::Replace(_) if artwork_input_added => {
+            ffmpeg_args.extend(vec![
+                "-map".to_string(),
+                "1:0".to_string(),
+                "-disposition:v:0".to_string(),
+                "attached_pic".to_string(),
+            ]);
+        }
+        ArtworkAction::Keep => {
+            ffmpeg_args.extend(vec!["-map".to_string(), "0:v?".to_string()]);
+        }
+        _ => {}
+    }
+
+    // Metadata (MP4/iTunes style)
+    push_metadata(ffmpeg_args, "-metadata", "title", item.title.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "artist", join_values(&item.artists).as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "album", item.album.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "album_artist", item.album_artist.as_deref());
+    let track = item.track_number.as_deref().map(|n| format_number_pair(n, item.track_total));
+    let disc = item.disk_number.as_deref().map(|n| format_number_pair(n, item.disk_total));
+    push_metadata(ffmpeg_args, "-metadata", "track", track.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "disc", disc.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "date", item.date.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "genre", item.genre.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "comment", item.comment.as_deref());
+
+    ffmpeg_args.extend(vec!["-c".to_string(), "copy".to_string()]);
+}
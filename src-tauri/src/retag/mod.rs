@@ -0,0 +1,311 @@
+use std::{fs, path::Path};
+
+use futures::{stream, StreamExt};
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use tauri::{AppHandle, Emitter};
+use tokio::process::Command;
+
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+mod mp3;
+mod flac;
+mod m4a;
+mod opus;
+mod wav;
+mod native;
+
+use crate::jobs::PartialOutput;
+use crate::models::{ConvertError, JobStartedEvent, ProgressEvent, WriteMetadataItem, WriteMetadataResult};
+
+/// カバーアートの扱い
+#[derive(Clone)]
+pub(super) enum ArtworkAction {
+    /// 既存の埋め込み画像をそのまま残す
+    Keep,
+    /// 指定した画像で置き換える
+    Replace(String),
+    /// 埋め込み画像を削除する
+    Remove,
+}
+
+fn resolve_artwork_action(item: &WriteMetadataItem) -> Result<ArtworkAction, String> {
+    if let Some(path) = &item.album_artwork_path {
+        let trimmed = path.trim();
+        if !trimmed.is_empty() {
+            if !crate::path_utils::path_exists(trimmed) {
+                return Err("アートワーク画像が見つかりません".to_string());
+            }
+            return Ok(ArtworkAction::Replace(trimmed.to_string()));
+        }
+    }
+
+    if item.remove_artwork {
+        Ok(ArtworkAction::Remove)
+    } else {
+        Ok(ArtworkAction::Keep)
+    }
+}
+
+/// `-metadata key=value` を追加する。None は既存値を維持（何も追加しない）、空文字は削除を意味する
+pub(super) fn push_metadata(ffmpeg_args: &mut Vec<String>, specifier: &str, key: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        ffmpeg_args.push(specifier.to_string());
+        ffmpeg_args.push(format!("{}={}", key, value.trim()));
+    }
+}
+
+pub(super) fn join_values(values: &Option<Vec<String>>) -> Option<String> {
+    values.as_ref().map(|v| v.join(";"))
+}
+
+/// 置き換えるアートワーク画像を読み込み、中身から判定した MIME と合わせて返す。
+/// 形式を判別できない画像は誤った MIME で埋め込まないよう拒否する
+pub(super) fn read_artwork_image(path: &str) -> Result<(Vec<u8>, &'static str), String> {
+    let data = fs::read(crate::path_utils::to_extended_length_path_if_needed(Path::new(path)))
+        .map_err(|e| format!("アートワーク画像の読み込みに失敗しました: {}", e))?;
+    let mime = crate::utils::sniff_image(&data).mime;
+    if mime == crate::utils::UNKNOWN_IMAGE_MIME {
+        return Err("アートワーク画像の形式を判別できません".to_string());
+    }
+    Ok((data, mime))
+}
+
+/// Ogg 系は画像ストリームを扱えないため、METADATA_BLOCK_PICTURE に入れる画像を用意する
+async fn resolve_block_picture(file_path: &str, action: &ArtworkAction) -> Result<Option<String>, String> {
+    let image_bytes = match action {
+        ArtworkAction::Replace(path) => Some(read_artwork_image(path)?.0),
+        // 既存の画像は ffmpeg でコピーされないため、抽出して書き戻す
+        ArtworkAction::Keep => crate::metadata::extract_metadata_internal(file_path)
+            .await
+            .ok()
+            .and_then(|m| m.album_art)
+            .and_then(|art| fs::read(crate::path_utils::to_extended_length_path_if_needed(Path::new(&art.path))).ok()),
+        ArtworkAction::Remove => None,
+    };
+
+    Ok(image_bytes.map(|bytes| crate::utils::build_metadata_block_picture(&bytes, crate::utils::sniff_image(&bytes).mime)))
+}
+
+/// write_metadata が 1 ファイルごとに行う書き込み。対応する形式はタグ領域を直接書き換え、
+/// それ以外や直接書き換えに失敗したファイルは FFmpeg で作り直す（metadata::write_tags などもこれを使う）
+pub(crate) async fn write_single_file(item: &WriteMetadataItem) -> Result<String, String> {
+    let source_path = Path::new(&item.file_path);
+    if !crate::path_utils::path_exists(source_path) {
+        return Err("ファイルが見つかりません".to_string());
+    }
+
+    let ext = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .ok_or_else(|| "サポートされていないファイル形式です".to_string())?;
+
+    let artwork_action = resolve_artwork_action(item)?;
+
+    // MP3 / FLAC / Ogg / MP4 はタグ領域だけを直接書き換える（WAV などは FFmpeg で作り直す）。
+    // 直接書き換えられない構造のファイルは FFmpeg で作り直す
+    if crate::tags::supports_writing(source_path) {
+        if let Err(native_error) = write_native_verified(item, &artwork_action).await {
+            write_with_ffmpeg(item, &ext, &artwork_action)
+                .await
+                .map_err(|e| format!("{}（FFmpeg での書き込みも失敗しました: {}）", native_error, e))?;
+        }
+        return Ok(item.file_path.clone());
+    }
+
+    write_with_ffmpeg(item, &ext, &artwork_action).await?;
+    Ok(item.file_path.clone())
+}
+
+/// タグ領域を元ファイルに直接書き込む。タグが余白に収まればその場で上書きし、収まらない場合だけ
+/// tags::write_file が一時ファイルに書き出して置き換える（音声データを丸ごと複製しない）。
+/// 書き込み後はタグを読み戻して検証する（native::write）
+async fn write_native_verified(item: &WriteMetadataItem, artwork_action: &ArtworkAction) -> Result<(), String> {
+    let source_path = Path::new(&item.file_path).to_path_buf();
+    let item = item.clone();
+    let artwork_action = artwork_action.clone();
+    // 同期 I/O のため専用のスレッドで行う。キャンセルされてもスレッドは最後まで書き込むため、途中のタグは残らない
+    tokio::task::spawn_blocking(move || native::write(&source_path, &item, &artwork_action))
+        .await
+        .map_err(|e| format!("タグの書き込みに失敗しました: {}", e))?
+}
+
+/// FFmpeg で一時ファイルに書き出し、検証してから元ファイルと置き換える
+async fn write_with_ffmpeg(item: &WriteMetadataItem, ext: &str, artwork_action: &ArtworkAction) -> Result<(), String> {
+    let source_path = Path::new(&item.file_path);
+    let mut ffmpeg_args: Vec<String> = vec![
+        "-i".to_string(),
+        crate::path_utils::prepare_cmd_arg(&item.file_path),
+    ];
+
+    let is_ogg = matches!(ext, "opus" | "ogg" | "oga");
+    let artwork_input_added = match artwork_action {
+        ArtworkAction::Replace(path) if !is_ogg => {
+            ffmpeg_args.push("-i".to_string());
+            ffmpeg_args.push(crate::path_utils::prepare_cmd_arg(path));
+            true
+        }
+        _ => false,
+    };
+
+    ffmpeg_args.push("-y".to_string());
+
+    match ext {
+        "mp3" => mp3::append_format_specific_args(&mut ffmpeg_args, artwork_action, artwork_input_added, item),
+        "flac" => flac::append_format_specific_args(&mut ffmpeg_args, artwork_action, artwork_input_added, item),
+        "m4a" => m4a::append_format_specific_args(&mut ffmpeg_args, artwork_action, artwork_input_added, item),
+        "opus" | "ogg" | "oga" => {
+            let block_picture = resolve_block_picture(&item.file_path, artwork_action).await?;
+            opus::append_format_specific_args(&mut ffmpeg_args, artwork_action, block_picture, item)
+        }
+        "wav" => wav::append_format_specific_args(&mut ffmpeg_args, artwork_action, item)?,
+        _ => return Err("サポートされていないファイル形式です".to_string()),
+    }
+
+    let temp_path = crate::path_utils::temp_sibling_path(source_path);
+    ffmpeg_args.push(crate::path_utils::prepare_cmd_arg(&temp_path.to_string_lossy()));
+    let partial_output = PartialOutput::new(&temp_path);
+
+    let ffmpeg_path = crate::system_check::get_ffmpeg_path()
+        .await
+        .unwrap_or_else(|| std::path::PathBuf::from("ffmpeg"));
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.kill_on_drop(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = cmd
+        .args(&ffmpeg_args)
+        .output()
+        .await
+        .map_err(|e| format!("ffmpegの実行に失敗しました: {}", e))?;
+
+    let temp_ep = crate::path_utils::to_extended_length_path_if_needed(&temp_path);
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("タグの書き込みに失敗しました: {}", error_msg));
+    }
+
+    if let Err(verification_error) = crate::convert::verify_output_file(&temp_ep).await {
+        return Err(format!("出力ファイルの検証に失敗しました: {}", verification_error));
+    }
+
+    // 検証済みの一時ファイルで元ファイルを置き換える
+    let source_ep = crate::path_utils::to_extended_length_path_if_needed(source_path);
+    fs::rename(&temp_ep, &source_ep)
+        .map_err(|e| format!("元ファイルの置き換えに失敗しました: {}", e))?;
+    partial_output.keep();
+
+    Ok(())
+}
+
+/// 1ファイル分の書き込み結果
+enum ItemOutcome {
+    Written(String),
+    Failed(ConvertError),
+    Cancelled(String),
+}
+
+#[tauri::command]
+pub async fn write_metadata(
+    app_handle: AppHandle,
+    items: Vec<WriteMetadataItem>,
+    job_id: Option<String>,
+) -> Result<WriteMetadataResult, String> {
+    let total = items.len();
+
+    // ストリームコピーのみで軽いため、読み込みと同程度の並列度にする
+    let cpu_cores = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4);
+    let default_concurrency = cpu_cores.clamp(1, 4);
+    let max_concurrency = std::env::var("VTE_RETAG_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .map(|v| v.clamp(1, 16))
+        .unwrap_or(default_concurrency);
+
+    let job = crate::jobs::register(job_id);
+    let _ = app_handle.emit(
+        "job-started",
+        &JobStartedEvent {
+            job_id: job.id().to_string(),
+            kind: "write-metadata".to_string(),
+            total,
+        },
+    );
+
+    let app_handle = Arc::new(app_handle);
+    let completed = Arc::new(AtomicUsize::new(0));
+
+    let results: Vec<ItemOutcome> = stream::iter(items.into_iter().enumerate())
+        .map(|(index, item)| {
+            let app_handle = Arc::clone(&app_handle);
+            let completed = Arc::clone(&completed);
+            let job = Arc::clone(&job);
+            async move {
+                let progress = ProgressEvent {
+                    job_id: job.id().to_string(),
+                    current: index + 1,
+                    total,
+                    file_path: item.file_path.clone(),
+                    status: "processing".to_string(),
+                };
+                let _ = app_handle.emit("write-metadata-progress", &progress);
+
+                // None はキャンセル（一時ファイルは破棄時に削除され、元ファイルはそのまま残る）
+                let result = crate::jobs::run_cancellable(&job, write_single_file(&item)).await;
+
+                let finished = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                let status = match &result {
+                    Some(Ok(_)) => "completed",
+                    Some(Err(_)) => "error",
+                    None => "cancelled",
+                };
+                let progress = ProgressEvent {
+                    job_id: job.id().to_string(),
+                    current: finished,
+                    total,
+                    file_path: item.file_path.clone(),
+                    status: status.to_string(),
+                };
+                let _ = app_handle.emit("write-metadata-progress", &progress);
+
+                match result {
+                    Some(Ok(path)) => ItemOutcome::Written(path),
+                    Some(Err(error_message)) => ItemOutcome::Failed(ConvertError {
+                        source_path: item.file_path,
+                        error_message,
+                    }),
+                    None => ItemOutcome::Cancelled(item.file_path),
+                }
+            }
+        })
+        .buffered(max_concurrency)
+        .collect()
+        .await;
+
+    let mut written_files: Vec<String> = Vec::new();
+    let mut failed_files: Vec<ConvertError> = Vec::new();
+    let mut cancelled_files: Vec<String> = Vec::new();
+    for r in results {
+        match r {
+            ItemOutcome::Written(path) => written_files.push(path),
+            ItemOutcome::Failed(err) => failed_files.push(err),
+            ItemOutcome::Cancelled(path) => cancelled_files.push(path),
+        }
+    }
+
+    Ok(WriteMetadataResult {
+        job_id: job.id().to_string(),
+        success: failed_files.is_empty() && cancelled_files.is_empty(),
+        cancelled: job.is_cancelled(),
+        written_files,
+        failed_files,
+        cancelled_files,
+        total_processed: total,
+    })
+}
@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use super::{join_values, ArtworkAction};
+use crate::models::WriteMetadataItem;
+use crate::tags::{FrameValue, Picture, StandardField, TagBlock, TagFormat};
+
+/// FFmpeg を使わずに path のタグ領域だけを書き換える（音声データは作り直さない）。同期 I/O のため spawn_blocking から呼ぶ
+pub(super) fn write(path: &Path, item: &WriteMetadataItem, artwork_action: &ArtworkAction) -> Result<(), String> {
+    let picture = match artwork_action {
+        ArtworkAction::Replace(path) => {
+            let (data, mime) = super::read_artwork_image(path)?;
+            Some(Picture {
+                picture_type: 3,
+                mime: mime.to_string(),
+                description: String::new(),
+                data,
+            })
+        }
+        _ => None,
+    };
+
+    crate::tags::write_file(path, |block| apply(block, item, artwork_action, picture))?;
+
+    // 書き込んだタグが読み戻せることを確認する
+    crate::tags::read_file(path)
+        .map(|_| ())
+        .map_err(|e| format!("書き込み後のタグの検証に失敗しました: {}", e))
+}
+
+fn apply(block: &mut TagBlock, item: &WriteMetadataItem, artwork_action: &ArtworkAction, picture: Option<Picture>) {
+    let fields = [
+        (StandardField::Title, item.title.as_deref()),
+        (StandardField::AlbumArtist, item.album_artist.as_deref()),
+        (StandardField::Album, item.album.as_deref()),
+        (StandardField::Date, item.date.as_deref()),
+        (StandardField::Comment, item.comment.as_deref()),
+    ];
+    for (field, value) in fields {
+        if let Some(value) = value {
+            block.set_field(field, value.trim());
+        }
+    }
+    // 番号は総数と合わせて書く（ID3v2 は "3/12"、Vorbis は TRACKTOTAL、MP4 は trkn の組）
+    block.set_number(StandardField::TrackNumber, item.track_number.as_deref(), item.track_total);
+    block.set_number(StandardField::DiscNumber, item.disk_number.as_deref(), item.disk_total);
+    // アーティスト・ジャンルは 1 つずつ別の値として書く（ジャンルは ";" 区切りの文字列を分ける）
+    if let Some(artists) = &item.artists {
+        block.set_values(StandardField::Artist, artists);
+    }
+    if let Some(genre) = &item.genre {
+        let genres: Vec<String> = genre.split(';').map(str::to_string).collect();
+        block.set_values(StandardField::Genre, &genres);
+    }
+    for (field, value) in crate::tags::extended_field_values(&item.extended) {
+        if let Some(value) = value {
+            block.set_field(field, value.trim());
+        }
+    }
+    // 声優も 1 人ずつ別の値にする（Vorbis は VOICE_ACTOR を複数、ID3v2.4 は NUL 区切り、MP4 は data を複数）
+    if let Some(voice_actors) = &item.extended.voice_actors {
+        block.set_values(StandardField::VoiceActor, voice_actors);
+    }
+
+    if let Some(tags) = join_values(&item.tags) {
+        set_custom_tags(block, &tags);
+    }
+
+    match artwork_action {
+        ArtworkAction::Keep => {}
+        ArtworkAction::Remove => block.remove_pictures(),
+        ArtworkAction::Replace(_) => {
+            block.remove_pictures();
+            if let Some(picture) = picture {
+                let id = block.picture_id();
+                block.set(id, FrameValue::Picture(picture));
+            }
+        }
+    }
+}
+
+/// カスタムタグ（";" 区切り）を形式ごとの場所に書く。読み込みは metadata::native::custom_tags
+fn set_custom_tags(block: &mut TagBlock, tags: &str) {
+    let id = match block.format {
+        TagFormat::Id3v2 => {
+            // FFmpeg 版が書いた `TXXX:TXXX` = "TAG=..." は新しい値と食い違うため消す
+            let legacy = matches!(
+                block.get("TXXX:TXXX"),
+                Some(FrameValue::Text(values)) if values.iter().any(|v| v.starts_with("TAG="))
+            );
+            if legacy {
+                block.remove("TXXX:TXXX");
+            }
+            "TXXX:TAG"
+        }
+        TagFormat::VorbisComment => "TAG",
+        TagFormat::Mp4 => "----:com.apple.iTunes:TAG",
+        TagFormat::Id3v1 | TagFormat::RiffInfo => return,
+    };
+
+    if tags.is_empty() {
+        block.remove(id);
+    } else {
+        block.set(id, FrameValue::Text(vec![tags.to_string()]));
+    }
+}
@@ -0,0 +1,41 @@
+use super::{join_values, push_metadata, ArtworkAction};
+use crate::models::WriteMetadataItem;
+
+pub fn append_format_specific_args(
+    ffmpeg_args: &mut Vec<String>,
+    artwork_action: &ArtworkAction,
+    block_picture: Option<String>,
+    item: &WriteMetadataItem,
+) {
+    // Oggはvideo/attached_picストリームを受け付けないため、画像はMETADATA_BLOCK_PICTUREで扱う
+    ffmpeg_args.extend(vec!["-map".to_string(), "0:a".to_string()]);
+
+    // Oggのタグはストリーム側に入っているため、ストリームのメタデータを直接上書きする
+    let specifier = "-metadata:s:a:0";
+    push_metadata(ffmpeg_args, specifier, "TITLE", item.title.as_deref());
+    push_metadata(ffmpeg_args, specifier, "ARTIST", join_values(&item.artists).as_deref());
+    push_metadata(ffmpeg_args, specifier, "ALBUM", item.album.as_deref());
+    push_metadata(ffmpeg_args, specifier, "ALBUMARTIST", item.album_artist.as_deref());
+    push_metadata(ffmpeg_args, specifier, "TRACKNUMBER", item.track_number.as_deref());
+    push_metadata(ffmpeg_args, specifier, "DISCNUMBER", item.disk_number.as_deref());
+    // 0 は空文字にして消す
+    let total = |total: Option<u32>| total.map(|t| if t > 0 { t.to_string() } else { String::new() });
+    push_metadata(ffmpeg_args, specifier, "TRACKTOTAL", total(item.track_total).as_deref());
+    push_metadata(ffmpeg_args, specifier, "DISCTOTAL", total(item.disk_total).as_deref());
+    push_metadata(ffmpeg_args, specifier, "DATE", item.date.as_deref());
+    push_metadata(ffmpeg_args, specifier, "GENRE", item.genre.as_deref());
+    push_metadata(ffmpeg_args, specifier, "COMMENT", item.comment.as_deref());
+    push_metadata(ffmpeg_args, specifier, "TAG", join_values(&item.tags).as_deref());
+
+    match (artwork_action, block_picture) {
+        (ArtworkAction::Remove, _) => {
+            push_metadata(ffmpeg_args, specifier, "METADATA_BLOCK_PICTURE", Some(""));
+        }
+        (_, Some(b64)) => {
+            push_metadata(ffmpeg_args, specifier, "METADATA_BLOCK_PICTURE", Some(&b64));
+        }
+        _ => {}
+    }
+
+    ffmpeg_args.extend(vec!["-c:a".to_string(), "copy".to_string()]);
+}
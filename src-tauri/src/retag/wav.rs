@@ -0,0 +1,28 @@
+use super::{join_values, push_metadata, ArtworkAction};
+use crate::models::WriteMetadataItem;
+
+pub fn append_format_specific_args(
+    ffmpeg_args: &mut Vec<String>,
+    artwork_action: &ArtworkAction,
+    item: &WriteMetadataItem,
+) -> Result<(), String> {
+    // ffmpegのWAV出力はRIFF INFOチャンクのみ対応で、画像は埋め込めない
+    if let ArtworkAction::Replace(_) = artwork_action {
+        return Err("WAVではカバーアートの埋め込みに対応していません".to_string());
+    }
+
+    ffmpeg_args.extend(vec!["-map".to_string(), "0:a".to_string()]);
+
+    push_metadata(ffmpeg_args, "-metadata", "title", item.title.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "artist", join_values(&item.artists).as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "album", item.album.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "track", item.track_number.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "date", item.date.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "genre", item.genre.as_deref());
+    push_metadata(ffmpeg_args, "-metadata", "comment", item.comment.as_deref());
+    // 拡張タグのうち INFO チャンクに対応する項目があるのは著作権表示（ICOP）だけ
+    push_metadata(ffmpeg_args, "-metadata", "copyright", item.extended.copyright.as_deref());
+
+    ffmpeg_args.extend(vec!["-c".to_string(), "copy".to_string()]);
+    Ok(())
+}
@@ -0,0 +1,270 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use super::{id3v2, read_at, vorbis, AudioProperties, FileTags, FrameValue, TagBlock, TagFormat};
+
+const BLOCK_STREAMINFO: u8 = 0;
+const BLOCK_PADDING: u8 = 1;
+const BLOCK_VORBIS_COMMENT: u8 = 4;
+const BLOCK_PICTURE: u8 = 6;
+
+/// メタデータブロックの最大長（長さは 24bit）
+const MAX_BLOCK_LEN: usize = 0xFF_FFFF;
+
+/// メタデータブロック（PADDING は中身を読まない）と、音声フレームの開始位置
+struct MetadataBlocks {
+    blocks: Vec<(u8, Vec<u8>)>,
+    audio_offset: u64,
+}
+
+/// start は "fLaC" の位置（先頭に ID3v2 が付いている場合はその後ろ）
+fn read_blocks(file: &mut File, file_len: u64, start: u64) -> Result<MetadataBlocks, String> {
+    let mut blocks = Vec::new();
+    let mut offset = start + 4;
+    loop {
+        let header = read_at(file, file_len, offset, 4)
+            .map_err(|_| "FLAC のメタデータブロックが途中で切れています".to_string())?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7F;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        offset += 4;
+
+        if block_type == 0x7F {
+            return Err("FLAC のメタデータブロックが不正です".to_string());
+        }
+        let data = if block_type == BLOCK_PADDING {
+            Vec::new()
+        } else {
+            read_at(file, file_len, offset, length)
+                .map_err(|_| "FLAC のメタデータブロックが途中で切れています".to_string())?
+        };
+        blocks.push((block_type, data));
+
+        offset += length as u64;
+        if is_last {
+            break;
+        }
+    }
+    Ok(MetadataBlocks { blocks, audio_offset: offset })
+}
+
+/// VORBIS_COMMENT と PICTURE ブロックを1つのタグブロックにまとめる
+fn build_tag_block(blocks: &[(u8, Vec<u8>)]) -> TagBlock {
+    let mut block = blocks
+        .iter()
+        .find(|(t, _)| *t == BLOCK_VORBIS_COMMENT)
+        .and_then(|(_, data)| vorbis::parse_comments(data))
+        .unwrap_or_else(|| TagBlock::new(TagFormat::VorbisComment));
+
+    for picture in blocks
+        .iter()
+        .filter(|(t, _)| *t == BLOCK_PICTURE)
+        .filter_map(|(_, data)| vorbis::parse_picture_block(data))
+    {
+        block.push("METADATA_BLOCK_PICTURE", FrameValue::Picture(picture));
+    }
+    block
+}
+
+pub(super) fn read(file: &mut File, file_len: u64, start: u64) -> Result<FileTags, String> {
+    let mut tags = Vec::new();
+
+    let metadata = read_blocks(file, file_len, start)?;
+    let mut properties = AudioProperties {
+        codec: Some("flac".to_string()),
+        ..Default::default()
+    };
+    if let Some((_, data)) = metadata.blocks.iter().find(|(t, _)| *t == BLOCK_STREAMINFO) {
+        parse_stream_info(data, &mut properties);
+    }
+    properties.bit_rate = super::average_bit_rate(
+        file_len.saturating_sub(metadata.audio_offset),
+        properties.duration_seconds,
+    );
+    tags.push(build_tag_block(&metadata.blocks));
+
+    if start > 0 {
+        // 非標準だが ID3v2 が前置されたファイルもあるため、読めれば優先度の低いタグとして使う
+        if let Ok(tag) = read_at(file, file_len, 0, start as usize) {
+            if let Ok(block) = id3v2::parse(&tag) {
+                tags.push(block);
+            }
+        }
+    }
+
+    Ok(FileTags { properties, tags })
+}
+
+/// STREAMINFO: サンプルレート 20bit / チャンネル数-1 3bit / ビット深度-1 5bit / 総サンプル数 36bit
+fn parse_stream_info(data: &[u8], properties: &mut AudioProperties) {
+    let Some(bytes) = data.get(10..18) else { return };
+    let packed = u64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]);
+    let sample_rate = (packed >> 44) as u32;
+    let channels = ((packed >> 41) & 0b111) as u16 + 1;
+    let total_samples = packed & 0xF_FFFF_FFFF;
+
+    if sample_rate > 0 {
+        properties.sample_rate = Some(sample_rate);
+        if total_samples > 0 {
+            properties.duration_seconds = Some(total_samples as f64 / sample_rate as f64);
+        }
+    }
+    properties.channels = Some(channels);
+}
+
+/// VORBIS_COMMENT と PICTURE ブロックを書き換える。既存のメタデータ領域（PADDING 含む）に収まればその場で上書きする
+pub(super) fn write(
+    mut file: File,
+    file_len: u64,
+    start: u64,
+    path: &Path,
+    update: impl FnOnce(&mut TagBlock),
+) -> Result<(), String> {
+    let metadata = read_blocks(&mut file, file_len, start)?;
+
+    let vendor = metadata
+        .blocks
+        .iter()
+        .find(|(t, _)| *t == BLOCK_VORBIS_COMMENT)
+        .and_then(|(_, data)| vorbis::vendor(data))
+        .unwrap_or_else(|| vorbis::DEFAULT_VENDOR.to_string());
+    let mut tag = build_tag_block(&metadata.blocks);
+    update(&mut tag);
+
+    // STREAMINFO を先頭に、SEEKTABLE / APPLICATION / CUESHEET はそのまま残す
+    let mut blocks: Vec<(u8, Vec<u8>)> = metadata
+        .blocks
+        .into_iter()
+        .filter(|(t, _)| !matches!(*t, BLOCK_PADDING | BLOCK_VORBIS_COMMENT | BLOCK_PICTURE))
+        .collect();
+    if blocks.first().map(|(t, _)| *t) != Some(BLOCK_STREAMINFO) {
+        return Err("FLAC の STREAMINFO が見つかりません".to_string());
+    }
+    blocks.push((BLOCK_VORBIS_COMMENT, vorbis::serialize_comments(&tag, &vendor, false)));
+    for frame in &tag.frames {
+        if let FrameValue::Picture(picture) = &frame.value {
+            blocks.push((BLOCK_PICTURE, vorbis::serialize_picture(picture)));
+        }
+    }
+    if blocks.iter().any(|(_, data)| data.len() > MAX_BLOCK_LEN) {
+        return Err("FLAC のメタデータブロックに収まらない大きさの画像です".to_string());
+    }
+
+    let needed: usize = blocks.iter().map(|(_, data)| 4 + data.len()).sum();
+    let available = (metadata.audio_offset - start - 4) as usize;
+
+    // 余りがちょうど 0 か、PADDING ブロックのヘッダー（4 バイト）以上あればその場で書ける
+    if needed == available || needed + 4 <= available {
+        let padding = (needed < available).then(|| available - needed - 4);
+        let bytes = serialize_blocks(&blocks, padding);
+        return super::overwrite_at(&mut file, start + 4, &bytes);
+    }
+
+    let bytes = serialize_blocks(&blocks, Some(super::DEFAULT_PADDING));
+    super::rewrite_file(path, file, |source, writer| {
+        // 前置された ID3v2 と "fLaC" はそのまま残す
+        super::copy_range(source, 0, Some(start + 4), writer)?;
+        writer.write_all(&bytes)?;
+        super::copy_range(source, metadata.audio_offset, None, writer)
+    })
+}
+
+fn serialize_blocks(blocks: &[(u8, Vec<u8>)], padding: Option<usize>) -> Vec<u8> {
+    let mut out = Vec::new();
+    let count = blocks.len();
+    for (index, (block_type, data)) in blocks.iter().enumerate() {
+        let is_last = index + 1 == count && padding.is_none();
+        push_block_header(&mut out, *block_type, data.len(), is_last);
+        out.extend_from_slice(data);
+    }
+    if let Some(padding) = padding {
+        push_block_header(&mut out, BLOCK_PADDING, padding, true);
+        out.resize(out.len() + padding, 0);
+    }
+    out
+}
+
+fn push_block_header(out: &mut Vec<u8>, block_type: u8, len: usize, is_last: bool) {
+    out.push(block_type | if is_last { 0x80 } else { 0 });
+    out.extend_from_slice(&(len as u32).to_be_bytes()[1..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::tests::temp_file;
+    use crate::tags::{Picture, StandardField};
+
+    const AUDIO: [u8; 6] = [0xFF, 0xF8, 0x69, 0x08, 0xAA, 0xBB];
+
+    /// STREAMINFO（44.1kHz / 2ch / 44100 サンプル）+ VORBIS_COMMENT + PADDING + 音声
+    fn flac_with_padding(name: &str, padding: usize) -> std::path::PathBuf {
+        let mut stream_info = vec![0u8; 34];
+        let packed: u64 = (44100 << 44) | (1 << 41) | (15 << 36) | 44100;
+        stream_info[10..18].copy_from_slice(&packed.to_be_bytes());
+        let mut tag = TagBlock::new(TagFormat::VorbisComment);
+        tag.set_field(StandardField::Title, "before");
+        let blocks = vec![
+            (BLOCK_STREAMINFO, stream_info),
+            (BLOCK_VORBIS_COMMENT, vorbis::serialize_comments(&tag, "reference libFLAC", false)),
+        ];
+
+        let mut bytes = b"fLaC".to_vec();
+        bytes.extend_from_slice(&serialize_blocks(&blocks, Some(padding)));
+        bytes.extend_from_slice(&AUDIO);
+        temp_file(name, &bytes)
+    }
+
+    fn read_back(path: &Path) -> (FileTags, MetadataBlocks) {
+        let tags = crate::tags::read_file(path).unwrap();
+        let (mut file, file_len) = crate::tags::open_with_len(path, false).unwrap();
+        (tags, read_blocks(&mut file, file_len, 0).unwrap())
+    }
+
+    fn audio_of(path: &Path, metadata: &MetadataBlocks) -> Vec<u8> {
+        std::fs::read(path).unwrap()[metadata.audio_offset as usize..].to_vec()
+    }
+
+    #[test]
+    fn small_edit_reuses_padding() {
+        let path = flac_with_padding("flac-reuse.flac", 1024);
+        let before = std::fs::metadata(&path).unwrap().len();
+        crate::tags::write_file(&path, |block| {
+            block.set_values(StandardField::Artist, &["A".to_string(), "B".to_string()])
+        })
+        .unwrap();
+
+        let (tags, metadata) = read_back(&path);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), before);
+        assert_eq!(tags.values(StandardField::Artist), ["A", "B"]);
+        assert_eq!(tags.text(StandardField::Title).as_deref(), Some("before"));
+        assert_eq!(tags.properties.sample_rate, Some(44100));
+        assert_eq!(tags.properties.channels, Some(2));
+        let vendor = metadata.blocks.iter().find(|(t, _)| *t == BLOCK_VORBIS_COMMENT).and_then(|(_, d)| vorbis::vendor(d));
+        assert_eq!(vendor.as_deref(), Some("reference libFLAC"));
+        assert_eq!(audio_of(&path, &metadata), AUDIO);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn picture_larger_than_padding_grows_metadata() {
+        let path = flac_with_padding("flac-grow.flac", 64);
+        let picture = Picture {
+            picture_type: 3,
+            mime: "image/jpeg".to_string(),
+            description: String::new(),
+            data: vec![0xAB; 4096],
+        };
+        crate::tags::write_file(&path, |block| block.add_picture(picture.clone())).unwrap();
+
+        let (tags, metadata) = read_back(&path);
+        assert_eq!(tags.pictures().collect::<Vec<_>>(), [&picture]);
+        assert_eq!(tags.text(StandardField::Title).as_deref(), Some("before"));
+        assert_eq!(metadata.blocks.last().map(|(t, _)| *t), Some(BLOCK_PADDING));
+        let used: usize = metadata.blocks.iter().map(|(_, data)| 4 + data.len()).sum::<usize>();
+        assert_eq!(metadata.audio_offset as usize - 4 - used, crate::tags::DEFAULT_PADDING);
+        assert_eq!(audio_of(&path, &metadata), AUDIO);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
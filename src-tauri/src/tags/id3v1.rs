@@ -0,0 +1,68 @@
+use super::{decode_latin1, TagBlock, TagFormat};
+
+/// ID3v1 のジャンル番号（Winamp 拡張の 80〜 は使われることが少ないため省略）
+const GENRES: [&str; 80] = [
+    "Blues", "Classic Rock", "Country", "Dance", "Disco", "Funk", "Grunge", "Hip-Hop",
+    "Jazz", "Metal", "New Age", "Oldies", "Other", "Pop", "R&B", "Rap",
+    "Reggae", "Rock", "Techno", "Industrial", "Alternative", "Ska", "Death Metal", "Pranks",
+    "Soundtrack", "Euro-Techno", "Ambient", "Trip-Hop", "Vocal", "Jazz+Funk", "Fusion", "Trance",
+    "Classical", "Instrumental", "Acid", "House", "Game", "Sound Clip", "Gospel", "Noise",
+    "AlternRock", "Bass", "Soul", "Punk", "Space", "Meditative", "Instrumental Pop", "Instrumental Rock",
+    "Ethnic", "Gothic", "Darkwave", "Techno-Industrial", "Electronic", "Pop-Folk", "Eurodance", "Dream",
+    "Southern Rock", "Comedy", "Cult", "Gangsta", "Top 40", "Christian Rap", "Pop/Funk", "Jungle",
+    "Native American", "Cabaret", "New Wave", "Psychadelic", "Rave", "Showtunes", "Trailer", "Lo-Fi",
+    "Tribal", "Acid Punk", "Acid Jazz", "Polka", "Retro", "Musical", "Rock & Roll", "Hard Rock",
+];
+
+pub(super) fn genre_name(index: u8) -> Option<&'static str> {
+    GENRES.get(index as usize).copied()
+}
+
+/// ファイル末尾 128 バイトの ID3v1 / v1.1 タグを解析する。フレーム ID は ID3v2 に揃える
+pub(super) fn parse(tag: &[u8]) -> Option<TagBlock> {
+    if tag.len() != 128 || !tag.starts_with(b"TAG") {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| {
+        let bytes = &tag[range];
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        decode_latin1(&bytes[..end]).trim().to_string()
+    };
+
+    let mut block = TagBlock::new(TagFormat::Id3v1);
+    block.version = 1;
+    let texts = [
+        ("TIT2", field(3..33)),
+        ("TPE1", field(33..63)),
+        ("TALB", field(63..93)),
+        ("TYER", field(93..97)),
+    ];
+    for (id, value) in texts {
+        if !value.is_empty() {
+            block.push_text(id, value);
+        }
+    }
+
+    // v1.1: コメントの 29 バイト目が 0 なら 30 バイト目がトラック番号
+    let (comment, track) = if tag[125] == 0 && tag[126] != 0 {
+        (field(97..125), Some(tag[126]))
+    } else {
+        (field(97..127), None)
+    };
+    if !comment.is_empty() {
+        block.push_text("COMM", comment);
+    }
+    if let Some(track) = track {
+        block.push_text("TRCK", track.to_string());
+    }
+    if let Some(genre) = genre_name(tag[127]) {
+        block.push_text("TCON", genre.to_string());
+    }
+
+    if block.frames.is_empty() {
+        None
+    } else {
+        Some(block)
+    }
+}
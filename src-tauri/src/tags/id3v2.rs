@@ -0,0 +1,633 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use super::{decode_latin1, read_at, trim_nul, FrameValue, Picture, TagBlock, TagFormat};
+
+/// 新しく作る ID3v2 タグのバージョン（convert と同じく互換性の高い v2.3）
+const DEFAULT_VERSION: u8 = 3;
+
+/// 10 バイトのヘッダーからタグ全体（ヘッダー・フッター込み）のバイト数を求める
+pub(super) fn total_size(header: &[u8]) -> Option<u64> {
+    if !header.starts_with(b"ID3") {
+        return None;
+    }
+    let size = synchsafe(header.get(6..10)?)? as u64;
+    let has_footer = header.get(5).is_some_and(|flags| flags & 0x10 != 0);
+    Some(10 + size + if has_footer { 10 } else { 0 })
+}
+
+fn synchsafe(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() != 4 || bytes.iter().any(|b| b & 0x80 != 0) {
+        return None;
+    }
+    Some(bytes.iter().fold(0u32, |acc, &b| (acc << 7) | b as u32))
+}
+
+/// 非同期化（0xFF 0x00 → 0xFF）を元に戻す
+fn remove_unsynchronisation(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut previous_ff = false;
+    for &b in data {
+        if previous_ff && b == 0x00 {
+            previous_ff = false;
+            continue;
+        }
+        out.push(b);
+        previous_ff = b == 0xFF;
+    }
+    out
+}
+
+/// ヘッダーを含むタグ全体を解析する（ID3v2.3 / 2.4）
+pub(super) fn parse(tag: &[u8]) -> Result<TagBlock, String> {
+    let version = *tag.get(3).ok_or("ID3v2 ヘッダーが不正です")?;
+    if version != 3 && version != 4 {
+        return Err(format!("ID3v2.{} には対応していません", version));
+    }
+    let flags = tag[5];
+    let size = synchsafe(tag.get(6..10).ok_or("ID3v2 ヘッダーが不正です")?)
+        .ok_or("ID3v2 のサイズが不正です")? as usize;
+    let body = tag.get(10..10 + size).ok_or("ID3v2 タグが途中で切れています")?;
+
+    // v2.3 の非同期化はタグ全体、v2.4 はフレーム単位（フラグが立っていれば全フレーム）
+    let tag_unsync = flags & 0x80 != 0;
+    let body = if tag_unsync && version == 3 {
+        remove_unsynchronisation(body)
+    } else {
+        body.to_vec()
+    };
+
+    let mut offset = 0usize;
+    if flags & 0x40 != 0 {
+        // 拡張ヘッダー（v2.3 はサイズ自身を含まない、v2.4 は含む synchsafe）
+        offset = if version == 3 {
+            super::be_u32(&body, 0).ok_or("ID3v2 拡張ヘッダーが不正です")? as usize + 4
+        } else {
+            synchsafe(body.get(0..4).ok_or("ID3v2 拡張ヘッダーが不正です")?)
+                .ok_or("ID3v2 拡張ヘッダーが不正です")? as usize
+        };
+    }
+
+    let mut block = TagBlock::new(TagFormat::Id3v2);
+    block.version = version;
+    while offset + 10 <= body.len() {
+        let header = &body[offset..offset + 10];
+        if header[0] == 0 {
+            // パディング
+            break;
+        }
+        let Ok(id) = std::str::from_utf8(&header[0..4]) else { break };
+        if !id.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()) {
+            break;
+        }
+
+        let frame_size = if version == 4 {
+            // iTunes など v2.4 でも synchsafe でないサイズを書くものがあるため、次のフレームが続くかで判定する
+            let safe = synchsafe(&header[4..8]).map(|s| s as usize);
+            let plain = super::be_u32(header, 4).unwrap_or(0) as usize;
+            match safe {
+                Some(safe) if safe == plain || looks_like_frame_start(&body, offset + 10 + safe) => safe,
+                _ => plain,
+            }
+        } else {
+            super::be_u32(header, 4).unwrap_or(0) as usize
+        };
+        let format_flags = header[9];
+        let start = offset + 10;
+        let Some(data) = body.get(start..start + frame_size) else { break };
+        offset = start + frame_size;
+
+        if is_undecodable(version, format_flags) {
+            // v2.4 のタグ全体の非同期化はフレームごとのフラグとして残す（本体は非同期化されたまま持つ）
+            let format_flags = if version == 4 && tag_unsync { format_flags | 0x02 } else { format_flags };
+            let flags = u16::from_be_bytes([header[8], format_flags]);
+            block.push(id, FrameValue::Undecoded { flags, data: data.to_vec() });
+        } else if let Some(data) = frame_payload(data, version, format_flags, tag_unsync) {
+            decode_frame(&mut block, id, &data);
+        }
+    }
+
+    Ok(block)
+}
+
+fn looks_like_frame_start(body: &[u8], offset: usize) -> bool {
+    match body.get(offset..offset + 4) {
+        Some(id) => id[0] == 0 || id.iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()),
+        // タグの末尾ちょうど
+        None => offset == body.len(),
+    }
+}
+
+/// 圧縮・暗号化されたフレームか（v2.4 は 0x08 / 0x04、v2.3 は 0x80 / 0x40）
+fn is_undecodable(version: u8, flags: u8) -> bool {
+    if version == 4 {
+        flags & 0x0C != 0
+    } else {
+        flags & 0xC0 != 0
+    }
+}
+
+/// フレームのフォーマットフラグを解釈して本体を取り出す（圧縮・暗号化されたフレームは is_undecodable で除いておく）
+fn frame_payload(data: &[u8], version: u8, flags: u8, tag_unsync: bool) -> Option<Vec<u8>> {
+    let mut data = data;
+    if version == 4 {
+        if flags & 0x40 != 0 {
+            data = data.get(1..)?;
+        }
+        if flags & 0x01 != 0 {
+            data = data.get(4..)?;
+        }
+        if flags & 0x02 != 0 || tag_unsync {
+            return Some(remove_unsynchronisation(data));
+        }
+    } else if flags & 0x20 != 0 {
+        data = data.get(1..)?;
+    }
+    Some(data.to_vec())
+}
+
+fn decode_frame(block: &mut TagBlock, id: &str, data: &[u8]) {
+    match id {
+        "TXXX" => {
+            let Some((&encoding, rest)) = data.split_first() else { return };
+            let (description, value) = split_terminated(rest, encoding);
+            let key = format!("TXXX:{}", decode_text(description, encoding));
+            for value in split_values(value, encoding) {
+                block.push_text(&key, value);
+            }
+        }
+        "COMM" | "USLT" => {
+            let Some((&encoding, rest)) = data.split_first() else { return };
+            // 言語コード（3 バイト）は読み捨てる
+            let Some(rest) = rest.get(3..) else { return };
+            let (description, text) = split_terminated(rest, encoding);
+            let description = decode_text(description, encoding);
+            let key = if description.is_empty() {
+                id.to_string()
+            } else {
+                format!("{}:{}", id, description)
+            };
+            block.push_text(&key, trim_nul(&decode_text(text, encoding)));
+        }
+        "APIC" => {
+            if let Some(picture) = decode_apic(data) {
+                block.push("APIC", FrameValue::Picture(picture));
+            }
+        }
+        "WXXX" => {
+            let Some((&encoding, rest)) = data.split_first() else { return };
+            let (description, url) = split_terminated(rest, encoding);
+            let key = format!("WXXX:{}", decode_text(description, encoding));
+            block.push_text(&key, trim_nul(&decode_latin1(url)));
+        }
+        _ if id.starts_with('T') => {
+            let Some((&encoding, rest)) = data.split_first() else { return };
+            for value in split_values(rest, encoding) {
+                block.push_text(id, value);
+            }
+        }
+        _ if id.starts_with('W') => block.push_text(id, trim_nul(&decode_latin1(data))),
+        _ => block.push(id, FrameValue::Binary(data.to_vec())),
+    }
+}
+
+fn decode_apic(data: &[u8]) -> Option<Picture> {
+    let (&encoding, rest) = data.split_first()?;
+    let mime_end = rest.iter().position(|&b| b == 0)?;
+    let mime = decode_latin1(&rest[..mime_end]);
+    let rest = rest.get(mime_end + 1..)?;
+    let (&picture_type, rest) = rest.split_first()?;
+    let (description, image) = split_terminated(rest, encoding);
+    if image.is_empty() {
+        return None;
+    }
+
+    // "image/jpg" や "JPG" のような表記ゆれはデータから判定し直す
+    let mime = if mime.contains('/') && mime != "image/jpg" {
+        mime.to_ascii_lowercase()
+    } else {
+        crate::utils::sniff_image(image).mime.to_string()
+    };
+
+    Some(Picture {
+        picture_type,
+        mime,
+        description: decode_text(description, encoding),
+        data: image.to_vec(),
+    })
+}
+
+/// 文字コードに応じた終端（UTF-16 は 2 バイト境界の 00 00）で分割する
+fn split_terminated(data: &[u8], encoding: u8) -> (&[u8], &[u8]) {
+    if is_utf16(encoding) {
+        let mut i = 0;
+        while i + 1 < data.len() {
+            if data[i] == 0 && data[i + 1] == 0 {
+                return (&data[..i], &data[i + 2..]);
+            }
+            i += 2;
+        }
+        (data, &[])
+    } else {
+        match data.iter().position(|&b| b == 0) {
+            Some(i) => (&data[..i], &data[i + 1..]),
+            None => (data, &[]),
+        }
+    }
+}
+
+/// NUL 区切りの複数値（ID3v2.4）を分割する。末尾の空要素は捨てる
+fn split_values(data: &[u8], encoding: u8) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (value, next) = split_terminated(rest, encoding);
+        values.push(decode_text(value, encoding));
+        rest = next;
+    }
+    while values.last().is_some_and(|v| v.is_empty()) {
+        values.pop();
+    }
+    values
+}
+
+fn is_utf16(encoding: u8) -> bool {
+    encoding == 1 || encoding == 2
+}
+
+fn decode_text(data: &[u8], encoding: u8) -> String {
+    match encoding {
+        0 => decode_latin1(data),
+        1 | 2 => {
+            let (big_endian, data) = match data {
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                // BOM が無い UTF-16 はエンコード 2 なら BE、1 なら LE とみなす
+                _ => (encoding == 2, data),
+            };
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|c| if big_endian { u16::from_be_bytes([c[0], c[1]]) } else { u16::from_le_bytes([c[0], c[1]]) })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// MP3 先頭の ID3v2 タグを書き換える。新しいタグが既存のタグ領域に収まる場合は余白を詰めてその場で上書きする
+pub(super) fn write(
+    mut file: File,
+    file_len: u64,
+    path: &Path,
+    update: impl FnOnce(&mut TagBlock),
+) -> Result<(), String> {
+    let head = read_at(&mut file, file_len, 0, 10.min(file_len as usize))
+        .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+    let (mut block, old_len) = match total_size(&head) {
+        Some(tag_len) => {
+            let tag = read_at(&mut file, file_len, 0, tag_len as usize)
+                .map_err(|_| "ID3v2 タグが途中で切れています".to_string())?;
+            (parse(&tag)?, tag_len)
+        }
+        None => {
+            let mut block = TagBlock::new(TagFormat::Id3v2);
+            block.version = DEFAULT_VERSION;
+            (block, 0)
+        }
+    };
+
+    update(&mut block);
+    let frames = serialize_frames(&block);
+
+    if old_len > 0 && 10 + frames.len() <= old_len as usize {
+        let tag = build_tag(block.version, &frames, old_len as usize - 10 - frames.len());
+        return super::overwrite_at(&mut file, 0, &tag);
+    }
+
+    let tag = build_tag(block.version, &frames, super::DEFAULT_PADDING);
+    super::rewrite_file(path, file, |source, writer| {
+        writer.write_all(&tag)?;
+        super::copy_range(source, old_len, None, writer)
+    })
+}
+
+fn to_synchsafe(value: usize) -> [u8; 4] {
+    [
+        ((value >> 21) & 0x7F) as u8,
+        ((value >> 14) & 0x7F) as u8,
+        ((value >> 7) & 0x7F) as u8,
+        (value & 0x7F) as u8,
+    ]
+}
+
+fn build_tag(version: u8, frames: &[u8], padding: usize) -> Vec<u8> {
+    let mut tag = Vec::with_capacity(10 + frames.len() + padding);
+    tag.extend_from_slice(b"ID3");
+    tag.extend_from_slice(&[version, 0, 0]);
+    tag.extend_from_slice(&to_synchsafe(frames.len() + padding));
+    tag.extend_from_slice(frames);
+    tag.resize(tag.len() + padding, 0);
+    tag
+}
+
+fn serialize_frames(block: &TagBlock) -> Vec<u8> {
+    let version = block.version;
+    // v2.3 には TDRC が無いため、TYER（年）と TDAT（DDMM）に分けて書く
+    let split_date = version == 3 && block.get("TDRC").is_some();
+
+    let mut out = Vec::new();
+    for frame in &block.frames {
+        if split_date && (frame.id == "TYER" || frame.id == "TDAT") {
+            continue;
+        }
+        let (frames, flags) = match &frame.value {
+            FrameValue::Undecoded { flags, data } => (vec![(frame.id.clone(), data.clone())], flags.to_be_bytes()),
+            _ => (encode_frame(frame, version), [0, 0]),
+        };
+        for (id, body) in frames {
+            if id.len() != 4 || !id.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()) {
+                continue;
+            }
+            out.extend_from_slice(id.as_bytes());
+            if version == 4 {
+                out.extend_from_slice(&to_synchsafe(body.len()));
+            } else {
+                out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            }
+            out.extend_from_slice(&flags);
+            out.extend_from_slice(&body);
+        }
+    }
+    out
+}
+
+/// フレームを (フレーム ID, 本体) に変換する。COMM の複数値や v2.3 の日付は複数フレームになる
+fn encode_frame(frame: &super::Frame, version: u8) -> Vec<(String, Vec<u8>)> {
+    let (base_id, description) = match frame.id.split_once(':') {
+        Some((id, description)) => (id, description),
+        None => (frame.id.as_str(), ""),
+    };
+
+    let values: Vec<String> = match &frame.value {
+        FrameValue::Text(values) => values.clone(),
+        FrameValue::Pair(number, total) if *total > 0 => vec![format!("{}/{}", number, total)],
+        FrameValue::Pair(number, _) => vec![number.to_string()],
+        FrameValue::Integer(n) => vec![n.to_string()],
+        FrameValue::Picture(picture) => return vec![(base_id.to_string(), encode_apic(picture, version))],
+        FrameValue::Binary(data) | FrameValue::Undecoded { data, .. } => return vec![(base_id.to_string(), data.clone())],
+    };
+
+    match base_id {
+        "TXXX" => {
+            let encoding = choose_encoding(values.iter().map(String::as_str).chain([description]), version);
+            let mut body = vec![encoding];
+            push_text(&mut body, description, encoding, true);
+            push_values(&mut body, &values, encoding, version);
+            vec![("TXXX".to_string(), body)]
+        }
+        "COMM" | "USLT" => values
+            .iter()
+            .map(|value| {
+                let encoding = choose_encoding([value.as_str(), description], version);
+                let mut body = vec![encoding];
+                body.extend_from_slice(b"XXX");
+                push_text(&mut body, description, encoding, true);
+                push_text(&mut body, value, encoding, false);
+                (base_id.to_string(), body)
+            })
+            .collect(),
+        "WXXX" => {
+            let encoding = choose_encoding([description], version);
+            let mut body = vec![encoding];
+            push_text(&mut body, description, encoding, true);
+            body.extend(values.first().map(|v| latin1_bytes(v)).unwrap_or_default());
+            vec![("WXXX".to_string(), body)]
+        }
+        "TDRC" if version == 3 => {
+            let date = values.first().map(|v| v.trim()).unwrap_or_default();
+            let digits: Vec<&str> = date
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|p| !p.is_empty())
+                .collect();
+            let mut frames = Vec::new();
+            if let Some(year) = digits.first().filter(|y| y.len() == 4) {
+                frames.push(("TYER".to_string(), [&[0u8][..], year.as_bytes()].concat()));
+                if let (Some(month), Some(day)) = (digits.get(1), digits.get(2)) {
+                    let ddmm = format!("{:0>2}{:0>2}", day, month);
+                    frames.push(("TDAT".to_string(), [&[0u8][..], ddmm.as_bytes()].concat()));
+                }
+            }
+            frames
+        }
+        id if id.starts_with('T') => {
+            let encoding = choose_encoding(values.iter().map(String::as_str), version);
+            let mut body = vec![encoding];
+            push_values(&mut body, &values, encoding, version);
+            vec![(id.to_string(), body)]
+        }
+        id if id.starts_with('W') => vec![(id.to_string(), values.first().map(|v| latin1_bytes(v)).unwrap_or_default())],
+        _ => Vec::new(),
+    }
+}
+
+fn encode_apic(picture: &Picture, version: u8) -> Vec<u8> {
+    let encoding = choose_encoding([picture.description.as_str()], version);
+    let mut body = vec![encoding];
+    body.extend(latin1_bytes(&picture.mime));
+    body.push(0);
+    body.push(picture.picture_type);
+    push_text(&mut body, &picture.description, encoding, true);
+    body.extend_from_slice(&picture.data);
+    body
+}
+
+/// ASCII だけなら ISO-8859-1、それ以外は v2.4 なら UTF-8、v2.3 なら BOM 付き UTF-16
+fn choose_encoding<'a>(texts: impl IntoIterator<Item = &'a str>, version: u8) -> u8 {
+    if texts.into_iter().all(|t| t.is_ascii()) {
+        0
+    } else if version == 4 {
+        3
+    } else {
+        1
+    }
+}
+
+fn push_text(body: &mut Vec<u8>, text: &str, encoding: u8, terminate: bool) {
+    match encoding {
+        0 => body.extend(latin1_bytes(text)),
+        1 => {
+            body.extend_from_slice(&[0xFF, 0xFE]);
+            body.extend(text.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        }
+        2 => body.extend(text.encode_utf16().flat_map(|u| u.to_be_bytes())),
+        _ => body.extend_from_slice(text.as_bytes()),
+    }
+    if terminate {
+        body.extend_from_slice(if is_utf16(encoding) { &[0, 0] } else { &[0] });
+    }
+}
+
+/// v2.4 は NUL 区切りの複数値、v2.3 は ";" で連結した1つの値として書く（読み込み時に同じ区切りで分ける）
+fn push_values(body: &mut Vec<u8>, values: &[String], encoding: u8, version: u8) {
+    if version == 4 {
+        for (i, value) in values.iter().enumerate() {
+            push_text(body, value, encoding, i + 1 < values.len());
+        }
+    } else {
+        push_text(body, &values.join(super::VALUE_SEPARATOR), encoding, false);
+    }
+}
+
+fn latin1_bytes(text: &str) -> Vec<u8> {
+    text.chars().map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::tests::temp_file;
+    use crate::tags::{FileTags, StandardField};
+
+    /// 音声データの代わり（フレーム同期だけのダミー）
+    const AUDIO: [u8; 8] = [0xFF, 0xFB, 0x90, 0x64, 1, 2, 3, 4];
+
+    fn mp3_with_padding(name: &str, padding: usize) -> std::path::PathBuf {
+        let mut block = TagBlock::new(TagFormat::Id3v2);
+        block.version = 3;
+        block.set_field(StandardField::Title, "before");
+        let mut bytes = build_tag(3, &serialize_frames(&block), padding);
+        bytes.extend_from_slice(&AUDIO);
+        temp_file(name, &bytes)
+    }
+
+    fn read_tag(path: &Path) -> (TagBlock, usize, Vec<u8>) {
+        let bytes = std::fs::read(path).unwrap();
+        let tag_len = total_size(&bytes).unwrap() as usize;
+        (parse(&bytes[..tag_len]).unwrap(), tag_len, bytes[tag_len..].to_vec())
+    }
+
+    fn round_trip(version: u8, values: &[&str]) -> FileTags {
+        let mut block = TagBlock::new(TagFormat::Id3v2);
+        block.version = version;
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        block.set_values(StandardField::Artist, &values);
+        let tag = build_tag(version, &serialize_frames(&block), 0);
+        FileTags {
+            properties: Default::default(),
+            tags: vec![parse(&tag).unwrap()],
+        }
+    }
+
+    #[test]
+    fn multi_values_round_trip_in_v23_and_v24() {
+        for version in [3, 4] {
+            let tags = round_trip(version, &["声優A", "Voice B"]);
+            assert_eq!(tags.values(StandardField::Artist), ["声優A", "Voice B"], "v2.{}", version);
+        }
+    }
+
+    #[test]
+    fn voice_actors_are_null_separated_in_v24() {
+        let mut block = TagBlock::new(TagFormat::Id3v2);
+        block.version = 4;
+        block.set_values(StandardField::VoiceActor, &["声優A".to_string(), "声優B".to_string()]);
+        let frames = serialize_frames(&block);
+        // TXXX: エンコーディング (UTF-8) + 説明 + NUL + 値 + NUL + 値
+        let body = [&[3u8][..], b"VOICE_ACTOR\0", "声優A\0声優B".as_bytes()].concat();
+        assert_eq!(&frames[10..], body.as_slice());
+
+        let tags = FileTags {
+            properties: Default::default(),
+            tags: vec![parse(&build_tag(4, &frames, 0)).unwrap()],
+        };
+        assert_eq!(tags.values(StandardField::VoiceActor), ["声優A", "声優B"]);
+    }
+
+    #[test]
+    fn v23_keeps_slash_inside_a_value() {
+        let tags = round_trip(3, &["AC/DC", "B"]);
+        assert_eq!(tags.values(StandardField::Artist), ["AC/DC", "B"]);
+    }
+
+    #[test]
+    fn small_edit_reuses_padding() {
+        let path = mp3_with_padding("id3v2-reuse.mp3", 512);
+        let before = std::fs::metadata(&path).unwrap().len();
+        crate::tags::write_file(&path, |block| block.set_field(StandardField::Title, "after")).unwrap();
+
+        let (block, tag_len, audio) = read_tag(&path);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), before);
+        assert_eq!(block.get("TIT2"), Some(&FrameValue::Text(vec!["after".to_string()])));
+        assert_eq!(block.version, 3);
+        assert_eq!(tag_len as u64 + AUDIO.len() as u64, before);
+        assert_eq!(audio, AUDIO);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn large_edit_grows_tag_with_default_padding() {
+        let path = mp3_with_padding("id3v2-grow.mp3", 16);
+        let lyrics = "歌詞".repeat(1000);
+        crate::tags::write_file(&path, |block| block.set("USLT", FrameValue::Text(vec![lyrics.clone()]))).unwrap();
+
+        let (block, tag_len, audio) = read_tag(&path);
+        assert_eq!(block.get("USLT"), Some(&FrameValue::Text(vec![lyrics])));
+        assert_eq!(block.get("TIT2"), Some(&FrameValue::Text(vec!["before".to_string()])));
+        assert_eq!(tag_len - 10 - serialize_frames(&block).len(), crate::tags::DEFAULT_PADDING);
+        assert_eq!(audio, AUDIO);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compressed_and_encrypted_frames_survive_a_rewrite() {
+        // (バージョン, 状態フラグ, フォーマットフラグ)。v2.3 は圧縮 0x80 / 暗号化 0x40、v2.4 は圧縮 0x08 / 暗号化 0x04
+        for (version, status, format) in [(3, 0x00, 0x80), (3, 0x40, 0x40), (4, 0x00, 0x09), (4, 0x20, 0x04)] {
+            let data = [0, 0, 0, 32, 0x78, 0x9C, 0xFF, 0x00, 0x01];
+            let mut frame = b"TPE1".to_vec();
+            if version == 4 {
+                frame.extend_from_slice(&to_synchsafe(data.len()));
+            } else {
+                frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            }
+            frame.extend_from_slice(&[status, format]);
+            frame.extend_from_slice(&data);
+
+            let mut block = TagBlock::new(TagFormat::Id3v2);
+            block.version = version;
+            block.set_field(StandardField::Title, "before");
+            let mut frames = serialize_frames(&block);
+            frames.extend_from_slice(&frame);
+            let mut bytes = build_tag(version, &frames, 64);
+            bytes.extend_from_slice(&AUDIO);
+            let path = temp_file(&format!("id3v2-opaque-{}-{}.mp3", version, format), &bytes);
+
+            crate::tags::write_file(&path, |block| block.set_field(StandardField::Title, "after")).unwrap();
+
+            let (block, tag_len, audio) = read_tag(&path);
+            let flags = u16::from_be_bytes([status, format]);
+            assert_eq!(block.get("TPE1"), Some(&FrameValue::Undecoded { flags, data: data.to_vec() }), "v2.{}", version);
+            assert_eq!(block.get("TIT2"), Some(&FrameValue::Text(vec!["after".to_string()])));
+            let written = std::fs::read(&path).unwrap();
+            assert!(written[..tag_len].windows(frame.len()).any(|w| w == frame.as_slice()), "v2.{}", version);
+            assert_eq!(audio, AUDIO);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn growing_tag_works_with_a_file_name_at_the_length_limit() {
+        // temp_file の接頭辞と合わせて 255 バイトのファイル名にする
+        let prefix_len = format!("vte-tags-{}-", std::process::id()).len();
+        let name = format!("{}.mp3", "a".repeat(255 - prefix_len - 4));
+        let path = mp3_with_padding(&name, 0);
+        assert_eq!(path.file_name().unwrap().len(), 255);
+        crate::tags::write_file(&path, |block| block.set_field(StandardField::Album, "アルバム")).unwrap();
+
+        let (block, _, audio) = read_tag(&path);
+        assert_eq!(block.get("TALB"), Some(&FrameValue::Text(vec!["アルバム".to_string()])));
+        assert_eq!(audio, AUDIO);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,822 @@
+//! ffprobe / FFmpeg を使わずにタグを読み書きする（ID3v2 / ID3v1 / Vorbis comment / MP4 ilst / RIFF INFO）
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::jobs::PartialOutput;
+use crate::models::ExtendedTags;
+use crate::utils::{parse_number, parse_number_pair};
+
+mod flac;
+mod id3v1;
+mod id3v2;
+mod mp4;
+mod mpeg;
+mod ogg;
+mod riff;
+mod vorbis;
+
+pub use vorbis::decode_block_picture;
+
+/// 1 つの値に複数の値を連結するときの区切り（ID3v2.3 の書き込みと、読み込み時の分割で共通）
+const VALUE_SEPARATOR: &str = ";";
+
+/// タグの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagFormat {
+    Id3v2,
+    Id3v1,
+    VorbisComment,
+    Mp4,
+    RiffInfo,
+}
+
+/// フレーム（ID3v2 フレーム / Vorbis コメント / MP4 アトム / INFO チャンク）の値
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameValue {
+    /// テキスト。ID3v2.4 の null 区切りや Vorbis の同名キーは複数の値になる
+    Text(Vec<String>),
+    /// MP4 の trkn / disk（番号, 総数。総数が無い場合は 0）
+    Pair(u32, u32),
+    /// MP4 の tmpo / cpil / gnre などの整数値
+    Integer(i64),
+    Picture(Picture),
+    /// 解釈しないフレーム（書き戻し時にそのまま残す）
+    Binary(Vec<u8>),
+    /// 圧縮・暗号化されていて読めない ID3v2 フレーム。フレームのフラグ（状態・フォーマットの 2 バイト）と
+    /// 本体をそのまま持ち、書き戻し時も変えずに書く
+    Undecoded { flags: u16, data: Vec<u8> },
+}
+
+/// 埋め込み画像（APIC / FLAC PICTURE / METADATA_BLOCK_PICTURE / covr）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Picture {
+    /// APIC / FLAC の画像種別（3 = 表紙）
+    pub picture_type: u8,
+    pub mime: String,
+    pub description: String,
+    pub data: Vec<u8>,
+}
+
+/// APIC / FLAC の画像種別の名前（FFmpeg が画像ストリームの comment に入れる表記）
+pub const PICTURE_TYPE_NAMES: [&str; 21] = [
+    "Other",
+    "32x32 pixels 'file icon'",
+    "Other file icon",
+    "Cover (front)",
+    "Cover (back)",
+    "Leaflet page",
+    "Media (e.g. label side of CD)",
+    "Lead artist/lead performer/soloist",
+    "Artist/performer",
+    "Conductor",
+    "Band/Orchestra",
+    "Composer",
+    "Lyricist/text writer",
+    "Recording Location",
+    "During recording",
+    "During performance",
+    "Movie/video screen capture",
+    "A bright coloured fish",
+    "Illustration",
+    "Band/artist logotype",
+    "Publisher/Studio logotype",
+];
+
+/// フレーム ID は形式ごとの表記（TIT2 / TITLE / ©nam / INAM）。
+/// ID3v2 の TXXX・COMM は `TXXX:説明`、MP4 のフリーフォームは `----:mean:name` とする
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub id: String,
+    pub value: FrameValue,
+}
+
+#[derive(Debug, Clone)]
+pub struct TagBlock {
+    pub format: TagFormat,
+    /// ID3v2 のメジャーバージョン（3 / 4）。書き戻し時に同じバージョンで書く。他の形式では 0
+    pub version: u8,
+    pub frames: Vec<Frame>,
+}
+
+impl TagBlock {
+    fn new(format: TagFormat) -> Self {
+        Self { format, version: 0, frames: Vec::new() }
+    }
+
+    /// 同じ ID のテキストフレームがあれば値を追加し、無ければフレームを作る
+    fn push_text(&mut self, id: &str, value: String) {
+        let existing = self.frames.iter_mut().find(|f| f.id == id);
+        match existing {
+            Some(Frame { value: FrameValue::Text(values), .. }) => values.push(value),
+            _ => self.frames.push(Frame {
+                id: id.to_string(),
+                value: FrameValue::Text(vec![value]),
+            }),
+        }
+    }
+
+    fn push(&mut self, id: &str, value: FrameValue) {
+        self.frames.push(Frame { id: id.to_string(), value });
+    }
+
+    pub fn get(&self, id: &str) -> Option<&FrameValue> {
+        self.frames
+            .iter()
+            .find(|f| f.id.eq_ignore_ascii_case(id))
+            .map(|f| &f.value)
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.frames.retain(|f| !f.id.eq_ignore_ascii_case(id));
+    }
+
+    /// 同じ ID のフレームを置き換える（既存の位置を保つ）
+    pub fn set(&mut self, id: &str, value: FrameValue) {
+        match self.frames.iter().position(|f| f.id.eq_ignore_ascii_case(id)) {
+            Some(index) => {
+                self.frames[index].value = value;
+                let mut seen = 0;
+                self.frames.retain(|f| {
+                    if f.id.eq_ignore_ascii_case(id) {
+                        seen += 1;
+                        seen == 1
+                    } else {
+                        true
+                    }
+                });
+            }
+            None => self.push(id, value),
+        }
+    }
+
+    /// フィールドの値を形式ごとのフレームに書く。空文字は削除（FFmpeg 版の push_metadata と同じ扱い）
+    pub fn set_field(&mut self, field: StandardField, value: &str) {
+        let ids = field.frame_ids(self.format);
+        let Some(&primary) = ids.first() else { return };
+
+        if value.is_empty() {
+            // 別名のフレームが残っていると読み込み時にそちらが表示されるため、すべて消す
+            for id in ids {
+                self.remove(id);
+            }
+            return;
+        }
+
+        let frame = match (self.format, field) {
+            // MP4 の trkn / disk は "番号/総数" の数値ペア
+            (TagFormat::Mp4, StandardField::TrackNumber | StandardField::DiscNumber) => match parse_number_pair(value) {
+                (Some(number), total) => FrameValue::Pair(number, total.unwrap_or(0)),
+                (None, _) => return,
+            },
+            // MP4 の tmpo / cpil は整数（BPM の小数は丸める）
+            (TagFormat::Mp4, StandardField::Bpm | StandardField::Compilation) => match value.parse::<f64>() {
+                Ok(number) => FrameValue::Integer(number.round() as i64),
+                Err(_) => return,
+            },
+            _ => FrameValue::Text(vec![value.to_string()]),
+        };
+        self.set(primary, frame);
+    }
+
+    /// 複数の値を形式ごとの書き方で書く（ID3v2.4 は null 区切り、v2.3 は ";" 区切り、Vorbis は同じキーの繰り返し、
+    /// MP4 のジャンルは data アトムの繰り返し）。MP4 のアーティストは表示用に "; " で連結した ©ART と、
+    /// 1 人ずつの ----:com.apple.iTunes:ARTISTS（Picard と同じ）に分けて書く
+    pub fn set_values(&mut self, field: StandardField, values: &[String]) {
+        let values: Vec<String> = values
+            .iter()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect();
+        let mp4_artists = self.format == TagFormat::Mp4 && matches!(field, StandardField::Artist);
+        if values.len() <= 1 {
+            if mp4_artists {
+                self.remove(MP4_ARTISTS_ID);
+            }
+            self.set_field(field, values.first().map(String::as_str).unwrap_or_default());
+            return;
+        }
+
+        let ids = field.frame_ids(self.format);
+        let Some((&primary, aliases)) = ids.split_first() else { return };
+        for id in aliases {
+            self.remove(id);
+        }
+        if mp4_artists {
+            self.set(primary, FrameValue::Text(vec![values.join("; ")]));
+            self.set(MP4_ARTISTS_ID, FrameValue::Text(values));
+        } else {
+            self.set(primary, FrameValue::Text(values));
+        }
+    }
+
+    /// トラック番号・ディスク番号と総数を形式ごとの書き方で書く（ID3v2 は "3/12"、Vorbis は TRACKNUMBER と
+    /// TRACKTOTAL、MP4 は trkn の組）。None の項目は今の値を残し、総数 0 は削除、番号の空文字は総数ごと削除する。
+    /// 数字として読めない番号（"A1" など）はそのまま書く
+    pub fn set_number(&mut self, field: StandardField, number: Option<&str>, total: Option<u32>) {
+        let total_field = match field {
+            StandardField::TrackNumber => StandardField::TrackTotal,
+            StandardField::DiscNumber => StandardField::DiscTotal,
+            _ => return,
+        };
+        if number.is_none() && total.is_none() {
+            return;
+        }
+
+        let (current_number, current_total) = self.number(field);
+        let number = number.map(str::trim);
+        if number == Some("") {
+            self.set_field(field, "");
+            self.set_field(total_field, "");
+            return;
+        }
+        let (parsed_number, parsed_total) = number.map(parse_number_pair).unwrap_or((current_number, None));
+        let Some(parsed_number) = parsed_number else {
+            if let Some(number) = number {
+                self.set_field(field, number);
+            }
+            return;
+        };
+        let total = total.or(parsed_total).or(current_total).filter(|&total| total > 0);
+
+        match self.format {
+            TagFormat::VorbisComment => {
+                self.set_field(field, &parsed_number.to_string());
+                self.set_field(total_field, &total.map(|t| t.to_string()).unwrap_or_default());
+            }
+            TagFormat::Mp4 => {
+                if let Some(&id) = field.frame_ids(self.format).first() {
+                    self.set(id, FrameValue::Pair(parsed_number, total.unwrap_or(0)));
+                }
+            }
+            TagFormat::Id3v2 | TagFormat::Id3v1 => {
+                self.set_field(field, &crate::utils::format_number_pair(&parsed_number.to_string(), total));
+            }
+            // INFO には総数を書く場所が無い
+            TagFormat::RiffInfo => self.set_field(field, &parsed_number.to_string()),
+        }
+    }
+
+    /// このブロックの番号と総数（"3/12"、trkn の組、Vorbis の TRACKTOTAL のいずれからも読む）
+    fn number(&self, field: StandardField) -> (Option<u32>, Option<u32>) {
+        let total_field = match field {
+            StandardField::TrackNumber => StandardField::TrackTotal,
+            _ => StandardField::DiscTotal,
+        };
+        let text = |field: StandardField| {
+            field
+                .frame_ids(self.format)
+                .iter()
+                .filter_map(|id| self.get(id))
+                .find_map(|value| frame_value_to_text(field, value))
+        };
+        let (number, total) = text(field).map(|t| parse_number_pair(&t)).unwrap_or_default();
+        let total = total.or_else(|| text(total_field).and_then(|t| parse_number(&t)).filter(|&t| t > 0));
+        (number, total)
+    }
+
+    pub fn remove_pictures(&mut self) {
+        self.frames.retain(|f| !matches!(f.value, FrameValue::Picture(_)));
+    }
+
+    /// 画像を追加する（set と違い、既にある画像は残す）
+    pub fn add_picture(&mut self, picture: Picture) {
+        let id = self.picture_id();
+        if !id.is_empty() {
+            self.push(id, FrameValue::Picture(picture));
+        }
+    }
+
+    /// 埋め込み画像のフレーム ID（FLAC の PICTURE ブロックも Vorbis comment として扱う）
+    pub fn picture_id(&self) -> &'static str {
+        match self.format {
+            TagFormat::Id3v2 | TagFormat::Id3v1 => "APIC",
+            TagFormat::VorbisComment => "METADATA_BLOCK_PICTURE",
+            TagFormat::Mp4 => "covr",
+            TagFormat::RiffInfo => "",
+        }
+    }
+}
+
+/// MP4 で複数のアーティストを 1 人ずつ入れるフリーフォーム
+const MP4_ARTISTS_ID: &str = "----:com.apple.iTunes:ARTISTS";
+
+/// 音声ストリームの情報（ffprobe の format / stream 相当）
+#[derive(Debug, Clone, Default)]
+pub struct AudioProperties {
+    /// ffprobe の codec_name と同じ表記（mp3, flac, opus, vorbis, aac, alac, pcm_s16le ...）
+    pub codec: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub duration_seconds: Option<f64>,
+    /// ビット/秒
+    pub bit_rate: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileTags {
+    pub properties: AudioProperties,
+    /// 優先度の高い順（ID3v2 → ID3v1 など）
+    pub tags: Vec<TagBlock>,
+}
+
+/// AudioMetadata（と ExtendedTags）の各項目に対応するフィールド
+#[derive(Debug, Clone, Copy)]
+pub enum StandardField {
+    Title,
+    Artist,
+    AlbumArtist,
+    Album,
+    TrackNumber,
+    DiscNumber,
+    /// Vorbis の TRACKTOTAL / DISCTOTAL（他の形式は TRCK "3/12" や trkn の組に含める）
+    TrackTotal,
+    DiscTotal,
+    Date,
+    Genre,
+    Comment,
+    Composer,
+    Lyricist,
+    Illustrator,
+    VoiceActor,
+    Circle,
+    Publisher,
+    Copyright,
+    Isrc,
+    CatalogNumber,
+    Bpm,
+    Compilation,
+    TitleSort,
+    ArtistSort,
+    AlbumSort,
+    AlbumArtistSort,
+    ComposerSort,
+}
+
+impl StandardField {
+    /// 形式ごとのフレーム ID（先にあるものを優先し、書き込みには先頭を使う）。
+    /// 標準のフレームが無い項目は TXXX / Vorbis の同名キー / iTunes のフリーフォームに Picard と同じ名前で書く
+    pub fn frame_ids(self, format: TagFormat) -> &'static [&'static str] {
+        use StandardField::*;
+        match format {
+            TagFormat::Id3v2 | TagFormat::Id3v1 => match self {
+                Title => &["TIT2"],
+                Artist => &["TPE1"],
+                AlbumArtist => &["TPE2"],
+                Album => &["TALB"],
+                TrackNumber => &["TRCK"],
+                DiscNumber => &["TPOS"],
+                TrackTotal | DiscTotal => &[],
+                Date => &["TDRC", "TYER"],
+                Genre => &["TCON"],
+                Comment => &["COMM"],
+                Composer => &["TCOM"],
+                Lyricist => &["TEXT"],
+                Illustrator => &["TXXX:ILLUSTRATOR"],
+                VoiceActor => &["TXXX:VOICE_ACTOR", "TXXX:CV"],
+                Circle => &["TXXX:LABEL", "TXXX:CIRCLE"],
+                Publisher => &["TPUB"],
+                Copyright => &["TCOP"],
+                Isrc => &["TSRC"],
+                CatalogNumber => &["TXXX:CATALOGNUMBER"],
+                Bpm => &["TBPM"],
+                Compilation => &["TCMP"],
+                TitleSort => &["TSOT"],
+                ArtistSort => &["TSOP"],
+                AlbumSort => &["TSOA"],
+                AlbumArtistSort => &["TSO2"],
+                ComposerSort => &["TSOC"],
+            },
+            TagFormat::VorbisComment => match self {
+                Title => &["TITLE"],
+                Artist => &["ARTIST", "PERFORMER"],
+                AlbumArtist => &["ALBUMARTIST", "ALBUM ARTIST", "ALBUM_ARTIST"],
+                Album => &["ALBUM"],
+                TrackNumber => &["TRACKNUMBER", "TRACK"],
+                DiscNumber => &["DISCNUMBER", "DISC"],
+                TrackTotal => &["TRACKTOTAL", "TOTALTRACKS"],
+                DiscTotal => &["DISCTOTAL", "TOTALDISCS"],
+                Date => &["DATE", "YEAR"],
+                Genre => &["GENRE"],
+                Comment => &["COMMENT", "DESCRIPTION"],
+                Composer => &["COMPOSER"],
+                Lyricist => &["LYRICIST"],
+                Illustrator => &["ILLUSTRATOR"],
+                VoiceActor => &["VOICE_ACTOR", "CV"],
+                Circle => &["LABEL", "CIRCLE"],
+                Publisher => &["PUBLISHER", "ORGANIZATION"],
+                Copyright => &["COPYRIGHT"],
+                Isrc => &["ISRC"],
+                CatalogNumber => &["CATALOGNUMBER"],
+                Bpm => &["BPM"],
+                Compilation => &["COMPILATION"],
+                TitleSort => &["TITLESORT"],
+                ArtistSort => &["ARTISTSORT"],
+                AlbumSort => &["ALBUMSORT"],
+                AlbumArtistSort => &["ALBUMARTISTSORT"],
+                ComposerSort => &["COMPOSERSORT"],
+            },
+            TagFormat::Mp4 => match self {
+                Title => &["©nam"],
+                Artist => &["©ART"],
+                AlbumArtist => &["aART"],
+                Album => &["©alb"],
+                TrackNumber => &["trkn"],
+                DiscNumber => &["disk"],
+                TrackTotal | DiscTotal => &[],
+                Date => &["©day"],
+                Genre => &["©gen", "gnre"],
+                Comment => &["©cmt"],
+                Composer => &["©wrt"],
+                Lyricist => &["----:com.apple.iTunes:LYRICIST"],
+                Illustrator => &["----:com.apple.iTunes:ILLUSTRATOR"],
+                VoiceActor => &["----:com.apple.iTunes:VOICE_ACTOR"],
+                Circle => &["----:com.apple.iTunes:LABEL"],
+                Publisher => &["----:com.apple.iTunes:PUBLISHER"],
+                Copyright => &["cprt"],
+                Isrc => &["----:com.apple.iTunes:ISRC"],
+                CatalogNumber => &["----:com.apple.iTunes:CATALOGNUMBER"],
+                Bpm => &["tmpo"],
+                Compilation => &["cpil"],
+                TitleSort => &["sonm"],
+                ArtistSort => &["soar"],
+                AlbumSort => &["soal"],
+                AlbumArtistSort => &["soaa"],
+                ComposerSort => &["soco"],
+            },
+            TagFormat::RiffInfo => match self {
+                Title => &["INAM"],
+                Artist => &["IART"],
+                AlbumArtist => &[],
+                Album => &["IPRD"],
+                TrackNumber => &["ITRK", "IPRT"],
+                DiscNumber | TrackTotal | DiscTotal => &[],
+                Date => &["ICRD"],
+                Genre => &["IGNR"],
+                Comment => &["ICMT"],
+                Composer => &["IMUS"],
+                Lyricist => &["IWRI"],
+                Copyright => &["ICOP"],
+                // INFO の ISRC は "Source" で ISRC コードではない
+                Illustrator | VoiceActor | Circle | Publisher | Isrc | CatalogNumber | Bpm | Compilation
+                | TitleSort | ArtistSort | AlbumSort | AlbumArtistSort | ComposerSort => &[],
+            },
+        }
+    }
+}
+
+/// ExtendedTags の各項目と書き込む文字列（None は未指定）。コンピレーションは "1"（false は空文字 = 削除）にする。
+/// 声優は 1 人ずつ別の値として set_values で書くため含めない
+pub fn extended_field_values(tags: &ExtendedTags) -> [(StandardField, Option<String>); 15] {
+    use StandardField::*;
+    [
+        (Composer, tags.composer.clone()),
+        (Lyricist, tags.lyricist.clone()),
+        (Illustrator, tags.illustrator.clone()),
+        (Circle, tags.circle.clone()),
+        (Publisher, tags.publisher.clone()),
+        (Copyright, tags.copyright.clone()),
+        (Isrc, tags.isrc.clone()),
+        (CatalogNumber, tags.catalog_number.clone()),
+        (Bpm, tags.bpm.clone()),
+        (Compilation, tags.compilation.map(|c| if c { "1" } else { "" }.to_string())),
+        (TitleSort, tags.title_sort.clone()),
+        (ArtistSort, tags.artist_sort.clone()),
+        (AlbumSort, tags.album_sort.clone()),
+        (AlbumArtistSort, tags.album_artist_sort.clone()),
+        (ComposerSort, tags.composer_sort.clone()),
+    ]
+}
+
+impl FileTags {
+    /// フィールドの値を文字列で返す（複数値は ";" で連結）。タグブロックは優先度順に探す
+    pub fn text(&self, field: StandardField) -> Option<String> {
+        self.tags.iter().find_map(|block| {
+            field
+                .frame_ids(block.format)
+                .iter()
+                .filter_map(|id| block.get(id))
+                .find_map(|value| frame_value_to_text(field, value))
+        })
+    }
+
+    /// フィールドの値を 1 つずつ返す。複数値のフレームに加え、";" で連結された値も分ける
+    pub fn values(&self, field: StandardField) -> Vec<String> {
+        self.tags
+            .iter()
+            .find_map(|block| {
+                if block.format == TagFormat::Mp4 && matches!(field, StandardField::Artist) {
+                    if let Some(values) = block.get(MP4_ARTISTS_ID).and_then(|v| frame_values(field, v)) {
+                        return Some(values);
+                    }
+                }
+                field
+                    .frame_ids(block.format)
+                    .iter()
+                    .filter_map(|id| block.get(id))
+                    .find_map(|value| frame_values(field, value))
+            })
+            .unwrap_or_default()
+    }
+
+    /// 指定した ID のフレームを全タグブロックから探す
+    pub fn find(&self, format: TagFormat, id: &str) -> Option<&FrameValue> {
+        self.tags
+            .iter()
+            .filter(|block| block.format == format)
+            .find_map(|block| block.get(id))
+    }
+
+    pub fn pictures(&self) -> impl Iterator<Item = &Picture> {
+        self.tags
+            .iter()
+            .flat_map(|block| block.frames.iter())
+            .filter_map(|frame| match &frame.value {
+                FrameValue::Picture(picture) => Some(picture),
+                _ => None,
+            })
+    }
+}
+
+fn frame_value_to_text(field: StandardField, value: &FrameValue) -> Option<String> {
+    let text = match value {
+        FrameValue::Text(values) => {
+            let values: Vec<&str> = values
+                .iter()
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .collect();
+            if matches!(field, StandardField::Genre) {
+                values.iter().map(|v| resolve_genre(v)).collect::<Vec<_>>().join(VALUE_SEPARATOR)
+            } else {
+                values.join(VALUE_SEPARATOR)
+            }
+        }
+        FrameValue::Pair(number, total) if *number > 0 => {
+            if *total > 0 {
+                format!("{}/{}", number, total)
+            } else {
+                number.to_string()
+            }
+        }
+        // MP4 の gnre は ID3v1 のジャンル番号 + 1
+        FrameValue::Integer(n) if matches!(field, StandardField::Genre) => {
+            id3v1::genre_name(n.checked_sub(1)?.try_into().ok()?)?.to_string()
+        }
+        FrameValue::Integer(n) => n.to_string(),
+        _ => return None,
+    };
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+fn frame_values(field: StandardField, value: &FrameValue) -> Option<Vec<String>> {
+    let values: Vec<String> = match value {
+        FrameValue::Text(values) => values
+            .iter()
+            .flat_map(|v| v.split(VALUE_SEPARATOR))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| if matches!(field, StandardField::Genre) { resolve_genre(v) } else { v.to_string() })
+            .collect(),
+        _ => vec![frame_value_to_text(field, value)?],
+    };
+    (!values.is_empty()).then_some(values)
+}
+
+/// ID3 の "(17)" / "17" / "(17)Rock" 形式のジャンル番号を名前に置き換える
+fn resolve_genre(value: &str) -> String {
+    let inner = value
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+        .map(|(number, rest)| (number, rest.trim()));
+
+    match inner {
+        Some((_, rest)) if !rest.is_empty() => rest.to_string(),
+        Some((number, _)) => number
+            .parse::<u8>()
+            .ok()
+            .and_then(id3v1::genre_name)
+            .map(str::to_string)
+            .unwrap_or_else(|| value.to_string()),
+        None => value
+            .parse::<u8>()
+            .ok()
+            .and_then(id3v1::genre_name)
+            .map(str::to_string)
+            .unwrap_or_else(|| value.to_string()),
+    }
+}
+
+/// 先頭のバイト列から判定したコンテナ
+enum Container {
+    Mpeg,
+    /// FLAC（"fLaC" の位置。ID3v2 が前置されている場合は 0 以外）
+    Flac(u64),
+    Ogg,
+    Riff,
+    Mp4,
+}
+
+fn detect_container(file: &mut File, file_len: u64) -> Result<Container, String> {
+    let head = read_at(file, file_len, 0, 12.min(file_len as usize))
+        .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+
+    if head.starts_with(b"fLaC") {
+        return Ok(Container::Flac(0));
+    }
+    if head.starts_with(b"OggS") {
+        return Ok(Container::Ogg);
+    }
+    if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WAVE") {
+        return Ok(Container::Riff);
+    }
+    if head.get(4..8) == Some(b"ftyp") {
+        return Ok(Container::Mp4);
+    }
+    if head.starts_with(b"ID3") {
+        // ID3v2 の後ろに FLAC が続くファイルもある
+        let tag_len = id3v2::total_size(&head).unwrap_or(0);
+        let after = read_at(file, file_len, tag_len, 4).unwrap_or_default();
+        if after == b"fLaC" {
+            return Ok(Container::Flac(tag_len));
+        }
+        return Ok(Container::Mpeg);
+    }
+    if mpeg::is_frame_sync(&head) {
+        return Ok(Container::Mpeg);
+    }
+
+    Err("タグの読み込みに対応していない形式です".to_string())
+}
+
+fn open_with_len(path: &Path, write: bool) -> Result<(File, u64), String> {
+    let ep = crate::path_utils::to_extended_length_path_if_needed(path);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(write)
+        .open(&ep)
+        .map_err(|e| format!("ファイルを開けませんでした: {}", e))?;
+    let file_len = file
+        .metadata()
+        .map_err(|e| format!("ファイル情報の取得に失敗しました: {}", e))?
+        .len();
+    Ok((file, file_len))
+}
+
+/// ファイルのタグと音声情報を読み込む。形式は拡張子ではなく先頭のバイト列で判定する
+pub fn read_file(path: &Path) -> Result<FileTags, String> {
+    let (mut file, file_len) = open_with_len(path, false)?;
+
+    match detect_container(&mut file, file_len)? {
+        Container::Mpeg => mpeg::read(&mut file, file_len),
+        Container::Flac(start) => flac::read(&mut file, file_len, start),
+        Container::Ogg => ogg::read(&mut file, file_len),
+        Container::Riff => riff::read(&mut file, file_len),
+        Container::Mp4 => mp4::read(&mut file, file_len),
+    }
+}
+
+/// ネイティブの書き込みに対応しているか（WAV と未対応のタグ・ストリームは FFmpeg で書き込む）
+pub fn supports_writing(path: &Path) -> bool {
+    let Ok((mut file, file_len)) = open_with_len(path, false) else { return false };
+    match detect_container(&mut file, file_len) {
+        // ID3v2.2 や Ogg FLAC など、読めない形式は書き込みもしない
+        Ok(Container::Mpeg | Container::Flac(_) | Container::Ogg | Container::Mp4) => read_file(path).is_ok(),
+        _ => false,
+    }
+}
+
+/// 主タグ（MP3 は ID3v2、FLAC / Ogg は Vorbis comment、MP4 は ilst）を読み込み、update で編集して書き戻す。
+/// 音声データは書き換えず、タグ領域に余白があればその場で上書きする
+pub fn write_file(path: &Path, update: impl FnOnce(&mut TagBlock)) -> Result<(), String> {
+    let (mut file, file_len) = open_with_len(path, true)?;
+
+    match detect_container(&mut file, file_len)? {
+        Container::Mpeg => id3v2::write(file, file_len, path, update),
+        Container::Flac(start) => flac::write(file, file_len, start, path, update),
+        Container::Ogg => ogg::write(file, file_len, path, update),
+        Container::Mp4 => mp4::write(file, file_len, path, update),
+        Container::Riff => Err("WAV へのタグの書き込みには対応していません".to_string()),
+    }
+}
+
+/// 新しく書くタグ領域に確保する余白（次回以降の編集をその場で済ませるため）
+const DEFAULT_PADDING: usize = 2048;
+
+/// タグ領域の先頭部分だけをその場で上書きする
+fn overwrite_at(file: &mut File, offset: u64, data: &[u8]) -> Result<(), String> {
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.write_all(data))
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("タグの書き込みに失敗しました: {}", e))
+}
+
+/// 同じディレクトリの一時ファイルに書き出してから元ファイルと置き換える
+fn rewrite_file(
+    path: &Path,
+    source: File,
+    write: impl FnOnce(&mut File, &mut BufWriter<File>) -> std::io::Result<()>,
+) -> Result<(), String> {
+    // 元の名前を含めない短い一時ファイル名にする（名前が上限の長さに近いファイルでも作れるように）
+    let temp_path = crate::path_utils::temp_sibling_path(path);
+    let temp_ep = crate::path_utils::to_extended_length_path_if_needed(&temp_path);
+    let partial_output = PartialOutput::new(&temp_path);
+
+    let mut source = source;
+    let temp_file = File::create(&temp_ep).map_err(|e| format!("一時ファイルの作成に失敗しました: {}", e))?;
+    let mut writer = BufWriter::new(temp_file);
+    write(&mut source, &mut writer)
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("タグの書き込みに失敗しました: {}", e))?;
+    // Windows では開いたままのファイルを置き換えられないため、先に閉じる
+    drop(writer);
+    drop(source);
+
+    let ep = crate::path_utils::to_extended_length_path_if_needed(path);
+    fs::rename(&temp_ep, &ep).map_err(|e| format!("元ファイルの置き換えに失敗しました: {}", e))?;
+    partial_output.keep();
+    Ok(())
+}
+
+/// source の offset から len バイト（None は末尾まで）を writer にコピーする
+fn copy_range<W: Write>(source: &mut File, offset: u64, len: Option<u64>, writer: &mut W) -> std::io::Result<()> {
+    source.seek(SeekFrom::Start(offset))?;
+    match len {
+        Some(len) => std::io::copy(&mut source.take(len), writer)?,
+        None => std::io::copy(source, writer)?,
+    };
+    Ok(())
+}
+
+/// offset から len バイトを読む。ファイル末尾を超える指定はエラーにする（壊れたサイズ値で巨大な確保をしないため）
+fn read_at(file: &mut File, file_len: u64, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
+    let end = offset.checked_add(len as u64);
+    if !matches!(end, Some(end) if end <= file_len) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "範囲がファイルの末尾を超えています",
+        ));
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0u8; len];
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u16> {
+    data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn le_u16(data: &[u8], at: usize) -> Option<u16> {
+    data.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn le_u32(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// ISO-8859-1 としてデコードする
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// UTF-8 として不正なら ISO-8859-1 とみなす（RIFF INFO や Vorbis の壊れたタグ向け）
+fn decode_utf8_or_latin1(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => decode_latin1(bytes),
+    }
+}
+
+/// 末尾の NUL を取り除く
+fn trim_nul(text: &str) -> String {
+    text.trim_end_matches('\0').to_string()
+}
+
+/// ファイルサイズと再生時間から平均ビットレートを求める
+fn average_bit_rate(bytes: u64, duration_seconds: Option<f64>) -> Option<u32> {
+    let duration = duration_seconds.filter(|d| *d > 0.0)?;
+    Some((bytes as f64 * 8.0 / duration) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    /// テスト用のファイルを一時ディレクトリに書き出す（name はテストごとに変える）
+    pub(super) fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("vte-tags-{}-{}", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+}
@@ -0,0 +1,603 @@
+use std::fs::File;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+
+use super::{read_at, AudioProperties, FileTags, FrameValue, Picture, TagBlock, TagFormat};
+
+/// moov をメモリに読み込む上限（カバー画像を含めても通常は数 MB）
+const MAX_MOOV_BYTES: u64 = 256 * 1024 * 1024;
+
+/// data アトムの型（well-known types）
+const DATA_UTF8: u32 = 1;
+const DATA_UTF16: u32 = 2;
+const DATA_JPEG: u32 = 13;
+const DATA_PNG: u32 = 14;
+const DATA_SIGNED_INT: u32 = 21;
+const DATA_UNSIGNED_INT: u32 = 22;
+const DATA_BMP: u32 = 27;
+
+/// アトムのヘッダーを解析し、(名前, ヘッダー長, アトム全体の長さ) を返す。長さ 0 は末尾まで
+fn parse_atom_header(header: &[u8], remaining: u64) -> Option<([u8; 4], u64, u64)> {
+    let size = super::be_u32(header, 0)? as u64;
+    let name: [u8; 4] = header.get(4..8)?.try_into().ok()?;
+    let (header_len, total) = match size {
+        0 => (8, remaining),
+        1 => {
+            let large = u64::from_be_bytes(header.get(8..16)?.try_into().ok()?);
+            (16, large)
+        }
+        _ => (8, size),
+    };
+    if total < header_len || total > remaining {
+        return None;
+    }
+    Some((name, header_len, total))
+}
+
+/// メモリ上のアトム列を (名前, 本体) に分解する
+fn children(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    child_ranges(data)
+        .into_iter()
+        .map(|(name, _, body)| (name, &data[body]))
+        .collect()
+}
+
+/// メモリ上のアトム列を (名前, アトム全体の範囲, 本体の範囲) に分解する
+fn child_ranges(data: &[u8]) -> Vec<([u8; 4], Range<usize>, Range<usize>)> {
+    let mut atoms = Vec::new();
+    let mut offset = 0usize;
+    while offset + 8 <= data.len() {
+        let remaining = (data.len() - offset) as u64;
+        let Some((name, header_len, total)) = parse_atom_header(&data[offset..], remaining) else { break };
+        let end = offset + total as usize;
+        atoms.push((name, offset..end, offset + header_len as usize..end));
+        offset = end;
+    }
+    atoms
+}
+
+fn child<'a>(data: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    children(data).into_iter().find(|(n, _)| n == name).map(|(_, body)| body)
+}
+
+/// meta はフルボックス（version/flags 4 バイト）だが、QuickTime 形式では付かないことがある
+fn meta_children(meta: &[u8]) -> &[u8] {
+    if meta.get(4..8) == Some(b"hdlr") {
+        meta
+    } else {
+        meta.get(4..).unwrap_or_default()
+    }
+}
+
+pub(super) fn read(file: &mut File, file_len: u64) -> Result<FileTags, String> {
+    let moov = find_moov(file, file_len)?;
+
+    let mut properties = read_audio_track(&moov);
+    properties.bit_rate = super::average_bit_rate(file_len, properties.duration_seconds);
+
+    Ok(FileTags {
+        properties,
+        tags: vec![parse_ilst(&moov)],
+    })
+}
+
+fn parse_ilst(moov: &[u8]) -> TagBlock {
+    let ilst = child(moov, b"udta")
+        .and_then(|udta| child(udta, b"meta"))
+        .or_else(|| child(moov, b"meta"))
+        .and_then(|meta| child(meta_children(meta), b"ilst"));
+
+    let mut block = TagBlock::new(TagFormat::Mp4);
+    if let Some(ilst) = ilst {
+        for (name, item) in children(ilst) {
+            parse_item(&mut block, &name, item);
+        }
+    }
+    block
+}
+
+/// ファイル直下のアトム
+struct TopLevelAtom {
+    name: [u8; 4],
+    offset: u64,
+    header_len: u64,
+    total: u64,
+}
+
+fn top_level_atoms(file: &mut File, file_len: u64) -> Result<Vec<TopLevelAtom>, String> {
+    let mut atoms = Vec::new();
+    let mut offset = 0u64;
+    while offset + 8 <= file_len {
+        let header = read_at(file, file_len, offset, 16.min((file_len - offset) as usize))
+            .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+        let (name, header_len, total) = parse_atom_header(&header, file_len - offset)
+            .ok_or_else(|| "MP4 のアトム構造が不正です".to_string())?;
+        atoms.push(TopLevelAtom { name, offset, header_len, total });
+        offset += total;
+    }
+    Ok(atoms)
+}
+
+fn read_moov(file: &mut File, file_len: u64, moov: &TopLevelAtom) -> Result<Vec<u8>, String> {
+    let body_len = moov.total - moov.header_len;
+    if body_len > MAX_MOOV_BYTES {
+        return Err("moov アトムが大きすぎます".to_string());
+    }
+    read_at(file, file_len, moov.offset + moov.header_len, body_len as usize)
+        .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))
+}
+
+fn find_moov(file: &mut File, file_len: u64) -> Result<Vec<u8>, String> {
+    let atoms = top_level_atoms(file, file_len)?;
+    let moov = atoms
+        .iter()
+        .find(|atom| &atom.name == b"moov")
+        .ok_or_else(|| "moov アトムが見つかりません".to_string())?;
+    read_moov(file, file_len, moov)
+}
+
+/// 最初の音声トラック（hdlr が soun）のコーデック・サンプルレート・再生時間
+fn read_audio_track(moov: &[u8]) -> AudioProperties {
+    let mut properties = AudioProperties::default();
+
+    let audio_track = children(moov)
+        .into_iter()
+        .filter(|(name, _)| name == b"trak")
+        .filter_map(|(_, trak)| child(trak, b"mdia"))
+        .find(|mdia| child(mdia, b"hdlr").and_then(|hdlr| hdlr.get(8..12)) == Some(b"soun"));
+
+    if let Some(mdia) = audio_track {
+        properties.duration_seconds = child(mdia, b"mdhd").and_then(header_duration);
+
+        let sample_entry = child(mdia, b"minf")
+            .and_then(|minf| child(minf, b"stbl"))
+            .and_then(|stbl| child(stbl, b"stsd"))
+            // フルボックス 4 バイト + エントリ数 4 バイト
+            .and_then(|stsd| stsd.get(8..))
+            .and_then(|entries| children(entries).into_iter().next());
+
+        if let Some((format, entry)) = sample_entry {
+            properties.codec = Some(
+                match &format {
+                    b"mp4a" => "aac",
+                    b"alac" => "alac",
+                    b"fLaC" => "flac",
+                    b"Opus" => "opus",
+                    b"ac-3" => "ac3",
+                    b"ec-3" => "eac3",
+                    _ => "unknown",
+                }
+                .to_string(),
+            );
+            // SampleEntry(8) + AudioSampleEntry: version/revision/vendor(8) channels(2) size(2) ... rate(16.16)
+            properties.channels = super::be_u16(entry, 16);
+            properties.sample_rate = super::be_u32(entry, 24).map(|rate| rate >> 16).filter(|r| *r > 0);
+        }
+    }
+
+    if properties.duration_seconds.is_none() {
+        properties.duration_seconds = child(moov, b"mvhd").and_then(header_duration);
+    }
+
+    properties
+}
+
+/// mvhd / mdhd の timescale と duration から秒数を求める（version 1 は 64bit の時刻・長さ）
+fn header_duration(header: &[u8]) -> Option<f64> {
+    let (timescale, duration) = if header.first() == Some(&1) {
+        (super::be_u32(header, 20)?, u64::from_be_bytes(header.get(24..32)?.try_into().ok()?))
+    } else {
+        (super::be_u32(header, 12)?, super::be_u32(header, 16)? as u64)
+    };
+    if timescale == 0 {
+        return None;
+    }
+    Some(duration as f64 / timescale as f64)
+}
+
+fn parse_item(block: &mut TagBlock, name: &[u8; 4], item: &[u8]) {
+    let mut id = super::decode_latin1(name);
+    let atoms = children(item);
+
+    if name == b"----" {
+        // フリーフォーム: mean（名前空間）と name はフルボックスの文字列
+        let text_of = |atom: &[u8; 4]| {
+            atoms
+                .iter()
+                .find(|(n, _)| n == atom)
+                .and_then(|(_, body)| body.get(4..))
+                .map(|b| String::from_utf8_lossy(b).into_owned())
+        };
+        let (Some(mean), Some(field)) = (text_of(b"mean"), text_of(b"name")) else { return };
+        id = format!("----:{}:{}", mean, field);
+    }
+
+    for (atom, data) in atoms {
+        if &atom != b"data" || data.len() < 8 {
+            continue;
+        }
+        let data_type = super::be_u32(data, 0).unwrap_or(0) & 0x00FF_FFFF;
+        let payload = &data[8..];
+
+        match (name, data_type) {
+            (b"trkn", _) | (b"disk", _) => {
+                let number = super::be_u16(payload, 2).unwrap_or(0) as u32;
+                let total = super::be_u16(payload, 4).unwrap_or(0) as u32;
+                block.push(&id, FrameValue::Pair(number, total));
+            }
+            (b"covr", _) => {
+                let mime = match data_type {
+                    DATA_PNG => "image/png".to_string(),
+                    DATA_BMP => "image/bmp".to_string(),
+                    DATA_JPEG => "image/jpeg".to_string(),
+                    _ => crate::utils::sniff_image(payload).mime.to_string(),
+                };
+                block.push(
+                    &id,
+                    FrameValue::Picture(Picture {
+                        picture_type: 3,
+                        mime,
+                        description: String::new(),
+                        data: payload.to_vec(),
+                    }),
+                );
+            }
+            (_, DATA_UTF8) => block.push_text(&id, String::from_utf8_lossy(payload).into_owned()),
+            (_, DATA_UTF16) => {
+                let units: Vec<u16> = payload.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+                block.push_text(&id, String::from_utf16_lossy(&units));
+            }
+            // tmpo / cpil / gnre などは暗黙型（0）で整数が入っている
+            (_, DATA_SIGNED_INT) | (_, DATA_UNSIGNED_INT) | (_, 0) if payload.len() <= 8 && !payload.is_empty() => {
+                let value = payload.iter().fold(0i64, |acc, &b| (acc << 8) | b as i64);
+                block.push(&id, FrameValue::Integer(value));
+            }
+            _ => block.push(&id, FrameValue::Binary(payload.to_vec())),
+        }
+    }
+}
+
+/// ilst を組み立て直して moov を書き換える。
+/// moov の大きさが変わる場合は、直後の free アトムで吸収できるか moov が末尾にあればその場で書き、
+/// それ以外は後ろのデータをずらしてチャンクオフセット（stco / co64）を補正する
+pub(super) fn write(
+    mut file: File,
+    file_len: u64,
+    path: &Path,
+    update: impl FnOnce(&mut TagBlock),
+) -> Result<(), String> {
+    let atoms = top_level_atoms(&mut file, file_len)?;
+    let moov_index = atoms
+        .iter()
+        .position(|atom| &atom.name == b"moov")
+        .ok_or_else(|| "moov アトムが見つかりません".to_string())?;
+    let moov = &atoms[moov_index];
+    let mut moov_body = read_moov(&mut file, file_len, moov)?;
+
+    let mut block = parse_ilst(&moov_body);
+    update(&mut block);
+    let ilst = serialize_ilst(&block);
+    moov_body = replace_ilst(&moov_body, &ilst);
+
+    let moov_start = moov.offset;
+    let old_len = moov.total;
+    let new_len = moov_body.len() as u64 + 8;
+
+    // moov の直後に続く free / skip アトム（と、moov 自体が末尾にあるか）
+    let following = &atoms[moov_index + 1..];
+    let free_after: u64 = following
+        .iter()
+        .take_while(|atom| matches!(&atom.name, b"free" | b"skip"))
+        .map(|atom| atom.total)
+        .sum();
+    let at_end = following.iter().all(|atom| matches!(&atom.name, b"free" | b"skip"));
+    let room = old_len + free_after;
+
+    // 余った領域は free アトムで埋める（8 バイト未満はアトムにできない）
+    if new_len == room || new_len + 8 <= room {
+        let mut bytes = build_atom(b"moov", &moov_body);
+        if new_len < room {
+            bytes.extend_from_slice(&free_atom((room - new_len) as usize));
+        }
+        return super::overwrite_at(&mut file, moov_start, &bytes);
+    }
+    if at_end {
+        let mut bytes = build_atom(b"moov", &moov_body);
+        bytes.extend_from_slice(&free_atom(super::DEFAULT_PADDING));
+        super::overwrite_at(&mut file, moov_start, &bytes)?;
+        return file
+            .set_len(moov_start + bytes.len() as u64)
+            .map_err(|e| format!("タグの書き込みに失敗しました: {}", e));
+    }
+
+    if atoms.iter().any(|atom| &atom.name == b"moof") {
+        return Err("フラグメント化された MP4 の moov を大きくする書き込みには対応していません".to_string());
+    }
+
+    // moov の後ろにあるデータ（mdat）がずれる分だけチャンクオフセットを補正する
+    let moov_end = moov_start + old_len;
+    let shift = new_len + super::DEFAULT_PADDING as u64 - old_len;
+    shift_chunk_offsets(&mut moov_body, moov_end, shift)?;
+    let mut bytes = build_atom(b"moov", &moov_body);
+    bytes.extend_from_slice(&free_atom(super::DEFAULT_PADDING));
+
+    super::rewrite_file(path, file, |source, writer| {
+        super::copy_range(source, 0, Some(moov_start), writer)?;
+        writer.write_all(&bytes)?;
+        super::copy_range(source, moov_end, None, writer)
+    })
+}
+
+fn build_atom(name: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut atom = Vec::with_capacity(8 + body.len());
+    atom.extend_from_slice(&((8 + body.len()) as u32).to_be_bytes());
+    atom.extend_from_slice(name);
+    atom.extend_from_slice(body);
+    atom
+}
+
+/// 全体で len バイトの free アトム
+fn free_atom(len: usize) -> Vec<u8> {
+    build_atom(b"free", &vec![0u8; len - 8])
+}
+
+/// container の中の name アトムの本体を置き換える（無ければ末尾に追加する）
+fn replace_child(container: &[u8], name: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(container.len() + body.len());
+    let mut replaced = false;
+    for (child_name, range, _) in child_ranges(container) {
+        if &child_name == name && !replaced {
+            out.extend_from_slice(&build_atom(name, body));
+            replaced = true;
+        } else {
+            out.extend_from_slice(&container[range]);
+        }
+    }
+    if !replaced {
+        out.extend_from_slice(&build_atom(name, body));
+    }
+    out
+}
+
+/// moov 内の ilst を置き換えた moov の本体を返す。udta/meta が無ければ iTunes と同じ構造で作る
+fn replace_ilst(moov: &[u8], ilst: &[u8]) -> Vec<u8> {
+    // 古い QuickTime 形式の moov/meta に ilst がある場合はそちらを書き換える
+    let udta_meta = child(moov, b"udta").and_then(|udta| child(udta, b"meta"));
+    if udta_meta.is_none() {
+        if let Some(meta) = child(moov, b"meta").filter(|meta| child(meta_children(meta), b"ilst").is_some()) {
+            return replace_child(moov, b"meta", &replace_meta_ilst(meta, ilst));
+        }
+    }
+
+    let meta = match udta_meta {
+        Some(meta) => replace_meta_ilst(meta, ilst),
+        None => {
+            let mut meta = vec![0u8; 4];
+            meta.extend_from_slice(&build_atom(b"hdlr", &mdir_handler()));
+            meta.extend_from_slice(&build_atom(b"ilst", ilst));
+            meta
+        }
+    };
+    let udta = child(moov, b"udta").unwrap_or_default();
+    replace_child(moov, b"udta", &replace_child(udta, b"meta", &meta))
+}
+
+fn replace_meta_ilst(meta: &[u8], ilst: &[u8]) -> Vec<u8> {
+    let children_start = meta.len() - meta_children(meta).len();
+    let mut out = meta[..children_start].to_vec();
+    out.extend_from_slice(&replace_child(&meta[children_start..], b"ilst", ilst));
+    out
+}
+
+/// iTunes 形式のメタデータを示す hdlr の本体
+fn mdir_handler() -> Vec<u8> {
+    let mut hdlr = vec![0u8; 8];
+    hdlr.extend_from_slice(b"mdirappl");
+    hdlr.extend_from_slice(&[0u8; 9]);
+    hdlr
+}
+
+/// path の順にたどったアトムの本体の範囲（同名のアトムはすべて）を、data の先頭からの位置で返す
+fn descend(data: &[u8], path: &[&[u8; 4]]) -> Vec<Range<usize>> {
+    let Some((first, rest)) = path.split_first() else { return std::iter::once(0..data.len()).collect() };
+    child_ranges(data)
+        .into_iter()
+        .filter(|(name, _, _)| name == *first)
+        .flat_map(|(_, _, body)| {
+            descend(&data[body.clone()], rest)
+                .into_iter()
+                .map(move |inner| body.start + inner.start..body.start + inner.end)
+        })
+        .collect()
+}
+
+/// 全トラックの stco / co64 のうち、threshold 以降を指すオフセットに shift を足す
+fn shift_chunk_offsets(moov: &mut [u8], threshold: u64, shift: u64) -> Result<(), String> {
+    let mut tables = Vec::new();
+    for stbl in descend(moov, &[b"trak", b"mdia", b"minf", b"stbl"]) {
+        for (name, _, body) in child_ranges(&moov[stbl.clone()]) {
+            if matches!(&name, b"stco" | b"co64") {
+                tables.push((name == *b"co64", stbl.start + body.start..stbl.start + body.end));
+            }
+        }
+    }
+
+    for (is_co64, range) in tables {
+        let table = &mut moov[range];
+        let count = super::be_u32(table, 4).unwrap_or(0) as usize;
+        let width = if is_co64 { 8 } else { 4 };
+        if 8 + count * width > table.len() {
+            return Err("MP4 のチャンクオフセット表が不正です".to_string());
+        }
+        for entry in table[8..8 + count * width].chunks_exact_mut(width) {
+            let offset = entry.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+            if offset < threshold {
+                continue;
+            }
+            if is_co64 {
+                entry.copy_from_slice(&(offset + shift).to_be_bytes());
+            } else {
+                let shifted = u32::try_from(offset + shift)
+                    .map_err(|_| "チャンクオフセットが 4GB を超えるため書き込めません".to_string())?;
+                entry.copy_from_slice(&shifted.to_be_bytes());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 整数アイテムの大きさ（バイト数）。一覧に無いものは値に合わせる
+fn integer_width(id: &str, value: i64) -> usize {
+    match id {
+        "cpil" | "pgap" | "pcst" | "hdvd" | "stik" | "rtng" | "shwm" => 1,
+        "tmpo" | "gnre" => 2,
+        "tvsn" | "tves" | "sfID" | "atID" | "cnID" | "plID" | "geID" | "cmID" => 4,
+        _ if i8::try_from(value).is_ok() => 1,
+        _ if i16::try_from(value).is_ok() => 2,
+        _ if i32::try_from(value).is_ok() => 4,
+        _ => 8,
+    }
+}
+
+fn serialize_ilst(block: &TagBlock) -> Vec<u8> {
+    let mut ilst = Vec::new();
+    for (index, frame) in block.frames.iter().enumerate() {
+        // 複数の画像は 1 つの covr に data アトムを並べるため、最初の画像のところでまとめて書く
+        let is_picture = |f: &&super::Frame| f.id == frame.id && matches!(f.value, FrameValue::Picture(_));
+        if is_picture(&frame) && block.frames[..index].iter().any(|f| is_picture(&f)) {
+            continue;
+        }
+
+        let mut item = Vec::new();
+        let name: [u8; 4] = if let Some(freeform) = frame.id.strip_prefix("----:") {
+            let Some((mean, field)) = freeform.split_once(':') else { continue };
+            item.extend_from_slice(&build_atom(b"mean", &[&[0u8; 4][..], mean.as_bytes()].concat()));
+            item.extend_from_slice(&build_atom(b"name", &[&[0u8; 4][..], field.as_bytes()].concat()));
+            *b"----"
+        } else {
+            // ©nam などの © は Latin-1 の 1 バイト
+            let bytes: Option<Vec<u8>> = frame.id.chars().map(|c| u8::try_from(c).ok()).collect();
+            match bytes.and_then(|b| <[u8; 4]>::try_from(b).ok()) {
+                Some(name) => name,
+                None => continue,
+            }
+        };
+
+        let mut push_data = |data_type: u32, payload: &[u8]| {
+            let mut data = Vec::with_capacity(8 + payload.len());
+            data.extend_from_slice(&data_type.to_be_bytes());
+            data.extend_from_slice(&[0u8; 4]);
+            data.extend_from_slice(payload);
+            item.extend_from_slice(&build_atom(b"data", &data));
+        };
+        match &frame.value {
+            FrameValue::Text(values) => values.iter().for_each(|v| push_data(DATA_UTF8, v.as_bytes())),
+            FrameValue::Pair(number, total) => {
+                let mut payload = vec![0u8; 2];
+                payload.extend_from_slice(&(*number as u16).to_be_bytes());
+                payload.extend_from_slice(&(*total as u16).to_be_bytes());
+                // trkn は末尾に 2 バイトの予約領域を持つ
+                if &name == b"trkn" {
+                    payload.extend_from_slice(&[0u8; 2]);
+                }
+                push_data(0, &payload);
+            }
+            FrameValue::Integer(value) => {
+                let width = integer_width(&frame.id, *value);
+                let data_type = if &name == b"gnre" { 0 } else { DATA_SIGNED_INT };
+                push_data(data_type, &value.to_be_bytes()[8 - width..]);
+            }
+            FrameValue::Picture(_) => {
+                for other in block.frames[index..].iter().filter(is_picture) {
+                    let FrameValue::Picture(picture) = &other.value else { continue };
+                    let data_type = match picture.mime.as_str() {
+                        "image/png" => DATA_PNG,
+                        "image/bmp" => DATA_BMP,
+                        _ => DATA_JPEG,
+                    };
+                    push_data(data_type, &picture.data);
+                }
+            }
+            FrameValue::Binary(data) => push_data(0, data),
+            // ID3v2 専用の値は MP4 には書けない
+            FrameValue::Undecoded { .. } => continue,
+        }
+        ilst.extend_from_slice(&build_atom(&name, &item));
+    }
+    ilst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::tests::temp_file;
+    use crate::tags::StandardField;
+
+    const AUDIO: &[u8] = b"AUDIO-CHUNK";
+
+    /// ftyp + moov（音声トラックの stco だけ）+ [free] + mdat
+    fn mp4_file(name: &str, free_after_moov: usize) -> std::path::PathBuf {
+        let moov_len = |stco_offset: u32| {
+            let mut stco = vec![0u8; 4];
+            stco.extend_from_slice(&1u32.to_be_bytes());
+            stco.extend_from_slice(&stco_offset.to_be_bytes());
+            let mut hdlr = vec![0u8; 8];
+            hdlr.extend_from_slice(b"soun");
+            let stbl = build_atom(b"stbl", &build_atom(b"stco", &stco));
+            let minf = build_atom(b"minf", &stbl);
+            let mdia = build_atom(b"mdia", &[build_atom(b"hdlr", &hdlr), minf].concat());
+            build_atom(b"moov", &build_atom(b"trak", &mdia))
+        };
+        let ftyp = build_atom(b"ftyp", b"M4A \0\0\0\0");
+        let free = if free_after_moov > 0 { free_atom(free_after_moov) } else { Vec::new() };
+        let mdat_start = ftyp.len() + moov_len(0).len() + free.len();
+        let moov = moov_len((mdat_start + 8) as u32);
+
+        let bytes = [ftyp, moov, free, build_atom(b"mdat", AUDIO)].concat();
+        temp_file(name, &bytes)
+    }
+
+    /// stco の最初のオフセットと、そこから読んだ音声データ
+    fn chunk_at_offset(path: &Path) -> (u64, Vec<u8>) {
+        let (mut file, file_len) = crate::tags::open_with_len(path, false).unwrap();
+        let moov = find_moov(&mut file, file_len).unwrap();
+        let stco = descend(&moov, &[b"trak", b"mdia", b"minf", b"stbl", b"stco"]).remove(0);
+        let offset = crate::tags::be_u32(&moov[stco], 8).unwrap() as u64;
+        let chunk = read_at(&mut file, file_len, offset, AUDIO.len()).unwrap();
+        (offset, chunk)
+    }
+
+    #[test]
+    fn growing_moov_before_mdat_shifts_chunk_offsets() {
+        let path = mp4_file("mp4-shift.m4a", 0);
+        let (before, _) = chunk_at_offset(&path);
+        crate::tags::write_file(&path, |block| {
+            block.set_field(StandardField::Title, "タイトル");
+            block.set_values(StandardField::Artist, &["A".to_string(), "B".to_string()]);
+        })
+        .unwrap();
+
+        let (after, chunk) = chunk_at_offset(&path);
+        assert!(after > before);
+        assert_eq!(chunk, AUDIO);
+        let tags = crate::tags::read_file(&path).unwrap();
+        assert_eq!(tags.text(StandardField::Title).as_deref(), Some("タイトル"));
+        assert_eq!(tags.values(StandardField::Artist), ["A", "B"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn free_atom_after_moov_absorbs_growth() {
+        let path = mp4_file("mp4-free.m4a", 4096);
+        let len = std::fs::metadata(&path).unwrap().len();
+        let (before, _) = chunk_at_offset(&path);
+        crate::tags::write_file(&path, |block| block.set_field(StandardField::Album, "アルバム")).unwrap();
+
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+        assert_eq!(chunk_at_offset(&path), (before, AUDIO.to_vec()));
+        let tags = crate::tags::read_file(&path).unwrap();
+        assert_eq!(tags.text(StandardField::Album).as_deref(), Some("アルバム"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use std::fs::File;
+
+use super::{id3v1, id3v2, read_at, AudioProperties, FileTags};
+
+/// 先頭フレームを探す範囲（ID3v2 の後ろのゴミやパディングを読み飛ばすため）
+const SYNC_SEARCH_BYTES: usize = 64 * 1024;
+
+/// MPEG-1 Layer III のビットレート（kbps）
+const BITRATES_V1_L3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+/// MPEG-1 Layer II
+const BITRATES_V1_L2: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+/// MPEG-1 Layer I
+const BITRATES_V1_L1: [u32; 15] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+/// MPEG-2 / 2.5 Layer II・III
+const BITRATES_V2_L23: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+/// MPEG-2 / 2.5 Layer I
+const BITRATES_V2_L1: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+
+pub(super) fn is_frame_sync(data: &[u8]) -> bool {
+    data.len() >= 4 && parse_frame_header(&data[..4]).is_some()
+}
+
+struct FrameHeader {
+    /// 1 = MPEG-1, 2 = MPEG-2, 25 = MPEG-2.5
+    version: u8,
+    layer: u8,
+    bit_rate: u32,
+    sample_rate: u32,
+    padding: bool,
+    mono: bool,
+}
+
+impl FrameHeader {
+    fn samples_per_frame(&self) -> u32 {
+        match (self.layer, self.version) {
+            (1, _) => 384,
+            (3, 2) | (3, 25) => 576,
+            _ => 1152,
+        }
+    }
+
+    fn frame_length(&self) -> usize {
+        if self.layer == 1 {
+            ((12 * self.bit_rate / self.sample_rate + self.padding as u32) * 4) as usize
+        } else {
+            (self.samples_per_frame() / 8 * self.bit_rate / self.sample_rate + self.padding as u32) as usize
+        }
+    }
+
+    /// Xing / Info ヘッダーの位置（フレーム先頭からのオフセット）
+    fn xing_offset(&self) -> usize {
+        match (self.version == 1, self.mono) {
+            (true, false) => 36,
+            (true, true) => 21,
+            (false, false) => 21,
+            (false, true) => 13,
+        }
+    }
+}
+
+fn parse_frame_header(bytes: &[u8]) -> Option<FrameHeader> {
+    let header = super::be_u32(bytes, 0)?;
+    if header >> 21 != 0x7FF {
+        return None;
+    }
+    let version = match (header >> 19) & 0b11 {
+        0b00 => 25,
+        0b10 => 2,
+        0b11 => 1,
+        _ => return None,
+    };
+    let layer = match (header >> 17) & 0b11 {
+        0b01 => 3,
+        0b10 => 2,
+        0b11 => 1,
+        _ => return None,
+    };
+    let bitrate_index = ((header >> 12) & 0xF) as usize;
+    let sample_rate_index = ((header >> 10) & 0b11) as usize;
+    if bitrate_index == 0 || bitrate_index == 0xF || sample_rate_index == 3 {
+        return None;
+    }
+
+    let table = match (version, layer) {
+        (1, 1) => &BITRATES_V1_L1,
+        (1, 2) => &BITRATES_V1_L2,
+        (1, _) => &BITRATES_V1_L3,
+        (_, 1) => &BITRATES_V2_L1,
+        _ => &BITRATES_V2_L23,
+    };
+    let base_rates = [44100, 48000, 32000];
+    let sample_rate = match version {
+        1 => base_rates[sample_rate_index],
+        2 => base_rates[sample_rate_index] / 2,
+        _ => base_rates[sample_rate_index] / 4,
+    };
+
+    Some(FrameHeader {
+        version,
+        layer,
+        bit_rate: table[bitrate_index] * 1000,
+        sample_rate,
+        padding: (header >> 9) & 1 == 1,
+        mono: (header >> 6) & 0b11 == 0b11,
+    })
+}
+
+pub(super) fn read(file: &mut File, file_len: u64) -> Result<FileTags, String> {
+    let mut tags = Vec::new();
+
+    let head = read_at(file, file_len, 0, 10.min(file_len as usize))
+        .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+    let mut audio_start = 0u64;
+    if let Some(tag_len) = id3v2::total_size(&head) {
+        let tag = read_at(file, file_len, 0, tag_len as usize)
+            .map_err(|_| "ID3v2 タグが途中で切れています".to_string())?;
+        tags.push(id3v2::parse(&tag)?);
+        audio_start = tag_len;
+    }
+
+    let mut audio_end = file_len;
+    if file_len >= 128 {
+        if let Ok(tail) = read_at(file, file_len, file_len - 128, 128) {
+            if let Some(block) = id3v1::parse(&tail) {
+                tags.push(block);
+            }
+            if tail.starts_with(b"TAG") {
+                audio_end = file_len - 128;
+            }
+        }
+    }
+
+    let search_len = SYNC_SEARCH_BYTES.min(audio_end.saturating_sub(audio_start) as usize);
+    let window = read_at(file, file_len, audio_start, search_len).unwrap_or_default();
+    let properties = read_properties(&window, audio_end.saturating_sub(audio_start));
+
+    if properties.is_none() && tags.is_empty() {
+        return Err("MPEG オーディオのフレームが見つかりません".to_string());
+    }
+
+    Ok(FileTags {
+        properties: properties.unwrap_or_default(),
+        tags,
+    })
+}
+
+/// 先頭フレームと Xing / VBRI ヘッダーから再生時間とビットレートを求める
+fn read_properties(window: &[u8], audio_len: u64) -> Option<AudioProperties> {
+    // 連続する 2 フレームのヘッダーが正しいものを先頭フレームとみなす（誤検出を避ける）
+    let (offset, header) = (0..window.len().saturating_sub(4)).find_map(|i| {
+        let header = parse_frame_header(&window[i..])?;
+        let next = i + header.frame_length();
+        match window.get(next..next + 4) {
+            Some(bytes) if parse_frame_header(bytes).is_none() => None,
+            _ => Some((i, header)),
+        }
+    })?;
+
+    let frame = &window[offset..];
+    let xing_at = header.xing_offset() + 4;
+    let frame_count = match frame.get(xing_at..xing_at + 4) {
+        Some(b"Xing") | Some(b"Info") => {
+            let flags = super::be_u32(frame, xing_at + 4)?;
+            if flags & 1 != 0 {
+                super::be_u32(frame, xing_at + 8)
+            } else {
+                None
+            }
+        }
+        _ => match frame.get(36..40) {
+            Some(b"VBRI") => super::be_u32(frame, 36 + 14),
+            _ => None,
+        },
+    };
+
+    let data_len = audio_len.saturating_sub(offset as u64);
+    let (duration_seconds, bit_rate) = match frame_count.filter(|n| *n > 0) {
+        Some(frames) => {
+            let duration = frames as f64 * header.samples_per_frame() as f64 / header.sample_rate as f64;
+            (Some(duration), super::average_bit_rate(data_len, Some(duration)))
+        }
+        // CBR とみなす
+        None => (Some(data_len as f64 * 8.0 / header.bit_rate as f64), Some(header.bit_rate)),
+    };
+
+    let codec = match header.layer {
+        1 => "mp1",
+        2 => "mp2",
+        _ => "mp3",
+    };
+
+    Some(AudioProperties {
+        codec: Some(codec.to_string()),
+        sample_rate: Some(header.sample_rate),
+        channels: Some(if header.mono { 1 } else { 2 }),
+        duration_seconds,
+        bit_rate,
+    })
+}
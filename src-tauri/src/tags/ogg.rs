@@ -0,0 +1,445 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::{read_at, vorbis, AudioProperties, FileTags, TagBlock, TagFormat};
+
+/// 末尾から最終ページを探す範囲（Ogg のページは最大でも約 64KB）
+const TAIL_SEARCH_BYTES: u64 = 128 * 1024;
+/// ヘッダーパケットの上限（カバー画像入りのコメントでも十分な大きさ）
+const MAX_HEADER_PACKET_BYTES: usize = 64 * 1024 * 1024;
+
+struct Page {
+    header: [u8; 27],
+    serial: u32,
+    lacing: Vec<u8>,
+    body: Vec<u8>,
+}
+
+impl Page {
+    fn len(&self) -> u64 {
+        (self.header.len() + self.lacing.len() + self.body.len()) as u64
+    }
+}
+
+fn read_page<R: Read>(reader: &mut R) -> Option<Page> {
+    let mut header = [0u8; 27];
+    reader.read_exact(&mut header).ok()?;
+    if &header[0..4] != b"OggS" {
+        return None;
+    }
+    let serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+    let mut lacing = vec![0u8; header[26] as usize];
+    reader.read_exact(&mut lacing).ok()?;
+    let body_len: usize = lacing.iter().map(|&l| l as usize).sum();
+    let mut body = vec![0u8; body_len];
+    reader.read_exact(&mut body).ok()?;
+    Some(Page { header, serial, lacing, body })
+}
+
+/// 最初の論理ストリームの先頭 count 個のパケット（識別ヘッダー・コメントヘッダー）を取り出す
+fn read_header_packets(file: &mut File, count: usize) -> Option<(u32, Vec<Vec<u8>>)> {
+    file.seek(SeekFrom::Start(0)).ok()?;
+    let mut reader = BufReader::new(file);
+
+    let mut serial = None;
+    let mut packets: Vec<Vec<u8>> = Vec::new();
+    let mut current: Vec<u8> = Vec::new();
+    while packets.len() < count {
+        let page = read_page(&mut reader)?;
+        let stream_serial = *serial.get_or_insert(page.serial);
+        if page.serial != stream_serial {
+            continue;
+        }
+
+        let mut offset = 0usize;
+        for &len in &page.lacing {
+            current.extend_from_slice(&page.body[offset..offset + len as usize]);
+            offset += len as usize;
+            if current.len() > MAX_HEADER_PACKET_BYTES {
+                return None;
+            }
+            // 255 未満の lacing 値でパケットが終わる
+            if len < 255 {
+                packets.push(std::mem::take(&mut current));
+                if packets.len() == count {
+                    break;
+                }
+            }
+        }
+    }
+    Some((serial?, packets))
+}
+
+/// 最終ページのグラニュール位置（= 総サンプル数）を末尾から探す
+fn last_granule(file: &mut File, file_len: u64, serial: u32) -> Option<u64> {
+    let start = file_len.saturating_sub(TAIL_SEARCH_BYTES);
+    let tail = read_at(file, file_len, start, (file_len - start) as usize).ok()?;
+
+    (0..tail.len().saturating_sub(27)).rev().find_map(|i| {
+        if &tail[i..i + 4] != b"OggS" {
+            return None;
+        }
+        let page_serial = super::le_u32(&tail, i + 14)?;
+        let granule = i64::from_le_bytes(tail[i + 6..i + 14].try_into().ok()?);
+        if page_serial == serial && granule >= 0 {
+            Some(granule as u64)
+        } else {
+            None
+        }
+    })
+}
+
+/// Ogg Opus / Ogg Vorbis のコメントヘッダーとストリーム情報を読む
+pub(super) fn read(file: &mut File, file_len: u64) -> Result<FileTags, String> {
+    let (serial, packets) = read_header_packets(file, 2)
+        .ok_or_else(|| "Ogg のヘッダーを読み込めませんでした".to_string())?;
+    let (ident, comment) = (&packets[0], &packets[1]);
+
+    let mut properties = AudioProperties::default();
+    let comments;
+    let pre_skip;
+    if ident.starts_with(b"OpusHead") {
+        let comment = comment
+            .strip_prefix(b"OpusTags")
+            .ok_or_else(|| "OpusTags が見つかりません".to_string())?;
+        comments = vorbis::parse_comments(comment);
+        properties.codec = Some("opus".to_string());
+        properties.channels = ident.get(9).map(|&c| c as u16);
+        // Opus は常に 48kHz でデコードされる（ffprobe の表示も 48000）
+        properties.sample_rate = Some(48000);
+        pre_skip = super::le_u16(ident, 10).unwrap_or(0) as u64;
+    } else if ident.starts_with(b"\x01vorbis") {
+        let comment = comment
+            .strip_prefix(b"\x03vorbis")
+            .ok_or_else(|| "Vorbis のコメントヘッダーが見つかりません".to_string())?;
+        comments = vorbis::parse_comments(comment);
+        properties.codec = Some("vorbis".to_string());
+        properties.channels = ident.get(11).map(|&c| c as u16);
+        properties.sample_rate = super::le_u32(ident, 12);
+        pre_skip = 0;
+    } else {
+        return Err("対応していない Ogg ストリームです".to_string());
+    }
+
+    if let (Some(granule), Some(sample_rate)) = (last_granule(file, file_len, serial), properties.sample_rate) {
+        if sample_rate > 0 {
+            properties.duration_seconds = Some(granule.saturating_sub(pre_skip) as f64 / sample_rate as f64);
+        }
+    }
+    properties.bit_rate = super::average_bit_rate(file_len, properties.duration_seconds);
+
+    Ok(FileTags {
+        properties,
+        tags: comments.into_iter().collect(),
+    })
+}
+
+/// コメントヘッダーを差し替えて書き戻す。ヘッダーのページ数が変わった場合は後続ページの通し番号を振り直す
+pub(super) fn write(
+    mut file: File,
+    file_len: u64,
+    path: &Path,
+    update: impl FnOnce(&mut TagBlock),
+) -> Result<(), String> {
+    let headers = read_headers_for_write(&mut file)?;
+    let (ident_len, serial) = (headers.ident_len, headers.serial);
+    let mut packets = headers.packets;
+
+    let (prefix, suffix): (&[u8], &[u8]) = if headers.is_opus {
+        (b"OpusTags", b"")
+    } else {
+        // Vorbis のコメントヘッダーは末尾にフレーミングビットを持つ
+        (b"\x03vorbis", b"\x01")
+    };
+    let comment = packets[0]
+        .strip_prefix(prefix)
+        .ok_or_else(|| "Ogg のコメントヘッダーが見つかりません".to_string())?;
+    let vendor = vorbis::vendor(comment).unwrap_or_else(|| vorbis::DEFAULT_VENDOR.to_string());
+    let mut tag = vorbis::parse_comments(comment).unwrap_or_else(|| TagBlock::new(TagFormat::VorbisComment));
+    update(&mut tag);
+
+    let mut new_comment = prefix.to_vec();
+    new_comment.extend_from_slice(&vorbis::serialize_comments(&tag, &vendor, true));
+    new_comment.extend_from_slice(suffix);
+    packets[0] = new_comment;
+
+    let pages = paginate(serial, 1, &packets);
+    let sequence_delta = (pages.len() as u32).wrapping_sub(headers.page_count);
+    let headers_end = headers.end;
+    if headers_end > file_len {
+        return Err("Ogg のヘッダーが途中で切れています".to_string());
+    }
+
+    super::rewrite_file(path, file, |source, writer| {
+        // 識別ヘッダーのページはそのまま残す
+        super::copy_range(source, 0, Some(ident_len), writer)?;
+        for page in &pages {
+            writer.write_all(page)?;
+        }
+        if sequence_delta == 0 {
+            return super::copy_range(source, headers_end, None, writer);
+        }
+
+        source.seek(SeekFrom::Start(headers_end))?;
+        let mut offset = headers_end;
+        {
+            let mut reader = BufReader::new(&mut *source);
+            while let Some(page) = read_page(&mut reader) {
+                offset += page.len();
+                let mut bytes = Vec::with_capacity(page.len() as usize);
+                bytes.extend_from_slice(&page.header);
+                bytes.extend_from_slice(&page.lacing);
+                bytes.extend_from_slice(&page.body);
+                if page.serial == serial {
+                    let sequence = u32::from_le_bytes([bytes[18], bytes[19], bytes[20], bytes[21]]);
+                    bytes[18..22].copy_from_slice(&sequence.wrapping_add(sequence_delta).to_le_bytes());
+                    set_checksum(&mut bytes);
+                }
+                writer.write_all(&bytes)?;
+            }
+        }
+        // 末尾にページとして読めないデータがあってもそのまま残す
+        super::copy_range(source, offset, None, writer)
+    })
+}
+
+struct HeaderPackets {
+    serial: u32,
+    is_opus: bool,
+    /// 識別ヘッダーのページの長さ
+    ident_len: u64,
+    /// 識別ヘッダーより後のヘッダーパケット（コメント、Vorbis はセットアップも）
+    packets: Vec<Vec<u8>>,
+    /// packets が占めていたページ数
+    page_count: u32,
+    /// ヘッダーページの終わり（= 音声ページの開始位置）
+    end: u64,
+}
+
+fn read_headers_for_write(file: &mut File) -> Result<HeaderPackets, String> {
+    let broken = || "Ogg のヘッダーを読み込めませんでした".to_string();
+    file.seek(SeekFrom::Start(0)).map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+    let mut reader = BufReader::new(file);
+
+    let first = read_page(&mut reader).ok_or_else(broken)?;
+    let is_opus = first.body.starts_with(b"OpusHead");
+    if !is_opus && !first.body.starts_with(b"\x01vorbis") {
+        return Err("対応していない Ogg ストリームです".to_string());
+    }
+    // 識別ヘッダーは単独で最初のページを占める
+    if first.lacing.iter().filter(|&&len| len < 255).count() != 1 || !matches!(first.lacing.last(), Some(&len) if len < 255) {
+        return Err("Ogg の識別ヘッダーが不正です".to_string());
+    }
+    let count = if is_opus { 1 } else { 2 };
+
+    let mut headers = HeaderPackets {
+        serial: first.serial,
+        is_opus,
+        ident_len: first.len(),
+        packets: Vec::new(),
+        page_count: 0,
+        end: first.len(),
+    };
+    let mut current: Vec<u8> = Vec::new();
+    while headers.packets.len() < count {
+        let page = read_page(&mut reader).ok_or_else(broken)?;
+        if page.serial != headers.serial {
+            return Err("複数のストリームを含む Ogg ファイルへの書き込みには対応していません".to_string());
+        }
+        headers.page_count += 1;
+        headers.end += page.len();
+
+        let mut offset = 0usize;
+        for (index, &len) in page.lacing.iter().enumerate() {
+            current.extend_from_slice(&page.body[offset..offset + len as usize]);
+            offset += len as usize;
+            if current.len() > MAX_HEADER_PACKET_BYTES {
+                return Err(broken());
+            }
+            if len < 255 {
+                headers.packets.push(std::mem::take(&mut current));
+                // 音声パケットは新しいページから始まる決まりだが、念のため確認する
+                if headers.packets.len() == count && index + 1 != page.lacing.len() {
+                    return Err("Ogg のヘッダーページが不正です".to_string());
+                }
+            }
+        }
+    }
+    Ok(headers)
+}
+
+/// パケットをページに分割する。パケットが終わるページのグラニュール位置は 0、終わらないページは -1
+fn paginate(serial: u32, first_sequence: u32, packets: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut pages = Vec::new();
+    let mut lacing: Vec<u8> = Vec::new();
+    let mut body: Vec<u8> = Vec::new();
+    let mut continued = false;
+    let mut packet_ended = false;
+
+    let mut flush = |lacing: &mut Vec<u8>, body: &mut Vec<u8>, continued: bool, packet_ended: bool| {
+        let sequence = first_sequence + pages.len() as u32;
+        let granule: i64 = if packet_ended { 0 } else { -1 };
+        pages.push(build_page(serial, sequence, if continued { 0x01 } else { 0 }, granule, lacing, body));
+        lacing.clear();
+        body.clear();
+    };
+
+    for packet in packets {
+        let mut rest = packet.as_slice();
+        loop {
+            let len = rest.len().min(255);
+            lacing.push(len as u8);
+            body.extend_from_slice(&rest[..len]);
+            rest = &rest[len..];
+            // 255 の倍数の長さのパケットは 0 の lacing 値で終わる
+            let finished = len < 255;
+            packet_ended |= finished;
+            if lacing.len() == 255 {
+                flush(&mut lacing, &mut body, continued, packet_ended);
+                continued = !finished;
+                packet_ended = false;
+            }
+            if finished {
+                break;
+            }
+        }
+    }
+    if !lacing.is_empty() {
+        flush(&mut lacing, &mut body, continued, packet_ended);
+    }
+    pages
+}
+
+fn build_page(serial: u32, sequence: u32, flags: u8, granule: i64, lacing: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut page = Vec::with_capacity(27 + lacing.len() + body.len());
+    page.extend_from_slice(b"OggS");
+    page.push(0);
+    page.push(flags);
+    page.extend_from_slice(&granule.to_le_bytes());
+    page.extend_from_slice(&serial.to_le_bytes());
+    page.extend_from_slice(&sequence.to_le_bytes());
+    page.extend_from_slice(&[0; 4]);
+    page.push(lacing.len() as u8);
+    page.extend_from_slice(lacing);
+    page.extend_from_slice(body);
+    set_checksum(&mut page);
+    page
+}
+
+/// Ogg の CRC32（多項式 0x04C11DB7、ビット反転なし、初期値 0）
+const CRC_TABLE: [u32; 256] = build_crc_table();
+
+const fn build_crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04C1_1DB7 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// チェックサム欄を 0 にしたページ全体から CRC を計算して埋める
+fn set_checksum(page: &mut [u8]) {
+    page[22..26].fill(0);
+    let crc = page
+        .iter()
+        .fold(0u32, |crc, &b| (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ b) as usize]);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::tests::temp_file;
+    use crate::tags::{FrameValue, Picture, StandardField};
+
+    const SERIAL: u32 = 0x1234_5678;
+
+    /// OpusHead / OpusTags / 音声パケット 1 つの Ogg Opus（1 秒 = 48000 + pre-skip 312 サンプル）
+    fn opus_file(name: &str) -> std::path::PathBuf {
+        let mut head = b"OpusHead".to_vec();
+        head.extend_from_slice(&[1, 2]);
+        head.extend_from_slice(&312u16.to_le_bytes());
+        head.extend_from_slice(&48000u32.to_le_bytes());
+        head.extend_from_slice(&[0, 0, 0]);
+        let mut tags = b"OpusTags".to_vec();
+        let mut block = TagBlock::new(TagFormat::VorbisComment);
+        block.set_field(StandardField::Title, "before");
+        tags.extend_from_slice(&vorbis::serialize_comments(&block, "libopus 1.4", true));
+
+        let mut bytes = build_page(SERIAL, 0, 0x02, 0, &[head.len() as u8], &head);
+        bytes.extend(paginate(SERIAL, 1, &[tags]).concat());
+        bytes.extend(build_page(SERIAL, 2, 0x04, 48312, &[3], &[0xFC, 0xFF, 0xFE]));
+        temp_file(name, &bytes)
+    }
+
+    fn pages_of(path: &Path) -> Vec<Page> {
+        let bytes = std::fs::read(path).unwrap();
+        let mut reader = bytes.as_slice();
+        std::iter::from_fn(|| read_page(&mut reader)).collect()
+    }
+
+    fn checksum_ok(page: &Page) -> bool {
+        let mut bytes = [&page.header[..], &page.lacing, &page.body].concat();
+        let stored = bytes[22..26].to_vec();
+        set_checksum(&mut bytes);
+        bytes[22..26] == stored[..]
+    }
+
+    #[test]
+    fn comment_spanning_pages_renumbers_following_pages() {
+        let path = opus_file("ogg-span.opus");
+        assert_eq!(pages_of(&path).len(), 3);
+
+        // 1 ページの上限（255 * 255 バイト）を超える画像でコメントヘッダーを複数ページにする
+        let picture = Picture {
+            picture_type: 3,
+            mime: "image/png".to_string(),
+            description: String::new(),
+            data: vec![0x5A; 70_000],
+        };
+        crate::tags::write_file(&path, |block| {
+            block.set_values(StandardField::Artist, &["A".to_string(), "B".to_string()]);
+            block.add_picture(picture.clone());
+        })
+        .unwrap();
+
+        let pages = pages_of(&path);
+        assert!(pages.len() >= 4, "{} pages", pages.len());
+        for (sequence, page) in pages.iter().enumerate() {
+            assert!(checksum_ok(page), "CRC of page {}", sequence);
+            assert_eq!(u32::from_le_bytes(page.header[18..22].try_into().unwrap()), sequence as u32);
+            assert_eq!(page.serial, SERIAL);
+        }
+        // コメントの 2 ページ目以降は前のページからの続き、途中のページのグラニュール位置は -1
+        let comment_pages = &pages[1..pages.len() - 1];
+        assert!(comment_pages[1..].iter().all(|page| page.header[5] & 0x01 != 0));
+        assert_eq!(i64::from_le_bytes(comment_pages[0].header[6..14].try_into().unwrap()), -1);
+        let audio = pages.last().unwrap();
+        assert_eq!(audio.body, [0xFC, 0xFF, 0xFE]);
+        assert_eq!(audio.header[5], 0x04);
+
+        let tags = crate::tags::read_file(&path).unwrap();
+        assert_eq!(tags.values(StandardField::Artist), ["A", "B"]);
+        assert_eq!(tags.text(StandardField::Title).as_deref(), Some("before"));
+        assert_eq!(tags.pictures().collect::<Vec<_>>(), [&picture]);
+        assert_eq!(tags.properties.duration_seconds, Some(1.0));
+        assert_eq!(tags.tags[0].get("METADATA_BLOCK_PICTURE"), Some(&FrameValue::Picture(picture)));
+
+        // 小さくすると 1 ページに戻り、後続ページの通し番号も戻る
+        crate::tags::write_file(&path, |block| block.remove_pictures()).unwrap();
+        let pages = pages_of(&path);
+        assert_eq!(pages.len(), 3);
+        assert!(pages.iter().all(checksum_ok));
+        assert_eq!(u32::from_le_bytes(pages[2].header[18..22].try_into().unwrap()), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use std::fs::File;
+
+use super::{decode_utf8_or_latin1, id3v2, read_at, trim_nul, AudioProperties, FileTags, TagBlock, TagFormat};
+
+/// 読み込む LIST / id3 チャンクの上限
+const MAX_TAG_CHUNK_BYTES: u32 = 64 * 1024 * 1024;
+
+/// RIFF WAVE の fmt / data / LIST INFO / id3 チャンクを読む
+pub(super) fn read(file: &mut File, file_len: u64) -> Result<FileTags, String> {
+    let mut properties = AudioProperties::default();
+    let mut byte_rate = 0u32;
+    let mut data_len = None;
+    let mut info: Option<TagBlock> = None;
+    let mut id3: Option<TagBlock> = None;
+
+    let mut offset = 12u64;
+    while offset + 8 <= file_len {
+        let header = read_at(file, file_len, offset, 8)
+            .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+        let id: [u8; 4] = [header[0], header[1], header[2], header[3]];
+        let size = super::le_u32(&header, 4).unwrap_or(0);
+        let body_offset = offset + 8;
+
+        match &id {
+            b"fmt " => {
+                let fmt = read_at(file, file_len, body_offset, size.min(40) as usize)
+                    .map_err(|_| "fmt チャンクが途中で切れています".to_string())?;
+                byte_rate = super::le_u32(&fmt, 8).unwrap_or(0);
+                properties.channels = super::le_u16(&fmt, 2);
+                properties.sample_rate = super::le_u32(&fmt, 4);
+                properties.codec = pcm_codec_name(&fmt);
+            }
+            b"data" => {
+                // 書き込み途中のファイルなどでサイズが実際より大きい場合は末尾までとする
+                data_len = Some((size as u64).min(file_len.saturating_sub(body_offset)));
+            }
+            b"LIST" if size <= MAX_TAG_CHUNK_BYTES => {
+                if let Ok(list) = read_at(file, file_len, body_offset, size as usize) {
+                    if list.starts_with(b"INFO") {
+                        info = Some(parse_info(&list[4..]));
+                    }
+                }
+            }
+            b"id3 " | b"ID3 " if size <= MAX_TAG_CHUNK_BYTES => {
+                if let Ok(tag) = read_at(file, file_len, body_offset, size as usize) {
+                    id3 = id3v2::parse(&tag).ok();
+                }
+            }
+            _ => {}
+        }
+
+        // チャンクは 2 バイト境界に揃えられる
+        offset = body_offset + size as u64 + (size as u64 & 1);
+    }
+
+    if byte_rate > 0 {
+        if let Some(data_len) = data_len {
+            properties.duration_seconds = Some(data_len as f64 / byte_rate as f64);
+        }
+        properties.bit_rate = Some(byte_rate.saturating_mul(8));
+    }
+
+    // ffmpeg は INFO と id3 の両方を書くことがある。id3 の方が表現力があるため優先する
+    Ok(FileTags {
+        properties,
+        tags: id3.into_iter().chain(info).collect(),
+    })
+}
+
+fn parse_info(data: &[u8]) -> TagBlock {
+    let mut block = TagBlock::new(TagFormat::RiffInfo);
+    let mut offset = 0usize;
+    while offset + 8 <= data.len() {
+        let id = String::from_utf8_lossy(&data[offset..offset + 4]).into_owned();
+        let size = super::le_u32(data, offset + 4).unwrap_or(0) as usize;
+        let start = offset + 8;
+        let Some(value) = data.get(start..start + size) else { break };
+        let value = trim_nul(&decode_utf8_or_latin1(value));
+        if !value.trim().is_empty() {
+            block.push_text(&id, value);
+        }
+        offset = start + size + (size & 1);
+    }
+    block
+}
+
+/// fmt チャンクから ffprobe と同じ表記のコーデック名を求める
+fn pcm_codec_name(fmt: &[u8]) -> Option<String> {
+    let mut format_tag = super::le_u16(fmt, 0)?;
+    let bits = super::le_u16(fmt, 14)?;
+    if format_tag == 0xFFFE {
+        // WAVE_FORMAT_EXTENSIBLE: SubFormat GUID の先頭 2 バイトが実際の形式
+        format_tag = super::le_u16(fmt, 24).unwrap_or(1);
+    }
+
+    let name = match (format_tag, bits) {
+        (1, 8) => "pcm_u8".to_string(),
+        (1, bits) => format!("pcm_s{}le", bits),
+        (3, bits) => format!("pcm_f{}le", bits),
+        (6, _) => "pcm_alaw".to_string(),
+        (7, _) => "pcm_mulaw".to_string(),
+        (0x55, _) => "mp3".to_string(),
+        _ => return None,
+    };
+    Some(name)
+}
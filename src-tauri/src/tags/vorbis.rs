@@ -0,0 +1,185 @@
+use base64::prelude::*;
+
+use super::{decode_utf8_or_latin1, FrameValue, Picture, TagBlock, TagFormat};
+
+/// Vorbis comment 本体（vendor 文字列 + コメント一覧）を解析する。
+/// キーは大文字に揃え、同名のキーは1つのフレームの複数値にまとめる
+pub(super) fn parse_comments(data: &[u8]) -> Option<TagBlock> {
+    let mut offset = 0usize;
+    let read_u32 = |offset: &mut usize| -> Option<usize> {
+        let value = super::le_u32(data, *offset)? as usize;
+        *offset += 4;
+        Some(value)
+    };
+
+    let vendor_len = read_u32(&mut offset)?;
+    offset = offset.checked_add(vendor_len)?;
+    let count = read_u32(&mut offset)?;
+
+    let mut block = TagBlock::new(TagFormat::VorbisComment);
+    for _ in 0..count {
+        let len = read_u32(&mut offset)?;
+        let entry = data.get(offset..offset.checked_add(len)?)?;
+        offset += len;
+
+        let Some(eq) = entry.iter().position(|&b| b == b'=') else { continue };
+        let key = String::from_utf8_lossy(&entry[..eq]).to_ascii_uppercase();
+        let value = &entry[eq + 1..];
+
+        if key == "METADATA_BLOCK_PICTURE" {
+            if let Some(picture) = std::str::from_utf8(value).ok().and_then(decode_block_picture) {
+                block.push(&key, FrameValue::Picture(picture));
+            }
+            continue;
+        }
+        block.push_text(&key, decode_utf8_or_latin1(value));
+    }
+
+    Some(block)
+}
+
+/// FLAC の PICTURE ブロック本体を解析する
+pub(super) fn parse_picture_block(block: &[u8]) -> Option<Picture> {
+    let mut offset = 0usize;
+    let read_u32 = |offset: &mut usize| -> Option<usize> {
+        let value = super::be_u32(block, *offset)? as usize;
+        *offset += 4;
+        Some(value)
+    };
+
+    let picture_type = read_u32(&mut offset)?;
+    let mime_len = read_u32(&mut offset)?;
+    let mime = block.get(offset..offset.checked_add(mime_len)?)?;
+    offset += mime_len;
+    let description_len = read_u32(&mut offset)?;
+    let description = block.get(offset..offset.checked_add(description_len)?)?;
+    offset += description_len;
+    // width / height / depth / colors
+    offset = offset.checked_add(16)?;
+    let data_len = read_u32(&mut offset)?;
+    let data = block.get(offset..offset.checked_add(data_len)?)?;
+    if data.is_empty() {
+        return None;
+    }
+
+    let mime = String::from_utf8_lossy(mime).to_ascii_lowercase();
+    Some(Picture {
+        picture_type: picture_type.min(u8::MAX as usize) as u8,
+        mime: if mime.contains('/') { mime } else { crate::utils::sniff_image(data).mime.to_string() },
+        description: String::from_utf8_lossy(description).into_owned(),
+        data: data.to_vec(),
+    })
+}
+
+/// base64 の METADATA_BLOCK_PICTURE（FLAC PICTURE ブロックと同一構造）をデコードする
+pub fn decode_block_picture(value: &str) -> Option<Picture> {
+    let block = BASE64_STANDARD.decode(value.trim()).ok()?;
+    parse_picture_block(&block)
+}
+
+/// 新しく作る Vorbis comment の vendor 文字列
+pub(super) const DEFAULT_VENDOR: &str = "VoiceTagEditor";
+
+/// 既存の Vorbis comment から vendor 文字列を取り出す（書き戻し時にエンコーダー情報を保つため）
+pub(super) fn vendor(data: &[u8]) -> Option<String> {
+    let len = super::le_u32(data, 0)? as usize;
+    let vendor = data.get(4..4usize.checked_add(len)?)?;
+    Some(String::from_utf8_lossy(vendor).into_owned())
+}
+
+/// Vorbis comment 本体を組み立てる。embed_pictures が true なら画像を METADATA_BLOCK_PICTURE として含める（Ogg 用）
+pub(super) fn serialize_comments(block: &TagBlock, vendor: &str, embed_pictures: bool) -> Vec<u8> {
+    let mut entries: Vec<Vec<u8>> = Vec::new();
+    for frame in &block.frames {
+        let mut push = |value: &str| entries.push(format!("{}={}", frame.id, value).into_bytes());
+        match &frame.value {
+            FrameValue::Text(values) => values.iter().for_each(|v| push(v)),
+            FrameValue::Pair(number, total) if *total > 0 => push(&format!("{}/{}", number, total)),
+            FrameValue::Pair(number, _) => push(&number.to_string()),
+            FrameValue::Integer(n) => push(&n.to_string()),
+            FrameValue::Picture(picture) if embed_pictures => {
+                let encoded = BASE64_STANDARD.encode(serialize_picture(picture));
+                entries.push(format!("METADATA_BLOCK_PICTURE={}", encoded).into_bytes());
+            }
+            FrameValue::Picture(_) | FrameValue::Binary(_) | FrameValue::Undecoded { .. } => {}
+        }
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    out.extend_from_slice(vendor.as_bytes());
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for entry in entries {
+        out.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        out.extend_from_slice(&entry);
+    }
+    out
+}
+
+/// FLAC の PICTURE ブロック本体（METADATA_BLOCK_PICTURE と同じ構造）を組み立てる
+pub(super) fn serialize_picture(picture: &Picture) -> Vec<u8> {
+    let info = crate::utils::sniff_image(&picture.data);
+    let mut out = Vec::with_capacity(32 + picture.mime.len() + picture.description.len() + picture.data.len());
+    out.extend_from_slice(&(picture.picture_type as u32).to_be_bytes());
+    out.extend_from_slice(&(picture.mime.len() as u32).to_be_bytes());
+    out.extend_from_slice(picture.mime.as_bytes());
+    out.extend_from_slice(&(picture.description.len() as u32).to_be_bytes());
+    out.extend_from_slice(picture.description.as_bytes());
+    out.extend_from_slice(&info.width.unwrap_or(0).to_be_bytes());
+    out.extend_from_slice(&info.height.unwrap_or(0).to_be_bytes());
+    // 色深度がヘッダーから読めなければ一般的な値にする
+    out.extend_from_slice(&info.depth.unwrap_or(24).to_be_bytes());
+    out.extend_from_slice(&info.colors.unwrap_or(0).to_be_bytes());
+    out.extend_from_slice(&(picture.data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&picture.data);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::StandardField;
+
+    #[test]
+    fn comments_round_trip_with_multi_values_and_picture() {
+        let mut block = TagBlock::new(TagFormat::VorbisComment);
+        block.push_text("TITLE", "タイトル".to_string());
+        block.push_text("ARTIST", "A".to_string());
+        block.push_text("ARTIST", "B".to_string());
+        let picture = Picture {
+            picture_type: 3,
+            mime: "image/png".to_string(),
+            description: String::new(),
+            data: vec![1, 2, 3, 4],
+        };
+        block.push("METADATA_BLOCK_PICTURE", FrameValue::Picture(picture.clone()));
+
+        let data = serialize_comments(&block, "encoder 1.0", true);
+        assert_eq!(vendor(&data).as_deref(), Some("encoder 1.0"));
+        let parsed = parse_comments(&data).unwrap();
+        assert_eq!(parsed.get("TITLE"), Some(&FrameValue::Text(vec!["タイトル".to_string()])));
+        assert_eq!(parsed.get("ARTIST"), Some(&FrameValue::Text(vec!["A".to_string(), "B".to_string()])));
+        assert_eq!(parsed.get("METADATA_BLOCK_PICTURE"), Some(&FrameValue::Picture(picture)));
+
+        // FLAC 用（画像は PICTURE ブロックに書く）では画像を含めない
+        let without_pictures = parse_comments(&serialize_comments(&block, "encoder 1.0", false)).unwrap();
+        assert!(without_pictures.get("METADATA_BLOCK_PICTURE").is_none());
+    }
+
+    #[test]
+    fn voice_actors_are_separate_comments() {
+        let mut block = TagBlock::new(TagFormat::VorbisComment);
+        block.push_text("CV", "旧".to_string());
+        block.set_values(StandardField::VoiceActor, &["声優A".to_string(), "声優B".to_string()]);
+
+        let data = serialize_comments(&block, "encoder 1.0", false);
+        let text = String::from_utf8_lossy(&data);
+        assert!(text.contains("VOICE_ACTOR=声優A") && text.contains("VOICE_ACTOR=声優B"));
+        assert!(!text.contains("CV="));
+        let tags = crate::tags::FileTags {
+            properties: Default::default(),
+            tags: vec![parse_comments(&data).unwrap()],
+        };
+        assert_eq!(tags.values(StandardField::VoiceActor), ["声優A", "声優B"]);
+    }
+}
@@ -0,0 +1,116 @@
+//! 画像一覧用のサムネイル（縮小した JPEG をキャッシュに保存して使い回す）
+
+use futures::{stream, StreamExt};
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path, time::UNIX_EPOCH};
+
+use crate::models::ImageThumbnail;
+use crate::path_utils::to_extended_length_path_if_needed;
+
+/// 未指定のときのサムネイルの最大の幅・高さ（px）
+const DEFAULT_MAX_SIZE: u32 = 256;
+/// サムネイルの JPEG の画質（FFmpeg の -q:v）
+const THUMBNAIL_QUALITY: u32 = 5;
+/// 寸法が収まりこれより小さい画像はサムネイルを作らず、元の画像をそのまま使う
+const PASSTHROUGH_BYTES: u64 = 256 * 1024;
+
+/// 画像のサムネイルを作る（キャッシュにあればそれを使う）。結果は file_paths と同じ順に返す
+#[tauri::command]
+pub async fn get_image_thumbnails(
+    file_paths: Vec<String>,
+    max_size: Option<u32>,
+) -> Result<Vec<ImageThumbnail>, String> {
+    let max_size = max_size.unwrap_or(DEFAULT_MAX_SIZE).clamp(32, 1024);
+
+    // 同時実行数: CPUコア数をベースに最大4に制限（ffmpeg を起動するため）
+    let default_concurrency = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .clamp(1, 4);
+    let max_concurrency = std::env::var("VTE_THUMBNAIL_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .map(|v| v.clamp(1, 16))
+        .unwrap_or(default_concurrency);
+
+    let thumbnails = stream::iter(file_paths)
+        .map(|file_path| async move { thumbnail(file_path, max_size).await })
+        .buffered(max_concurrency)
+        .collect()
+        .await;
+    Ok(thumbnails)
+}
+
+async fn thumbnail(file_path: String, max_size: u32) -> ImageThumbnail {
+    let mut result = ImageThumbnail {
+        path: file_path.clone(),
+        thumbnail_path: None,
+        mime: None,
+        width: None,
+        height: None,
+        size: 0,
+        error: None,
+    };
+
+    let source = Path::new(&file_path);
+    let meta = match fs::metadata(to_extended_length_path_if_needed(source)) {
+        Ok(meta) => meta,
+        Err(e) => {
+            result.error = Some(format!("画像の読み込みに失敗しました: {}", e));
+            return result;
+        }
+    };
+    result.size = meta.len();
+
+    let info = match crate::utils::read_image_info(source) {
+        Ok(info) => info,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+    result.mime = Some(info.mime.to_string());
+    result.width = info.width;
+    result.height = info.height;
+
+    let fits = info
+        .width
+        .zip(info.height)
+        .map(|(width, height)| width <= max_size && height <= max_size)
+        .unwrap_or(false);
+    if fits && meta.len() <= PASSTHROUGH_BYTES {
+        result.thumbnail_path = Some(file_path);
+        return result;
+    }
+
+    let key = thumbnail_key(source, &meta, max_size);
+    if let Some(cached) = crate::cache::cached_thumbnail(&key) {
+        result.thumbnail_path = Some(cached);
+        return result;
+    }
+
+    let stored = crate::artwork::encode_jpeg(source, max_size, THUMBNAIL_QUALITY)
+        .await
+        .and_then(|jpeg| crate::cache::store_thumbnail(&key, &jpeg));
+    match stored {
+        Ok(path) => result.thumbnail_path = Some(path),
+        Err(e) => result.error = Some(e),
+    }
+    result
+}
+
+/// キャッシュのキー。パス・更新日時・サイズ・サムネイルの大きさから決めるため、元の画像が変われば作り直す
+/// （古いサムネイルはキャッシュの追い出しに任せる）
+fn thumbnail_key(path: &Path, meta: &fs::Metadata, max_size: u32) -> String {
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let source = format!("{}\n{}\n{}\n{}", path.to_string_lossy(), modified, meta.len(), max_size);
+    Sha256::digest(source.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
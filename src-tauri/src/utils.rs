@@ -1,11 +1,501 @@
-pub fn sanitize_filename(name: &str) -> String {
+/// ファイル名の制約を合わせる対象のファイルシステム
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilenameTarget {
+    /// NTFS / exFAT: 予約名（CON, NUL, COM1…）と末尾のドット・空白が使えず、長さは UTF-16 で 255 単位まで
+    Windows,
+    /// Linux / macOS: 長さは UTF-8 で 255 バイトまで
+    Posix,
+    /// どちらでも使える名前（両方の制約を満たす）
+    Portable,
+}
+
+const MAX_FILENAME_BYTES: usize = 255;
+const MAX_FILENAME_UTF16_UNITS: usize = 255;
+
+impl FilenameTarget {
+    /// 実行中のOSに合わせた既定値
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            FilenameTarget::Windows
+        } else {
+            FilenameTarget::Posix
+        }
+    }
+
+    /// 設定値（"windows" / "posix" / "portable" / "auto"）から決める。未指定・不明なら current()
+    pub fn from_setting(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            Some("windows") => FilenameTarget::Windows,
+            Some("posix") | Some("linux") | Some("macos") => FilenameTarget::Posix,
+            Some("portable") => FilenameTarget::Portable,
+            _ => FilenameTarget::current(),
+        }
+    }
+
+    fn windows_rules(self) -> bool {
+        matches!(self, FilenameTarget::Windows | FilenameTarget::Portable)
+    }
+
+    fn fits(self, name: &str) -> bool {
+        let bytes_ok = name.len() <= MAX_FILENAME_BYTES;
+        let utf16_ok = name.encode_utf16().count() <= MAX_FILENAME_UTF16_UNITS;
+        match self {
+            FilenameTarget::Windows => utf16_ok,
+            FilenameTarget::Posix => bytes_ok,
+            FilenameTarget::Portable => bytes_ok && utf16_ok,
+        }
+    }
+}
+
+/// パス区切りやファイル名に使えない文字、制御文字を `_` に置き換える（タグの値をファイル名に埋め込むとき用）
+pub fn replace_invalid_chars(name: &str) -> String {
     name
         .chars()
         .map(|c| match c {
             '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
             _ => c,
         })
         .collect::<String>()
         .trim()
         .to_string()
 }
+
+fn is_windows_reserved_name(name: &str) -> bool {
+    // "CON.txt" のように拡張子が付いていても予約名として扱われる
+    let base = name.split('.').next().unwrap_or("").trim_end().to_ascii_uppercase();
+    match base.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" | "CONIN$" | "CONOUT$" => true,
+        _ => {
+            let Some(digit) = base.strip_prefix("COM").or_else(|| base.strip_prefix("LPT")) else {
+                return false;
+            };
+            matches!(digit, "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9")
+        }
+    }
+}
+
+/// 予約名の直後（最初のドットの前）に `_` を入れて予約名でなくする（"CON.txt" → "CON_.txt"）
+fn escape_windows_reserved_name(name: &str) -> String {
+    if !is_windows_reserved_name(name) {
+        return name.to_string();
+    }
+    match name.split_once('.') {
+        Some((base, rest)) => format!("{}_.{}", base, rest),
+        None => format!("{}_", name),
+    }
+}
+
+/// 文字境界を保ったまま、target の長さ制限に収まるよう末尾を切り詰める
+fn truncate_to_fit(name: &str, reserved: &str, target: FilenameTarget) -> String {
+    // 1文字は1バイト・1単位以上なので、上限を超える文字数は最初から不要
+    let mut result: String = name.chars().take(MAX_FILENAME_UTF16_UNITS).collect();
+    while !result.is_empty() && !target.fits(&format!("{}{}", result, reserved)) {
+        result.pop();
+    }
+    result
+}
+
+/// パスの1要素（ディレクトリ名・ファイル名）として安全な名前にする
+/// - 使用できない文字・制御文字の置換
+/// - `.` / `..` のような要素の無効化
+/// - Windows の予約名・末尾のドットと空白の回避（target が Windows / Portable のとき）
+/// - ファイル名長の上限に合わせた切り詰め
+pub fn sanitize_path_component(name: &str, target: FilenameTarget) -> String {
+    let mut result = replace_invalid_chars(name);
+
+    if target.windows_rules() {
+        result = escape_windows_reserved_name(result.trim_end_matches(['.', ' ']));
+    }
+    result = truncate_to_fit(&result, "", target);
+    if target.windows_rules() {
+        // 切り詰めで末尾にドット・空白が出てくることがある
+        result = result.trim_end_matches(['.', ' ']).to_string();
+    }
+
+    if result.is_empty() || result.chars().all(|c| c == '.') {
+        return "_".to_string();
+    }
+    result
+}
+
+/// 拡張子と末尾の付加文字列（連番など）を残したまま、stem を切り詰めてファイル名を組み立てる
+/// stem は先頭から残るため、"01-03 タイトル" のようなトラック番号の接頭辞は保たれる
+pub fn fit_file_name(stem: &str, suffix: &str, extension: &str, target: FilenameTarget) -> String {
+    let tail = if extension.is_empty() {
+        suffix.to_string()
+    } else {
+        format!("{}.{}", suffix, extension)
+    };
+
+    let mut stem = replace_invalid_chars(stem);
+    if target.windows_rules() {
+        stem = escape_windows_reserved_name(&stem);
+    }
+    stem = truncate_to_fit(&stem, &tail, target);
+    if target.windows_rules() && tail.is_empty() {
+        // 拡張子も付加文字列も無い場合は stem の末尾がそのままファイル名の末尾になる
+        stem = stem.trim_end_matches(['.', ' ']).to_string();
+    }
+    if stem.trim().is_empty() {
+        stem = "_".to_string();
+    }
+
+    format!("{}{}", stem, tail)
+}
+
+/// 商品 ID を書き込むタグのキー（ID3v2 は TXXX の説明、MP4 は com.apple.iTunes のフリーフォーム名）
+pub const PRODUCT_ID_TAG: &str = "PRODUCT_ID";
+
+/// 商品 ID の接頭辞（RJ: 同人音声、VJ: 商業 PC ゲーム、BJ: 同人誌・書籍）
+const PRODUCT_ID_PREFIXES: [&str; 3] = ["RJ", "VJ", "BJ"];
+
+/// パスから商品 ID（RJ01234567 など）を探す。ファイルに近い要素（ファイル名 → 親フォルダ → …）を優先し、
+/// 数字は 6 桁または 8 桁。前後が英数字に続く場合（"XRJ123456" や "RJ1234567"）は ID とみなさない
+pub fn detect_product_id(path: &str) -> Option<String> {
+    path.rsplit(['/', '\\']).find_map(find_product_id)
+}
+
+fn find_product_id(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    (0..bytes.len().saturating_sub(2)).find_map(|start| {
+        let prefix = text.get(start..start + 2)?.to_ascii_uppercase();
+        if !PRODUCT_ID_PREFIXES.contains(&prefix.as_str()) {
+            return None;
+        }
+        if start > 0 && bytes[start - 1].is_ascii_alphanumeric() {
+            return None;
+        }
+        let digits = bytes[start + 2..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits != 6 && digits != 8 {
+            return None;
+        }
+        // "RJ123456a" のように英字が続くものは別の語の一部
+        if bytes.get(start + 2 + digits).is_some_and(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(format!("{}{}", prefix, &text[start + 2..start + 2 + digits]))
+    })
+}
+
+/// 全角数字を含む番号を数値にする（"03" → 3）。数字以外を含む値は None
+pub fn parse_number(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let digits: Option<String> = text
+        .chars()
+        .map(|c| match c {
+            '0'..='9' => Some(c),
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32),
+            _ => None,
+        })
+        .collect();
+    digits?.parse().ok()
+}
+
+/// トラック番号・ディスク番号を (番号, 総数) に分ける。"3/12"、"03"、"３／１２"、"3 of 12" を受け付ける
+pub fn parse_number_pair(text: &str) -> (Option<u32>, Option<u32>) {
+    let text = text.trim();
+    let lower = text.to_ascii_lowercase();
+    let (number, total) = match text.split_once(['/', '／']) {
+        Some(pair) => pair,
+        None => match lower.find(" of ") {
+            Some(index) => (&text[..index], &text[index + 4..]),
+            None => (text, ""),
+        },
+    };
+    (parse_number(number), parse_number(total).filter(|&total| total > 0))
+}
+
+/// 番号に総数を付けた "3/12"（ID3v2 の TRCK / TPOS、FFmpeg の track / disc の書き方）。総数が無ければ番号だけ
+pub fn format_number_pair(number: &str, total: Option<u32>) -> String {
+    let number = number.trim();
+    match total.filter(|&total| total > 0) {
+        Some(total) if !number.is_empty() => format!("{}/{}", number, total),
+        _ => number.to_string(),
+    }
+}
+
+/// Vorbis コメント用の METADATA_BLOCK_PICTURE（FLAC PICTURE ブロック構造）を組み立て、base64 文字列で返す
+pub fn build_metadata_block_picture(image_bytes: &[u8], mime: &str) -> String {
+    use base64::prelude::*;
+
+    // picture type = 3 (Cover front)
+    let info = sniff_image(image_bytes);
+    let mime_bytes = mime.as_bytes();
+    let description: &[u8] = b"";
+    let width: u32 = info.width.unwrap_or(0);
+    let height: u32 = info.height.unwrap_or(0);
+    let depth: u32 = info.depth.unwrap_or(24); // bits-per-pixel（ヘッダーから読めなければ一般的な値）
+    let colors: u32 = info.colors.unwrap_or(0); // indexed palette colors (0 for non-indexed)
+
+    let mut block: Vec<u8> = Vec::new();
+    block.extend_from_slice(&3u32.to_be_bytes());
+    block.extend_from_slice(&(mime_bytes.len() as u32).to_be_bytes());
+    block.extend_from_slice(mime_bytes);
+    block.extend_from_slice(&(description.len() as u32).to_be_bytes());
+    block.extend_from_slice(description);
+    block.extend_from_slice(&width.to_be_bytes());
+    block.extend_from_slice(&height.to_be_bytes());
+    block.extend_from_slice(&depth.to_be_bytes());
+    block.extend_from_slice(&colors.to_be_bytes());
+    block.extend_from_slice(&(image_bytes.len() as u32).to_be_bytes());
+    block.extend_from_slice(image_bytes);
+
+    BASE64_STANDARD.encode(&block)
+}
+
+/// 形式を判別できなかった画像の MIME
+pub const UNKNOWN_IMAGE_MIME: &str = "application/octet-stream";
+
+/// 画像のマジックナンバーとヘッダーから判定した形式と寸法
+#[derive(Debug, Clone, Copy)]
+pub struct ImageInfo {
+    pub mime: &'static str,
+    pub extension: &'static str,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// 1 ピクセルのビット数（FLAC PICTURE の color depth）
+    pub depth: Option<u32>,
+    /// パレットの色数（パレット形式の PNG / GIF のみ）
+    pub colors: Option<u32>,
+}
+
+/// 寸法の判定に読む先頭のバイト数（JPEG は Exif の後に SOF があるため多めに読む）
+const IMAGE_HEADER_BYTES: u64 = 256 * 1024;
+
+/// 画像の形式と寸法を判定する。先頭だけ読み、寸法が分からなければ全体を読む
+pub fn read_image_info(path: &std::path::Path) -> Result<ImageInfo, String> {
+    use std::io::Read;
+
+    let ep = crate::path_utils::to_extended_length_path_if_needed(path);
+    let mut header = Vec::new();
+    std::fs::File::open(&ep)
+        .and_then(|file| file.take(IMAGE_HEADER_BYTES).read_to_end(&mut header))
+        .map_err(|e| format!("画像の読み込みに失敗しました: {}", e))?;
+
+    let info = sniff_image(&header);
+    if info.width.is_some() || (header.len() as u64) < IMAGE_HEADER_BYTES {
+        return Ok(info);
+    }
+    let data = std::fs::read(&ep).map_err(|e| format!("画像の読み込みに失敗しました: {}", e))?;
+    Ok(sniff_image(&data))
+}
+
+/// 画像データの形式・寸法・色深度をヘッダーから判定する（デコードはしない）。不明な形式は JPEG とみなす
+pub fn sniff_image(bytes: &[u8]) -> ImageInfo {
+    let be_u16 = |at: usize| bytes.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as u32);
+    let le_u16 = |at: usize| bytes.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as u32);
+    let be_u32 = |at: usize| bytes.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let le_u32 = |at: usize| bytes.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let le_u24 = |at: usize| bytes.get(at..at + 3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]));
+
+    let info = |mime, extension, size: Option<(u32, u32)>, depth: Option<u32>| ImageInfo {
+        mime,
+        extension,
+        width: size.map(|(w, _)| w),
+        height: size.map(|(_, h)| h),
+        depth,
+        colors: None,
+    };
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // IHDR チャンクの先頭に幅・高さ、続いてビット深度と色の種類がある
+        let size = be_u32(16).zip(be_u32(20));
+        let bit_depth = bytes.get(24).map(|&b| b as u32);
+        let (channels, indexed) = match bytes.get(25) {
+            Some(0) => (1, false),
+            Some(2) => (3, false),
+            Some(3) => (1, true),
+            Some(4) => (2, false),
+            Some(6) => (4, false),
+            _ => (0, false),
+        };
+        let depth = bit_depth.filter(|_| channels > 0).map(|bits| bits * channels);
+        return ImageInfo {
+            colors: bit_depth.filter(|_| indexed).map(|bits| 1 << bits),
+            ..info("image/png", "png", size, depth)
+        };
+    }
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        // グローバルカラーテーブルの大きさ（2^(n+1) 色）
+        let bits = bytes.get(10).map(|&packed| (packed & 0x07) as u32 + 1);
+        return ImageInfo {
+            colors: bits.map(|bits| 1 << bits),
+            ..info("image/gif", "gif", le_u16(6).zip(le_u16(8)), bits)
+        };
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        let (size, alpha) = match bytes.get(12..16) {
+            Some(b"VP8 ") => (le_u16(26).zip(le_u16(28)).map(|(w, h)| (w & 0x3fff, h & 0x3fff)), Some(false)),
+            Some(b"VP8L") => (
+                le_u32(21).map(|bits| ((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1)),
+                le_u32(21).map(|bits| bits & (1 << 28) != 0),
+            ),
+            Some(b"VP8X") => (
+                le_u24(24).zip(le_u24(27)).map(|(w, h)| (w + 1, h + 1)),
+                bytes.get(20).map(|flags| flags & 0x10 != 0),
+            ),
+            _ => (None, None),
+        };
+        let depth = alpha.map(|alpha| if alpha { 32 } else { 24 });
+        return info("image/webp", "webp", size, depth);
+    }
+    if bytes.starts_with(b"BM") {
+        let size = le_u32(18).zip(le_u32(22)).map(|(w, h)| (w, (h as i32).unsigned_abs()));
+        return info("image/bmp", "bmp", size, le_u16(28));
+    }
+
+    // どの形式にも当てはまらないものを JPEG として扱うと、埋め込み先に誤った MIME を書くことになる
+    if !bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return info(UNKNOWN_IMAGE_MIME, "bin", None, None);
+    }
+
+    // JPEG: SOFn マーカーまでセグメントを読み飛ばす（精度 × 成分数が色深度）
+    let mut size = None;
+    let mut depth = None;
+    let mut offset = 2usize;
+    while let (Some(&0xFF), Some(&marker)) = (bytes.get(offset), bytes.get(offset + 1)) {
+        if marker == 0xFF {
+            offset += 1;
+            continue;
+        }
+        let Some(length) = be_u16(offset + 2) else { break };
+        let is_sof = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_sof {
+            size = be_u16(offset + 7).zip(be_u16(offset + 5));
+            depth = bytes
+                .get(offset + 4)
+                .zip(bytes.get(offset + 9))
+                .map(|(&precision, &components)| precision as u32 * components as u32);
+            break;
+        }
+        offset += 2 + length as usize;
+    }
+    info("image/jpeg", "jpg", size, depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_names_are_escaped_for_windows() {
+        for target in [FilenameTarget::Windows, FilenameTarget::Portable] {
+            assert_eq!(sanitize_path_component("CON", target), "CON_");
+            assert_eq!(sanitize_path_component("con.txt", target), "con_.txt");
+            assert_eq!(sanitize_path_component("COM1 ", target), "COM1_");
+            assert_eq!(sanitize_path_component("LPT9.tar.gz", target), "LPT9_.tar.gz");
+            assert_eq!(fit_file_name("nul", "", "mp3", target), "nul_.mp3");
+        }
+        assert_eq!(sanitize_path_component("COM0", FilenameTarget::Windows), "COM0");
+        assert_eq!(sanitize_path_component("CONSOLE", FilenameTarget::Windows), "CONSOLE");
+        assert_eq!(sanitize_path_component("CON", FilenameTarget::Posix), "CON");
+    }
+
+    #[test]
+    fn trailing_dots_and_spaces_and_dot_components() {
+        assert_eq!(sanitize_path_component("Album... ", FilenameTarget::Windows), "Album");
+        assert_eq!(sanitize_path_component("Album...", FilenameTarget::Posix), "Album...");
+        for target in [FilenameTarget::Windows, FilenameTarget::Posix, FilenameTarget::Portable] {
+            assert_eq!(sanitize_path_component(".", target), "_");
+            assert_eq!(sanitize_path_component("..", target), "_");
+            assert_eq!(sanitize_path_component("", target), "_");
+            assert_eq!(sanitize_path_component("a/b\\c:d", target), "a_b_c_d");
+        }
+        // 拡張子が続く場合は stem の末尾のドットは問題にならない
+        assert_eq!(fit_file_name("Vol. 1.", "", "mp3", FilenameTarget::Windows), "Vol. 1..mp3");
+        assert_eq!(fit_file_name("Vol. 1. ", "", "", FilenameTarget::Windows), "Vol. 1");
+        assert_eq!(fit_file_name(" ", "", "flac", FilenameTarget::Posix), "_.flac");
+    }
+
+    #[test]
+    fn long_names_are_cut_by_bytes_or_utf16_units() {
+        // "あ" は UTF-8 で 3 バイト、UTF-16 で 1 単位
+        let kana = "あ".repeat(100);
+        assert_eq!(sanitize_path_component(&kana, FilenameTarget::Posix).chars().count(), 85);
+        assert_eq!(sanitize_path_component(&kana, FilenameTarget::Portable).chars().count(), 85);
+        assert_eq!(sanitize_path_component(&kana, FilenameTarget::Windows), kana);
+
+        // 絵文字は 4 バイト・2 単位。サロゲートペアの途中では切らない
+        let emoji = "😀".repeat(200);
+        assert_eq!(sanitize_path_component(&emoji, FilenameTarget::Windows).chars().count(), 127);
+        assert_eq!(sanitize_path_component(&emoji, FilenameTarget::Posix).chars().count(), 63);
+        assert_eq!(sanitize_path_component(&"a".repeat(300), FilenameTarget::Windows).len(), 255);
+    }
+
+    #[test]
+    fn fit_file_name_keeps_the_prefix_suffix_and_extension() {
+        let stem = format!("01-03 {}", "あ".repeat(100));
+        for target in [FilenameTarget::Windows, FilenameTarget::Posix, FilenameTarget::Portable] {
+            let name = fit_file_name(&stem, " (2)", "flac", target);
+            assert!(name.starts_with("01-03 あ"), "{:?}", target);
+            assert!(name.ends_with("あ (2).flac"), "{:?}", target);
+            assert!(target.fits(&name), "{:?}", target);
+        }
+        // "01-03 " 6 バイト + "あ" 80 文字 240 バイト + " (2).flac" 9 バイト
+        assert_eq!(fit_file_name(&stem, " (2)", "flac", FilenameTarget::Posix).len(), 255);
+        // UTF-16 の上限には収まっているので切り詰めない
+        assert_eq!(fit_file_name(&stem, " (2)", "flac", FilenameTarget::Windows), format!("{} (2).flac", stem));
+    }
+
+    #[test]
+    fn number_pairs_in_the_usual_notations() {
+        assert_eq!(parse_number_pair("3/12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_pair(" 03 "), (Some(3), None));
+        assert_eq!(parse_number_pair("３／１２"), (Some(3), Some(12)));
+        assert_eq!(parse_number_pair("3 of 12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_pair("3 OF 12"), (Some(3), Some(12)));
+        // 総数 0 は総数なし
+        assert_eq!(parse_number_pair("3/0"), (Some(3), None));
+        assert_eq!(parse_number_pair("/12"), (None, Some(12)));
+        assert_eq!(parse_number_pair("A1"), (None, None));
+        assert_eq!(parse_number_pair(""), (None, None));
+    }
+
+    #[test]
+    fn number_pairs_are_formatted_only_with_a_total() {
+        assert_eq!(format_number_pair("3", Some(12)), "3/12");
+        assert_eq!(format_number_pair("3", Some(0)), "3");
+        assert_eq!(format_number_pair(" 3 ", None), "3");
+        assert_eq!(format_number_pair("", Some(12)), "");
+    }
+
+    #[test]
+    fn product_id_needs_a_boundary_before_the_prefix() {
+        assert_eq!(detect_product_id("RJ123456").as_deref(), Some("RJ123456"));
+        assert_eq!(detect_product_id("[rj01234567] 作品").as_deref(), Some("RJ01234567"));
+        assert_eq!(detect_product_id("作品_RJ123456").as_deref(), Some("RJ123456"));
+        assert_eq!(detect_product_id("XRJ123456"), None);
+        assert_eq!(detect_product_id("2RJ123456"), None);
+    }
+
+    #[test]
+    fn product_id_needs_a_boundary_after_the_digits() {
+        assert_eq!(detect_product_id("RJ123456a"), None);
+        assert_eq!(detect_product_id("RJ123456B"), None);
+        // 桁数が 6 / 8 以外になるものも一致させない
+        assert_eq!(detect_product_id("RJ1234567"), None);
+        assert_eq!(detect_product_id("RJ123456789"), None);
+        assert_eq!(detect_product_id("RJ123456_体験版").as_deref(), Some("RJ123456"));
+        assert_eq!(detect_product_id("RJ12345678.zip").as_deref(), Some("RJ12345678"));
+        // パスの区切りごとに探す
+        assert_eq!(detect_product_id("/music/RJ123456a/BJ01234567").as_deref(), Some("BJ01234567"));
+    }
+
+    #[test]
+    fn sniff_image_accepts_jpeg_only_with_its_signature() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+        assert_eq!(sniff_image(&jpeg).mime, "image/jpeg");
+        assert_eq!(sniff_image(b"\x89PNG\r\n\x1a\n").mime, "image/png");
+
+        // 先頭 2 バイトだけ一致するものや、どの形式でもないデータは JPEG にしない
+        for data in [&[0xFF, 0xD8, 0x00][..], b"II*\x00", b"", b"<svg"] {
+            let info = sniff_image(data);
+            assert_eq!(info.mime, UNKNOWN_IMAGE_MIME);
+            assert_eq!(info.extension, "bin");
+        }
+    }
+}
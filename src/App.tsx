@@ -37,6 +37,21 @@ interface Track {
   filePath?: string; // ファイルパスを追加
 }
 
+interface AlbumArt {
+  hash: string;
+  path: string;
+  mime: string;
+  width?: number | null;
+  height?: number | null;
+  size: number;
+}
+
+// 埋め込み画像（picture_type は APIC / FLAC の種別。3 = 表紙、4 = 裏表紙 など）
+interface EmbeddedPicture extends AlbumArt {
+  picture_type: number;
+  description: string;
+}
+
 interface AudioMetadata {
   title?: string;
   artist?: string;
@@ -44,6 +59,8 @@ interface AudioMetadata {
   album?: string;
   track_number?: string;
   disk_number?: string;
+  track_total?: number; // トラックの総数（"3/12" の 12）
+  disk_total?: number;
   date?: string;
   genre?: string;
   comment?: string;
@@ -51,8 +68,40 @@ interface AudioMetadata {
   bitrate?: string;
   sample_rate?: string;
   codec?: string;
-  album_art?: string; // base64 encoded
+  album_art?: AlbumArt | null; // キャッシュに保存された埋め込み画像
+  pictures?: EmbeddedPicture[]; // すべての埋め込み画像
   tags?: string[]; // TXXX tags
+  artists?: string[]; // 複数のアーティスト（artist を区切った値）
+  genres?: string[]; // 複数のジャンル
+  redecoded_fields?: RedecodedField[]; // Shift_JIS などから読み直した項目
+  product_id?: string; // PRODUCT_ID タグの商品 ID（RJ01234567 など）
+  composer?: string;
+  lyricist?: string;
+  illustrator?: string;
+  voice_actors?: string[]; // 声優（CV）
+  circle?: string; // サークル・レーベル
+  publisher?: string;
+  copyright?: string;
+  isrc?: string;
+  catalog_number?: string;
+  bpm?: string;
+  compilation?: boolean;
+  title_sort?: string;
+  artist_sort?: string;
+  album_sort?: string;
+  album_artist_sort?: string;
+  composer_sort?: string;
+}
+
+interface RedecodedField {
+  field: string;
+  encoding: string;
+  original: string;
+}
+
+interface ScannedAudioFile {
+  path: string;
+  product_id?: string | null; // パスから検出した商品 ID
 }
 
 interface AudioFileResult {
@@ -620,37 +669,19 @@ function App() {
           // ただし、フォルダ/ドロップに画像候補が含まれている場合は埋め込みを使わない。
           if (!hasExternalImageCandidate && !hasAlbumArt && metadata.album_art) {
             hasAlbumArt = true;
-            albumArtData = `data:image/jpeg;base64,${metadata.album_art}`;
-
-            // アルバムアートをキャッシュに保存
-            try {
-              const cachePath = await invoke<string>('save_album_art_to_cache', {
-                base64Data: metadata.album_art,
-                albumTitle: metadata.album || 'Unknown Album',
-                albumArtist: metadata.album_artist || 'Unknown Artist'
-              });
+            // 埋め込み画像はバックエンドで既にキャッシュに保存されているため、そのパスを参照する
+            const cachePath = metadata.album_art.path;
+            albumArtData = convertFileSrc(cachePath);
 
-              setAlbumData(prev => ({
-                ...prev,
-                albumArtwork: albumArtData,
-                albumArtworkCachePath: cachePath,
-                albumTitle: metadata.album || prev.albumTitle,
-                albumArtist: metadata.album_artist || prev.albumArtist,
-                releaseDate: metadata.date || prev.releaseDate
-              }));
-              hasAlbumInfo = true;
-            } catch (error) {
-              console.error('アルバムアートキャッシュ保存エラー:', error);
-              // キャッシュ保存に失敗してもアルバムアートは表示
-              setAlbumData(prev => ({
-                ...prev,
-                albumArtwork: albumArtData,
-                albumTitle: metadata.album || prev.albumTitle,
-                albumArtist: metadata.album_artist || prev.albumArtist,
-                releaseDate: metadata.date || prev.releaseDate
-              }));
-              hasAlbumInfo = true;
-            }
+            setAlbumData(prev => ({
+              ...prev,
+              albumArtwork: albumArtData,
+              albumArtworkCachePath: cachePath,
+              albumTitle: metadata.album || prev.albumTitle,
+              albumArtist: metadata.album_artist || prev.albumArtist,
+              releaseDate: metadata.date || prev.releaseDate
+            }));
+            hasAlbumInfo = true;
           }
 
           // アルバムアートが無い場合でも、最初の1回はアルバム情報を反映
@@ -860,10 +891,10 @@ function App() {
 
             for (const dirPath of directoryPaths) {
               try {
-                const files = await invoke<string[]>('scan_directory_for_audio_files', {
+                const files = await invoke<ScannedAudioFile[]>('scan_directory_for_audio_files', {
                   directoryPath: dirPath
                 });
-                directoryAudioFiles.push(...files);
+                directoryAudioFiles.push(...files.map((file) => file.path));
                 console.log(`Found ${files.length} audio files in ${dirPath}`);
                 // 画像もスキャン
                 try {
@@ -1340,10 +1371,10 @@ ${dirPath}
 
     for (const dirPath of directoryPaths) {
       try {
-        const files = await invoke<string[]>('scan_directory_for_audio_files', {
+        const files = await invoke<ScannedAudioFile[]>('scan_directory_for_audio_files', {
           directoryPath: dirPath
         });
-        directoryAudioFiles.push(...files);
+        directoryAudioFiles.push(...files.map((file) => file.path));
       } catch (error) {
         console.error(`Error scanning directory ${dirPath}:`, error);
         await confirm(`ディレクトリの処理中にエラーが発生しました: